@@ -28,9 +28,9 @@ fn main() {
 
     let mut trajectory = Trajectory::new();
 
-    trajectory.push_point(Coord3::new(0.0, 0.0, 0.0));
-    trajectory.push_point(Coord3::new(5.0, 0.0, 0.0));
-    trajectory.push_point(Coord3::new(5.0, 2.0, 0.0));
+    trajectory.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+    trajectory.push_point(Coord3::new(5.0, 0.0, 0.0)).unwrap();
+    trajectory.push_point(Coord3::new(5.0, 2.0, 0.0)).unwrap();
 
     // // Generate random points on every run
     // for _ in 0..10 {
@@ -71,12 +71,52 @@ fn main() {
                     line.q1().z,
                 )
             }
+            Item::SCurve(line) => {
+                println!(
+                    "SCurve   start {}, duration {} from [{}, {}, {}] -> [{}, {}, {}]",
+                    line.start_t,
+                    line.total_time,
+                    line.q0().x,
+                    line.q0().y,
+                    line.q0().z,
+                    line.q1().x,
+                    line.q1().y,
+                    line.q1().z,
+                )
+            }
             Item::ArcBlend(blend) => {
                 println!(
                     "ArcBlend start {}, duration {}, midpoint [{}, {}, {}]",
                     blend.start_t, blend.time, blend.mid.x, blend.mid.y, blend.mid.z,
                 )
             }
+            Item::ClothoidBlend(blend) => {
+                println!(
+                    "Clothoid start {}, duration {}, midpoint [{}, {}, {}]",
+                    blend.start_t, blend.time, blend.mid.x, blend.mid.y, blend.mid.z,
+                )
+            }
+            Item::BezierBlend(blend) => {
+                println!(
+                    "Bezier   start {}, duration {}, midpoint [{}, {}, {}]",
+                    blend.start_t, blend.time, blend.mid.x, blend.mid.y, blend.mid.z,
+                )
+            }
+            Item::Arc(arc) => {
+                println!(
+                    "Arc      start {}, duration {}, center [{}, {}, {}]",
+                    arc.start_t, arc.time, arc.center.x, arc.center.y, arc.center.z,
+                )
+            }
+            Item::Spline(spline) => {
+                println!(
+                    "Spline   start {}, duration {}, degree {}, {} control points",
+                    spline.start_t,
+                    spline.time,
+                    spline.degree,
+                    spline.control_points.len(),
+                )
+            }
         }
     }
 
@@ -135,10 +175,11 @@ fn main() {
             window.draw_line(&start, &end, &Point3::new(1.0, 1.0, 1.0));
         }
 
-        let lines = state.trajectory.items.iter().filter_map(|item| match item {
-            Item::Linear(line) => Some(line),
-            Item::ArcBlend(_) => None,
-        });
+        let lines = state
+            .trajectory
+            .items
+            .iter()
+            .filter(|item| item.as_blend().is_none());
 
         // Draw straight line segments between blends. Commented out for now as we want to draw the
         // lines using the TP output.
@@ -153,8 +194,8 @@ fn main() {
         }
 
         let blends = state.trajectory.items.iter().filter_map(|item| match item {
-            Item::Linear(_) => None,
             Item::ArcBlend(blend) => Some(blend),
+            _ => None,
         });
 
         // Draw blend arcs using a bunch of line segments for each one
@@ -29,7 +29,9 @@ fn main() {
     let mut trajectory = Trajectory::new();
 
     for _ in 0..10 {
-        trajectory.push_point((Coord3::new_random() * range).map(|axis| axis - (range / 2.0)));
+        trajectory
+            .push_point((Coord3::new_random() * range).map(|axis| axis - (range / 2.0)))
+            .unwrap();
     }
 
     let mut window = Window::new("Spaghetti!");
@@ -93,8 +95,8 @@ fn main() {
         }
 
         for blend in state.trajectory.items.iter().filter_map(|item| match item {
-            Item::Linear(_) => None,
             Item::ArcBlend(blend) => Some(blend),
+            _ => None,
         }) {
             let mut prev_point =
                 Point3::new(blend.arc_start.x, blend.arc_start.y, blend.arc_start.z);
@@ -11,7 +11,8 @@ use env_logger::Env;
 use nalgebra::{Point3, Translation3, UnitQuaternion, Vector3};
 use std::{path::PathBuf, sync::Arc, thread, time::Duration};
 use tp::arc_blend::Coord3;
-use tp::segments_blends::{Item, Trajectory};
+use tp::profile::Profile;
+use tp::segments_blends::Trajectory;
 use tp::trapezoidal_non_zero_3d::{Lim, Out};
 
 struct MyApp {
@@ -104,14 +105,10 @@ impl MyApp {
                     .trajectory
                     .items
                     .iter()
-                    .map(|item| match item {
-                        Item::Linear(line) => [
-                            (line.start_t, false),
-                            (line.start_t + line.total_time, false),
-                        ],
-                        Item::ArcBlend(blend) => {
-                            [(blend.start_t, true), (blend.start_t + blend.time, true)]
-                        }
+                    .map(|item| {
+                        let is_arc = item.as_blend().is_some();
+
+                        [(item.start_t(), is_arc), (item.end_t(), is_arc)]
                     })
                     .flatten()
                     .collect::<Vec<_>>();
@@ -340,9 +337,9 @@ fn main() -> Result<(), eframe::Error> {
 
     let mut trajectory = Trajectory::new();
 
-    trajectory.push_point(Coord3::new(0.0, 0.0, 0.0));
-    trajectory.push_point(Coord3::new(5.0, 0.0, 0.0));
-    trajectory.push_point(Coord3::new(5.0, 2.0, 0.0));
+    trajectory.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+    trajectory.push_point(Coord3::new(5.0, 0.0, 0.0)).unwrap();
+    trajectory.push_point(Coord3::new(5.0, 2.0, 0.0)).unwrap();
 
     log::info!("Duration {}", trajectory.total_time);
 
@@ -0,0 +1,780 @@
+//! Programmed circular arcs, as from `G2` and `G3` moves.
+//!
+//! An arc turns around its center in one of three [`Plane`]s. Any axes outside the plane move
+//! linearly with the angle turned, making a helix. The distance from the center can also change
+//! linearly with the angle to make a spiral.
+//!
+//! Speed along the arc follows a trapezoidal profile. Its limits are found per axis from the
+//! direction and curvature of the path at samples along it, with half of the acceleration limit of
+//! each turning axis left for the centripetal acceleration of going round.
+
+use alloc::vec::Vec;
+
+use crate::{
+    bezier_blend::GAUSS_LEGENDRE,
+    kinematics::{self, Kinematics, LimitError},
+    profile::Profile,
+    real::Real,
+    trapezoidal_non_zero::{self, Segment},
+    trapezoidal_non_zero_3d::{Coord, Lim, Out},
+};
+
+pub use crate::trapezoidal_non_zero_3d::Coord3;
+
+/// Largest difference between the distances of the start and end points from the center, as a
+/// proportion of the radius.
+const RADIUS_TOLERANCE: f64 = 1.0e-3;
+
+/// Number of intervals in the arc length table of a spiral.
+const TABLE_LEN: usize = 32;
+
+/// Number of samples per quarter turn when finding the limits of an arc.
+const LIMIT_SAMPLES: usize = 32;
+
+/// Plane an arc turns in. Counterclockwise is from the first axis of the plane towards the second,
+/// looking down the normal axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Plane {
+    /// `G17`
+    #[default]
+    Xy,
+    /// `G18`
+    Zx,
+    /// `G19`
+    Yz,
+}
+
+impl Plane {
+    /// Indices of the first, second and normal axes of this plane.
+    pub fn axes(&self) -> (usize, usize, usize) {
+        match self {
+            Self::Xy => (0, 1, 2),
+            Self::Zx => (2, 0, 1),
+            Self::Yz => (1, 2, 0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// `G2`
+    Clockwise,
+    /// `G3`
+    Counterclockwise,
+}
+
+/// Center of an arc.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArcCenter<const N: usize = 3, T: Real = f32> {
+    /// Position of the center. Only the axes in the plane of the arc are used. Full circles end at
+    /// the same point they start at.
+    Point(Coord<N, T>),
+    /// Radius of the arc. A positive radius takes the shorter way round to the end point, and a
+    /// negative radius the longer way. Full circles can't be programmed this way.
+    Radius(T),
+    /// Position of the center of a spiral. The distance from the center changes linearly with the
+    /// angle turned, from that of the start point to that of the end point. Either can be zero to
+    /// spiral in to or out from the center.
+    Spiral(Coord<N, T>),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Arc<const N: usize = 3, T: Real = f32> {
+    pub start: Coord<N, T>,
+    pub end: Coord<N, T>,
+    /// Center of the arc. Only the axes in the plane of the arc are meaningful.
+    pub center: Coord<N, T>,
+    pub plane: Plane,
+    /// Distance of the start point from the center.
+    pub radius: T,
+    /// Distance of the end point from the center. Only differs from `radius` for spirals.
+    pub end_radius: T,
+    /// Angle of the start point around the center, from the first axis of the plane.
+    pub start_angle: T,
+    /// Angle turned from the start to the end point, positive counterclockwise. Every extra full
+    /// turn adds `2π`.
+    pub sweep: T,
+    /// Length of the path, including any helical motion.
+    pub len: T,
+    /// Fastest speed along the path.
+    pub velocity_limit: T,
+    /// Fastest acceleration or deceleration along the path.
+    pub acceleration_limit: T,
+    lim: Lim<N, T>,
+    /// Length of a spiral at each of `TABLE_LEN` equal steps of the angle turned.
+    lengths: [T; TABLE_LEN + 1],
+    /// Distance along the path over time.
+    profile: Segment<T>,
+    /// Speed along the path at its start.
+    pub v0: T,
+    /// Speed along the path at its end.
+    pub v1: T,
+    pub time: T,
+    pub start_t: T,
+}
+
+impl<const N: usize, T: Real> Arc<N, T> {
+    /// Arc from `start` to `end`, making `turns` extra full turns on the way. The arc starts and
+    /// finishes at rest at time zero until replanned with [`set_speeds`](Self::set_speeds).
+    ///
+    /// An end point slightly off the circle spirals onto it, so the arc finishes exactly where
+    /// programmed. Returns `None` if `end` is further off than that, or a radius is too small to
+    /// reach `end`.
+    pub fn new(
+        start: Coord<N, T>,
+        end: Coord<N, T>,
+        center: ArcCenter<N, T>,
+        plane: Plane,
+        direction: Direction,
+        turns: usize,
+        lim: &Lim<N, T>,
+    ) -> Option<Self> {
+        let (a, b, _) = plane.axes();
+        let two = T::lit(2.0);
+        let ccw = direction == Direction::Counterclockwise;
+
+        let (center_a, center_b) = match center {
+            ArcCenter::Point(center) | ArcCenter::Spiral(center) => (center[a], center[b]),
+            ArcCenter::Radius(radius) => {
+                let chord_a = end[a] - start[a];
+                let chord_b = end[b] - start[b];
+                let chord_len = (chord_a.powi(2) + chord_b.powi(2)).sqrt();
+
+                if chord_len == T::zero() {
+                    return None;
+                }
+
+                let half_chord = chord_len / two;
+
+                if radius.abs() * (T::one() + T::lit(RADIUS_TOLERANCE)) < half_chord {
+                    return None;
+                }
+
+                // Distance from chord midpoint to the center
+                let height = (radius.powi(2) - half_chord.powi(2)).max(T::zero()).sqrt();
+
+                // Center is to the left of the chord for CCW arcs shorter than a semicircle. A
+                // negative radius selects the longer arc, which puts the center on the other side.
+                let side = if ccw == (radius > T::zero()) {
+                    T::one()
+                } else {
+                    -T::one()
+                };
+
+                (
+                    start[a] + chord_a / two - side * height * chord_b / chord_len,
+                    start[b] + chord_b / two + side * height * chord_a / chord_len,
+                )
+            }
+        };
+
+        let radius = ((start[a] - center_a).powi(2) + (start[b] - center_b).powi(2)).sqrt();
+        let end_radius = ((end[a] - center_a).powi(2) + (end[b] - center_b).powi(2)).sqrt();
+
+        if let ArcCenter::Spiral(_) = center {
+            if radius == T::zero() && end_radius == T::zero() {
+                return None;
+            }
+        } else if radius == T::zero()
+            || (radius - end_radius).abs() > radius * T::lit(RADIUS_TOLERANCE)
+        {
+            return None;
+        }
+
+        let start_angle = (start[b] - center_b).atan2(start[a] - center_a);
+        let end_angle = (end[b] - center_b).atan2(end[a] - center_a);
+
+        // A spiral starting or finishing at the center heads straight out from or in to it
+        let (start_angle, end_angle) = if radius == T::zero() {
+            (end_angle, end_angle)
+        } else if end_radius == T::zero() {
+            (start_angle, start_angle)
+        } else {
+            (start_angle, end_angle)
+        };
+
+        let full_turn = two * T::pi();
+        let turns = full_turn * T::lit(turns as f64);
+
+        // Identical start and end angles are a full turn
+        let sweep = end_angle - start_angle;
+
+        let sweep = if ccw {
+            if sweep <= T::zero() {
+                sweep + full_turn
+            } else {
+                sweep
+            }
+        } else if sweep >= T::zero() {
+            sweep - full_turn
+        } else {
+            sweep
+        };
+
+        let sweep = if ccw { sweep + turns } else { sweep - turns };
+
+        let mut center = start;
+
+        center[a] = center_a;
+        center[b] = center_b;
+
+        let mut arc = Self {
+            start,
+            end,
+            center,
+            plane,
+            radius,
+            end_radius,
+            start_angle,
+            sweep,
+            len: T::zero(),
+            velocity_limit: T::zero(),
+            acceleration_limit: T::zero(),
+            lim: *lim,
+            lengths: [T::zero(); TABLE_LEN + 1],
+            profile: Segment::default(),
+            v0: T::zero(),
+            v1: T::zero(),
+            time: T::zero(),
+            start_t: T::zero(),
+        };
+
+        arc.measure();
+        arc.find_limits();
+        arc.set_speeds(T::zero(), T::zero());
+
+        Some(arc)
+    }
+
+    /// Motion of the axes outside the plane from the start to the end of the arc.
+    fn helix(&self) -> Coord<N, T> {
+        let (a, b, _) = self.plane.axes();
+
+        let mut helix = self.end - self.start;
+
+        helix[a] = T::zero();
+        helix[b] = T::zero();
+
+        helix
+    }
+
+    /// Whether the distance from the center changes along the arc.
+    pub fn is_spiral(&self) -> bool {
+        self.radius != self.end_radius
+    }
+
+    /// Position and its first and second derivatives at proportion `u` of the angle turned.
+    fn derivatives(&self, u: T) -> (Coord<N, T>, Coord<N, T>, Coord<N, T>) {
+        let (a, b, _) = self.plane.axes();
+        let two = T::lit(2.0);
+
+        let growth = self.end_radius - self.radius;
+        let radius = self.radius + growth * u;
+        let (sin, cos) = (self.start_angle + self.sweep * u).sin_cos();
+
+        let helix = self.helix();
+
+        let mut pos = self.start + helix * u;
+        let mut d1 = helix;
+        let mut d2 = Coord::zeros();
+
+        pos[a] = self.center[a] + radius * cos;
+        pos[b] = self.center[b] + radius * sin;
+
+        d1[a] = growth * cos - radius * self.sweep * sin;
+        d1[b] = growth * sin + radius * self.sweep * cos;
+
+        d2[a] = -two * growth * self.sweep * sin - radius * self.sweep.powi(2) * cos;
+        d2[b] = two * growth * self.sweep * cos - radius * self.sweep.powi(2) * sin;
+
+        (pos, d1, d2)
+    }
+
+    /// Position, unit tangent and curvature vector at proportion `u` of the angle turned.
+    fn frame(&self, u: T) -> (Coord<N, T>, Coord<N, T>, Coord<N, T>) {
+        let (pos, d1, d2) = self.derivatives(u);
+
+        let speed_squared = d1.norm_squared();
+
+        // Remove the component along the tangent, which only changes the parameterisation
+        let curvature = (d2 - d1 * (d1.dot(&d2) / speed_squared)) / speed_squared;
+
+        (pos, d1 / speed_squared.sqrt(), curvature)
+    }
+
+    /// Length of the arc between proportions `u0` and `u1` of the angle turned.
+    fn integrate_speed(&self, u0: T, u1: T) -> T {
+        let half = (u1 - u0) / T::lit(2.0);
+        let center = (u0 + u1) / T::lit(2.0);
+
+        GAUSS_LEGENDRE
+            .iter()
+            .fold(T::zero(), |sum, (node, weight)| {
+                sum + T::lit(*weight) * self.derivatives(center + half * T::lit(*node)).1.norm()
+            })
+            * half
+    }
+
+    /// Update the length of the arc, and the table of lengths along it for a spiral.
+    fn measure(&mut self) {
+        if !self.is_spiral() {
+            self.len = ((self.radius * self.sweep).powi(2) + self.helix().norm_squared()).sqrt();
+
+            return;
+        }
+
+        let table_len = T::lit(TABLE_LEN as f64);
+
+        for i in 0..TABLE_LEN {
+            let (u0, u1) = (
+                T::lit(i as f64) / table_len,
+                T::lit((i + 1) as f64) / table_len,
+            );
+
+            self.lengths[i + 1] = self.lengths[i] + self.integrate_speed(u0, u1);
+        }
+
+        self.len = self.lengths[TABLE_LEN];
+    }
+
+    /// Proportion of the angle turned at distance `s` along the arc. The spacing is even except
+    /// for spirals, where the table gives a first guess which is refined with Newton's method.
+    fn param(&self, s: T) -> T {
+        let s = s.max(T::zero()).min(self.len);
+
+        if !self.is_spiral() {
+            return s / self.len;
+        }
+
+        let i = self
+            .lengths
+            .partition_point(|len| *len <= s)
+            .clamp(1, TABLE_LEN)
+            - 1;
+
+        let table_len = T::lit(TABLE_LEN as f64);
+
+        let (u0, u1) = (
+            T::lit(i as f64) / table_len,
+            T::lit((i + 1) as f64) / table_len,
+        );
+        let (len0, len1) = (self.lengths[i], self.lengths[i + 1]);
+
+        let mut u = u0 + (u1 - u0) * (s - len0) / (len1 - len0);
+
+        let tolerance = T::default_epsilon() * T::lit(16.0) * (len1 - len0);
+
+        for _ in 0..8 {
+            let error = len0 + self.integrate_speed(u0, u) - s;
+
+            if error.abs() <= tolerance {
+                break;
+            }
+
+            u = (u - error / self.derivatives(u).1.norm()).max(u0).min(u1);
+        }
+
+        u
+    }
+
+    /// Find the fastest speed and acceleration along the arc that keep every axis within its
+    /// limits. The direction and curvature of the axes in the plane peak every quarter turn on a
+    /// circle, so those angles are always sampled along with evenly spaced ones between them.
+    fn find_limits(&mut self) {
+        let (a, b, _) = self.plane.axes();
+        let two = T::lit(2.0);
+        let quarter = T::pi() / two;
+
+        let turned = self.sweep.abs();
+
+        let mut max_tangent = Coord::<N, T>::zeros();
+        let mut max_curvature = Coord::<N, T>::zeros();
+
+        let mut sample = |angle: T| {
+            let (_, tangent, curvature) = self.frame(angle / turned);
+
+            max_tangent = max_tangent.sup(&tangent.abs());
+            max_curvature = max_curvature.sup(&curvature.abs());
+        };
+
+        // Angle turned to the first quarter turn
+        let first = -self.sweep.signum() * self.start_angle;
+        let mut to = first - (first / quarter).floor() * quarter;
+        let mut from = T::zero();
+
+        loop {
+            to = to.min(turned);
+
+            for i in 0..LIMIT_SAMPLES {
+                sample(from + (to - from) * T::lit(i as f64) / T::lit(LIMIT_SAMPLES as f64));
+            }
+
+            if to >= turned {
+                break;
+            }
+
+            from = to;
+            to += quarter;
+        }
+
+        sample(turned);
+
+        self.velocity_limit = T::lit(f64::INFINITY);
+        self.acceleration_limit = T::lit(f64::INFINITY);
+
+        for axis in 0..N {
+            // Axes going round the arc leave half their acceleration for going round. Only the
+            // axes in the plane do unless the arc is a spiral, which curves the helix too.
+            let turning = axis == a || axis == b || self.is_spiral();
+
+            let share = if turning { two } else { T::one() };
+
+            if max_tangent[axis] > T::zero() {
+                self.velocity_limit = self
+                    .velocity_limit
+                    .min(self.lim.vel[axis] / max_tangent[axis]);
+                self.acceleration_limit = self
+                    .acceleration_limit
+                    .min(self.lim.acc[axis] / (share * max_tangent[axis]));
+            }
+
+            if turning && max_curvature[axis] > T::zero() {
+                self.velocity_limit = self
+                    .velocity_limit
+                    .min((self.lim.acc[axis] / (two * max_curvature[axis])).sqrt());
+            }
+        }
+    }
+
+    pub fn lim(&self) -> Lim<N, T> {
+        self.lim
+    }
+
+    /// Fastest speed anywhere along the arc.
+    pub fn max_speed(&self) -> T {
+        self.velocity_limit
+    }
+
+    /// Change speed from `v0` at the start of the arc to `v1` at its end, as fast as the limits
+    /// allow in between, updating its duration. The two speeds must be reachable from each other
+    /// within the length of the arc.
+    pub fn set_speeds(&mut self, v0: T, v1: T) {
+        self.v0 = v0;
+        self.v1 = v1;
+
+        self.profile = Segment::new(
+            T::zero(),
+            self.len,
+            v0,
+            v1,
+            &trapezoidal_non_zero::Lim {
+                vel: self.velocity_limit,
+                acc: self.acceleration_limit,
+            },
+        );
+        self.time = self.profile.t;
+    }
+
+    /// Position, unit tangent and curvature vector at distance `s` along the arc.
+    pub fn geometry(&self, s: T) -> (Coord<N, T>, Coord<N, T>, Coord<N, T>) {
+        self.frame(self.param(s))
+    }
+
+    /// Cut `len` off the start of the arc, keeping its speeds and limits.
+    pub fn trim_start(&mut self, len: T) {
+        let u = self.param(len);
+
+        self.start = self.derivatives(u).0;
+        self.radius += (self.end_radius - self.radius) * u;
+        self.start_angle += self.sweep * u;
+        self.sweep -= self.sweep * u;
+
+        self.measure();
+        self.set_speeds(self.v0, self.v1);
+    }
+
+    /// Cut `len` off the end of the arc, keeping its speeds and limits.
+    pub fn trim_end(&mut self, len: T) {
+        let u = self.param(self.len - len);
+
+        self.end = self.derivatives(u).0;
+        self.end_radius = self.radius + (self.end_radius - self.radius) * u;
+        self.sweep *= u;
+
+        self.measure();
+        self.set_speeds(self.v0, self.v1);
+    }
+
+    /// Get trajectory parameters at the given time `t`.
+    pub fn tp(&self, t: T) -> Option<Out<N, T>> {
+        let t = t - self.start_t;
+
+        if t >= self.time || t < T::zero() {
+            return None;
+        }
+
+        let path = self.profile.tp(t)?;
+
+        let (pos, tangent, curvature) = self.geometry(path.pos);
+
+        Some(Out {
+            pos,
+            vel: tangent * path.vel,
+            acc: tangent * path.acc + curvature * path.vel.powi(2),
+        })
+    }
+
+    /// Check this arc against the joint limits of the given kinematics, sampling every `dt`
+    /// seconds.
+    pub fn check_joint_limits<K: Kinematics<N, T> + ?Sized>(
+        &self,
+        kinematics: &K,
+        dt: T,
+    ) -> Result<(), LimitError<N, T>> {
+        kinematics::check_limits(
+            kinematics,
+            self.start_t,
+            self.start_t + self.time,
+            dt,
+            |t| self.tp(t),
+        )
+    }
+}
+
+impl<const N: usize, T: Real> Profile<T> for Arc<N, T> {
+    type Out = Out<N, T>;
+
+    fn start_t(&self) -> T {
+        self.start_t
+    }
+
+    fn duration(&self) -> T {
+        self.time
+    }
+
+    fn tp(&self, t: T) -> Option<Out<N, T>> {
+        Arc::tp(self, t)
+    }
+
+    fn phase_times(&self) -> Vec<T> {
+        // The speed profile starts at zero
+        self.profile
+            .phase_times()
+            .into_iter()
+            .map(|t| self.start_t + t)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_cmp::assert_approx_eq;
+
+    fn lim() -> Lim {
+        Lim {
+            vel: Coord3::new(2.0, 2.0, 2.0),
+            acc: Coord3::new(5.0, 5.0, 5.0),
+        }
+    }
+
+    #[test]
+    fn center_format() {
+        // Half circle clockwise over the top of (1, 0)
+        let arc = Arc::new(
+            Coord3::new(0.0, 0.0, 0.0),
+            Coord3::new(2.0, 0.0, 0.0),
+            ArcCenter::Point(Coord3::new(1.0, 0.0, 0.0)),
+            Plane::Xy,
+            Direction::Clockwise,
+            0,
+            &lim(),
+        )
+        .unwrap();
+
+        assert_approx_eq!(f32, arc.sweep, -core::f32::consts::PI);
+        assert_approx_eq!(f32, arc.len, core::f32::consts::PI);
+        assert!((arc.geometry(arc.len / 2.0).0 - Coord3::new(1.0, 1.0, 0.0)).norm() < 1.0e-6);
+    }
+
+    #[test]
+    fn radius_format() {
+        let short = Arc::new(
+            Coord3::new(0.0, 0.0, 0.0),
+            Coord3::new(1.0, 1.0, 0.0),
+            ArcCenter::Radius(1.0),
+            Plane::Xy,
+            Direction::Counterclockwise,
+            0,
+            &lim(),
+        )
+        .unwrap();
+
+        let long = Arc::new(
+            Coord3::new(0.0, 0.0, 0.0),
+            Coord3::new(1.0, 1.0, 0.0),
+            ArcCenter::Radius(-1.0),
+            Plane::Xy,
+            Direction::Counterclockwise,
+            0,
+            &lim(),
+        )
+        .unwrap();
+
+        // Shorter way round bulges towards positive X
+        assert!((short.center - Coord3::new(0.0, 1.0, 0.0)).norm() < 1.0e-6);
+        assert!((long.center - Coord3::new(1.0, 0.0, 0.0)).norm() < 1.0e-6);
+        assert_approx_eq!(f32, short.sweep, core::f32::consts::FRAC_PI_2);
+        assert_approx_eq!(f32, long.sweep, 3.0 * core::f32::consts::FRAC_PI_2);
+
+        // Too small to reach the end point
+        assert!(Arc::new(
+            Coord3::new(0.0, 0.0, 0.0),
+            Coord3::new(1.0, 1.0, 0.0),
+            ArcCenter::Radius(0.5),
+            Plane::Xy,
+            Direction::Counterclockwise,
+            0,
+            &lim(),
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn full_circle_helix() {
+        // Two turns in the ZX plane, climbing 1 along Y
+        let mut arc = Arc::new(
+            Coord3::new(1.0, 0.0, 0.0),
+            Coord3::new(1.0, 1.0, 0.0),
+            ArcCenter::Point(Coord3::zeros()),
+            Plane::Zx,
+            Direction::Counterclockwise,
+            1,
+            &lim(),
+        )
+        .unwrap();
+
+        assert_approx_eq!(f32, arc.sweep, 4.0 * core::f32::consts::PI);
+        assert_approx_eq!(
+            f32,
+            arc.len,
+            (16.0 * core::f32::consts::PI.powi(2) + 1.0).sqrt()
+        );
+
+        arc.start_t = 1.0;
+        arc.set_speeds(0.0, 0.0);
+
+        let dt = 0.001;
+
+        let mut prev = arc.tp(1.0).unwrap();
+        let mut t = 1.0 + dt;
+
+        // Counterclockwise in ZX is from Z towards X, so carries on from X towards -Z
+        assert!(arc.tp(1.1).unwrap().pos.x < 1.0 && arc.tp(1.1).unwrap().pos.z < 0.0);
+
+        while let Some(out) = arc.tp(t) {
+            assert_approx_eq!(
+                f32,
+                (out.pos.x.powi(2) + out.pos.z.powi(2)).sqrt(),
+                1.0,
+                epsilon = 1.0e-5
+            );
+
+            // Derivatives match the motion
+            assert!(((out.pos - prev.pos) / dt - (out.vel + prev.vel) / 2.0).norm() < 1.0e-2);
+
+            assert!(out.vel.abs() <= lim().vel * 1.001, "{t}: {}", out.vel);
+            assert!(out.acc.abs() <= lim().acc * 1.001, "{t}: {}", out.acc);
+
+            prev = out;
+            t += dt;
+        }
+
+        assert!((prev.pos - Coord3::new(1.0, 1.0, 0.0)).norm() < 1.0e-3);
+        assert!(prev.vel.norm() < 1.0e-2);
+    }
+
+    #[test]
+    fn per_axis_limits() {
+        let lim = Lim {
+            vel: Coord::<3, f64>::new(1.0, 2.0, 0.1),
+            acc: Coord::<3, f64>::new(5.0, 5.0, 5.0),
+        };
+
+        let (sin, cos) = core::f64::consts::FRAC_PI_4.sin_cos();
+
+        // Quarter turn either side of the X axis, climbing 1 along Z
+        let arc = Arc::new(
+            Coord::<3, f64>::new(10.0 * cos, -10.0 * sin, 0.0),
+            Coord::<3, f64>::new(10.0 * cos, 10.0 * sin, 1.0),
+            ArcCenter::Point(Coord::<3, f64>::zeros()),
+            Plane::Xy,
+            Direction::Counterclockwise,
+            0,
+            &lim,
+        )
+        .unwrap();
+
+        let in_plane = 10.0 * core::f64::consts::FRAC_PI_2 / arc.len;
+
+        // X never moves faster than at the ends of the arc, and Y only reaches full speed half way
+        assert_approx_eq!(
+            f64,
+            arc.velocity_limit,
+            1.0 / (in_plane * sin),
+            epsilon = 1.0e-9
+        );
+        assert_approx_eq!(
+            f64,
+            arc.acceleration_limit,
+            5.0 / (2.0 * in_plane),
+            epsilon = 1.0e-9
+        );
+    }
+
+    #[test]
+    fn spiral() {
+        // Two turns from radius 1 out to 3, climbing 1 along Z
+        let mut arc = Arc::new(
+            Coord3::new(1.0, 0.0, 0.0),
+            Coord3::new(3.0, 0.0, 1.0),
+            ArcCenter::Spiral(Coord3::zeros()),
+            Plane::Xy,
+            Direction::Counterclockwise,
+            1,
+            &lim(),
+        )
+        .unwrap();
+
+        assert!(arc.is_spiral());
+        assert_approx_eq!(f32, arc.sweep, 4.0 * core::f32::consts::PI);
+
+        arc.set_speeds(0.0, 0.0);
+
+        let dt = 0.001;
+
+        let mut prev = arc.tp(0.0).unwrap();
+        let mut t = dt;
+
+        while let Some(out) = arc.tp(t) {
+            // Radius and height both change linearly with the angle turned
+            assert_approx_eq!(
+                f32,
+                (out.pos.x.powi(2) + out.pos.y.powi(2)).sqrt(),
+                1.0 + 2.0 * out.pos.z,
+                epsilon = 1.0e-4
+            );
+
+            assert!(((out.pos - prev.pos) / dt - (out.vel + prev.vel) / 2.0).norm() < 1.0e-2);
+
+            assert!(out.vel.abs() <= lim().vel * 1.001, "{t}: {}", out.vel);
+            assert!(out.acc.abs() <= lim().acc * 1.001, "{t}: {}", out.acc);
+
+            prev = out;
+            t += dt;
+        }
+
+        assert!((prev.pos - Coord3::new(3.0, 0.0, 1.0)).norm() < 1.0e-3);
+        assert!(prev.vel.norm() < 1.0e-2);
+    }
+}
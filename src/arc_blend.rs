@@ -5,47 +5,86 @@
 //!
 //! - Start and end points have discontinuous acceleration.
 
-use crate::trapezoidal_non_zero_3d::{Lim, Out};
-use nalgebra::Vector3;
-
-pub type Coord3 = Vector3<f32>;
-
-#[derive(Debug, Copy, Clone, Default)]
-pub struct ArcBlend {
-    pub prev: Coord3,
-    pub mid: Coord3,
-    pub next: Coord3,
-    pub max_deviation: f32,
-
-    pub arc_start: Coord3,
-    pub arc_center: Coord3,
-    pub arc_radius: f32,
-    pub arc_end: Coord3,
-    pub arc_len: f32,
-    pub velocity_limit: Coord3,
-    pub time: f32,
-    pub start_t: f32,
+use alloc::vec::Vec;
+
+use crate::{
+    blend::Blend,
+    kinematics::{self, Kinematics, LimitError},
+    profile::Profile,
+    real::Real,
+    trapezoidal_non_zero_3d::{Coord, Lim, Out},
+};
+
+pub use crate::trapezoidal_non_zero_3d::Coord3;
+
+#[derive(Debug, Copy, Clone)]
+pub struct ArcBlend<const N: usize = 3, T: Real = f32> {
+    pub prev: Coord<N, T>,
+    pub mid: Coord<N, T>,
+    pub next: Coord<N, T>,
+    pub max_deviation: T,
+
+    pub arc_start: Coord<N, T>,
+    pub arc_center: Coord<N, T>,
+    pub arc_radius: T,
+    pub arc_end: Coord<N, T>,
+    pub arc_len: T,
+    pub velocity_limit: Coord<N, T>,
+    pub acceleration_limit: Coord<N, T>,
+    /// Speed along the arc at its start.
+    pub v0: T,
+    /// Speed along the arc at its end.
+    pub v1: T,
+    pub time: T,
+    pub start_t: T,
     pub is_colinear: bool,
     // Actual deviation from the midpoint
     // deviation: f32
 }
 
-impl ArcBlend {
+impl<const N: usize, T: Real> Default for ArcBlend<N, T> {
+    fn default() -> Self {
+        Self {
+            prev: Coord::zeros(),
+            mid: Coord::zeros(),
+            next: Coord::zeros(),
+            max_deviation: T::zero(),
+            arc_start: Coord::zeros(),
+            arc_center: Coord::zeros(),
+            arc_radius: T::zero(),
+            arc_end: Coord::zeros(),
+            arc_len: T::zero(),
+            velocity_limit: Coord::zeros(),
+            acceleration_limit: Coord::zeros(),
+            v0: T::zero(),
+            v1: T::zero(),
+            time: T::zero(),
+            start_t: T::zero(),
+            is_colinear: false,
+        }
+    }
+}
+
+impl<const N: usize, T: Real> ArcBlend<N, T> {
     pub fn new(
-        prev: Coord3,
-        mid: Coord3,
-        next: Coord3,
-        max_deviation: f32,
-        start_t: f32,
+        prev: Coord<N, T>,
+        mid: Coord<N, T>,
+        next: Coord<N, T>,
+        max_deviation: T,
+        start_t: T,
         Lim {
             acc: max_acceleration,
             vel: max_velocity,
-        }: Lim,
+        }: Lim<N, T>,
     ) -> Self {
         let v1 = mid - prev;
         let v2 = next - mid;
 
-        let is_colinear = v1.cross(&v2).norm_squared() <= f32::EPSILON;
+        // Squared norm of the cross product of `v1` and `v2` by Lagrange's identity, so this works
+        // for any number of axes.
+        let cross_norm_squared = v1.norm_squared() * v2.norm_squared() - v1.dot(&v2).powi(2);
+
+        let is_colinear = cross_norm_squared <= T::default_epsilon();
 
         if is_colinear {
             return Self {
@@ -55,25 +94,30 @@ impl ArcBlend {
                 max_deviation,
                 arc_start: mid,
                 arc_center: mid,
-                arc_radius: 0.0,
+                arc_radius: T::zero(),
                 arc_end: mid,
-                arc_len: 0.0,
+                arc_len: T::zero(),
                 velocity_limit: max_velocity,
-                time: 0.0,
+                acceleration_limit: max_acceleration,
+                v0: max_velocity.min(),
+                v1: max_velocity.min(),
+                time: T::zero(),
                 start_t,
                 is_colinear,
             };
         }
 
+        let two = T::lit(2.0);
+
         // Qi
-        let prev_delta: Vector3<f32> = mid - prev;
+        let prev_delta: Coord<N, T> = mid - prev;
         // Qi+1
-        let next_delta: Vector3<f32> = next - mid;
+        let next_delta: Coord<N, T> = next - mid;
 
         // Yi
-        let prev_delta_norm: Vector3<f32> = prev_delta.normalize();
+        let prev_delta_norm: Coord<N, T> = prev_delta.normalize();
         // Yi+1
-        let next_delta_norm: Vector3<f32> = next_delta.normalize();
+        let next_delta_norm: Coord<N, T> = next_delta.normalize();
 
         // Lengths of both line segments
         let prev_len = prev_delta.norm();
@@ -82,16 +126,16 @@ impl ArcBlend {
         // ⍺i: Outside angle between segments in radians
         let outside_angle = prev_delta.angle(&next_delta);
 
-        let half_angle = outside_angle / 2.0;
+        let half_angle = outside_angle / two;
 
         // Li: The maximum arc radius that is within the maximum deviation from the midpoint
         let deviation_limit_max_radius =
-            (max_deviation * half_angle.sin()) / (1.0 - half_angle.cos());
+            (max_deviation * half_angle.sin()) / (T::one() - half_angle.cos());
 
         // Arc may at most contain half of the smallest path segment, or be a maximum distance
         // away from the midpoint, specified by the given configuration.
-        let radius_limit = (prev_len / 2.0)
-            .min(next_len / 2.0)
+        let radius_limit = (prev_len / two)
+            .min(next_len / two)
             .min(deviation_limit_max_radius);
 
         // Ri
@@ -104,7 +148,7 @@ impl ArcBlend {
         // Xi
         let start_point = {
             // Xi: Vector pointing from arc center to start point
-            let x_i = (mid - radius_limit * prev_delta_norm - arc_center).normalize();
+            let x_i = (mid - prev_delta_norm * radius_limit - arc_center).normalize();
 
             arc_center + (x_i * arc_radius)
         };
@@ -122,7 +166,7 @@ impl ArcBlend {
 
         let end_point = {
             // Xi+1: Vector pointing from arc center to end point
-            let x_i = (mid + radius_limit * next_delta_norm - arc_center).normalize();
+            let x_i = (mid + next_delta_norm * radius_limit - arc_center).normalize();
 
             arc_center + (x_i * arc_radius)
         };
@@ -135,17 +179,15 @@ impl ArcBlend {
         // Equation from <https://openstax.org/books/physics/pages/6-2-uniform-circular-motion> `a_c
         // = v^2 / r` rearranged.
         let velocity_limit = {
-            let lim = arc_radius * accel_limit;
+            let lim = accel_limit * arc_radius;
 
             // Clamp limit to maximum allowed velocity for each axis
-            Coord3::new(
-                lim.x.sqrt().min(max_velocity.x),
-                lim.y.sqrt().min(max_velocity.y),
-                lim.z.sqrt().min(max_velocity.z),
-            )
+            lim.zip_map(&max_velocity, |lim, max_velocity| {
+                lim.sqrt().min(max_velocity)
+            })
         };
 
-        Self {
+        let mut blend = Self {
             prev,
             mid,
             next,
@@ -156,42 +198,191 @@ impl ArcBlend {
             arc_radius,
             arc_len,
             velocity_limit,
-            // Ensure time can never be negative. This can occur for extremely small arc angles
-            time: (velocity_limit.norm() * arc_len).max(0.0),
+            acceleration_limit: accel_limit,
+            v0: T::zero(),
+            v1: T::zero(),
+            time: T::zero(),
             start_t,
             is_colinear,
+        };
+
+        blend.set_speed(blend.max_speed());
+
+        blend
+    }
+
+    /// Fastest constant speed through the arc. The direction of travel sweeps through the plane of
+    /// the arc, so this is the lowest per-axis velocity limit.
+    pub fn max_speed(&self) -> T {
+        self.velocity_limit.min()
+    }
+
+    /// Move along the arc at the given constant speed, updating its duration.
+    pub fn set_speed(&mut self, speed: T) {
+        self.set_speeds(speed, speed);
+    }
+
+    /// Change speed from `v0` at the start of the arc to `v1` at its end with a constant
+    /// tangential acceleration, updating its duration. Use
+    /// [`reachable_speed`](Self::reachable_speed) to keep within the acceleration limits.
+    pub fn set_speeds(&mut self, v0: T, v1: T) {
+        debug_assert!(
+            v0 >= T::zero() && v1 >= T::zero() && v0 + v1 > T::zero(),
+            "Arc speeds must be positive, got {:?} and {:?}",
+            v0,
+            v1
+        );
+
+        self.v0 = v0;
+        self.v1 = v1;
+        // Ensure time can never be negative. This can occur for extremely small arc angles
+        self.time = (T::lit(2.0) * self.arc_len / (v0 + v1)).max(T::zero());
+    }
+
+    /// Tangential acceleration along the arc.
+    pub fn tangential_acc(&self) -> T {
+        if self.arc_len > T::zero() {
+            (self.v1.powi(2) - self.v0.powi(2)) / (T::lit(2.0) * self.arc_len)
+        } else {
+            T::zero()
         }
     }
 
-    pub fn tp(&self, t: f32) -> Option<Out> {
+    /// Fastest speed at one end of the arc when the other end is at `speed`.
+    ///
+    /// Centripetal acceleration `v^2 / r` takes up part of the acceleration limit, leaving the rest
+    /// to speed up or slow down along the arc. Every axis is within its limit when the two add up
+    /// to no more than the lowest limit, which solved for the speed at the faster end gives
+    /// `v^2 = (speed^2 + 2 * a * len) / (1 + 2 * len / r)`.
+    pub fn reachable_speed(&self, speed: T) -> T {
+        if self.arc_radius <= T::zero() {
+            return self.max_speed();
+        }
+
+        let two = T::lit(2.0);
+        let acc = self.acceleration_limit.min();
+
+        ((speed.powi(2) + two * acc * self.arc_len)
+            / (T::one() + two * self.arc_len / self.arc_radius))
+            .sqrt()
+            .max(speed)
+            .min(self.max_speed())
+    }
+
+    pub fn tp(&self, t: T) -> Option<Out<N, T>> {
         let t = t - self.start_t;
 
-        if t >= self.time || t < 0.0 {
+        if t >= self.time || t < T::zero() {
             return None;
         }
 
-        let t = t / self.time;
+        let tangential_acc = self.tangential_acc();
+
+        // Distance and speed along the arc
+        let s = self.v0 * t + tangential_acc * t.powi(2) / T::lit(2.0);
+        let speed = self.v0 + tangential_acc * t;
+
+        // Angle travelled around the arc
+        let angle = s / self.arc_radius;
+
+        // Unit vectors in the plane of the arc. One points from the center to the start point, the
+        // other is perpendicular to it, pointing towards the end point. Unlike a cross product
+        // this works for any number of axes.
+        let start_dir = (self.arc_start - self.arc_center).normalize();
+        let end_dir = (self.arc_end - self.arc_center).normalize();
+        let perp_dir = (end_dir - start_dir * end_dir.dot(&start_dir)).normalize();
+
+        let (sin, cos) = angle.sin_cos();
 
-        let pos = (self.arc_start - self.arc_center).slerp(&(self.arc_end - self.arc_center), t);
-        let pos = self.arc_center + pos * self.arc_radius;
+        let radial = start_dir * cos + perp_dir * sin;
 
-        // Centripetal acceleration: it always points towards center of circle
-        // TODO: Magnitude
-        let acc = (self.arc_center - pos).normalize();
+        let pos = self.arc_center + radial * self.arc_radius;
 
         // Instantaneous velocity is always tangent to the arc
-        // TODO: Magnitude
-        let vel = {
-            let a = self.mid - self.prev;
-            let b = self.next - self.mid;
+        let tangent = perp_dir * cos - start_dir * sin;
 
-            let normal = b.cross(&a).normalize();
+        let vel = tangent * speed;
 
-            (normal.cross(&acc)).normalize()
-        };
+        // Centripetal acceleration always points towards the center of the circle, plus any
+        // change in speed along the tangent
+        let acc = tangent * tangential_acc - radial * (speed.powi(2) / self.arc_radius);
 
         Some(Out { pos, vel, acc })
     }
+
+    /// Check this blend against the joint limits of the given kinematics, sampling every `dt`
+    /// seconds.
+    pub fn check_joint_limits<K: Kinematics<N, T> + ?Sized>(
+        &self,
+        kinematics: &K,
+        dt: T,
+    ) -> Result<(), LimitError<N, T>> {
+        kinematics::check_limits(
+            kinematics,
+            self.start_t,
+            self.start_t + self.time,
+            dt,
+            |t| self.tp(t),
+        )
+    }
+}
+
+impl<const N: usize, T: Real> Blend<N, T> for ArcBlend<N, T> {
+    fn q0(&self) -> Coord<N, T> {
+        self.arc_start
+    }
+
+    fn q1(&self) -> Coord<N, T> {
+        self.arc_end
+    }
+
+    fn path_len(&self) -> T {
+        self.arc_len
+    }
+
+    fn set_start_t(&mut self, start_t: T) {
+        self.start_t = start_t;
+    }
+
+    fn v0(&self) -> T {
+        self.v0
+    }
+
+    fn v1(&self) -> T {
+        self.v1
+    }
+
+    fn max_speed(&self) -> T {
+        ArcBlend::max_speed(self)
+    }
+
+    fn reachable_speed(&self, speed: T) -> T {
+        ArcBlend::reachable_speed(self, speed)
+    }
+
+    fn set_speeds(&mut self, v0: T, v1: T) {
+        ArcBlend::set_speeds(self, v0, v1)
+    }
+}
+
+impl<const N: usize, T: Real> Profile<T> for ArcBlend<N, T> {
+    type Out = Out<N, T>;
+
+    fn start_t(&self) -> T {
+        self.start_t
+    }
+
+    fn duration(&self) -> T {
+        self.time
+    }
+
+    fn tp(&self, t: T) -> Option<Out<N, T>> {
+        ArcBlend::tp(self, t)
+    }
+
+    fn phase_times(&self) -> Vec<T> {
+        Vec::new()
+    }
 }
 
 #[cfg(test)]
@@ -254,4 +445,53 @@ mod tests {
             },
         );
     }
+
+    #[test]
+    fn accelerate_through_arc() {
+        let lim = Lim {
+            acc: Coord3::new(5.0, 5.0, 5.0),
+            vel: Coord3::new(2.0, 2.0, 2.0),
+        };
+
+        let mut blend = ArcBlend::new(
+            Coord3::new(0.0, 0.0, 0.0),
+            Coord3::new(2.0, 0.0, 0.0),
+            Coord3::new(2.0, 2.0, 0.0),
+            0.2,
+            1.0,
+            lim,
+        );
+
+        let v0 = 0.2;
+        let v1 = blend.reachable_speed(v0);
+
+        assert!(v1 > v0 && v1 <= blend.max_speed());
+
+        blend.set_speeds(v0, v1);
+
+        let dt = 0.0001;
+
+        let start = blend.tp(1.0).unwrap();
+
+        assert!((start.pos - blend.arc_start).norm() < 1.0e-5);
+        assert!((start.vel - Coord3::new(v0, 0.0, 0.0)).norm() < 1.0e-5);
+
+        let mut prev = start;
+        let mut t = 1.0 + dt;
+
+        while let Some(out) = blend.tp(t) {
+            // Derivatives match the motion
+            assert!(((out.pos - prev.pos) / dt - (out.vel + prev.vel) / 2.0).norm() < 1.0e-2);
+            assert!(((out.vel - prev.vel) / dt - (out.acc + prev.acc) / 2.0).norm() < 1.0e-1);
+
+            assert!(out.vel.abs() <= lim.vel * 1.001, "{t}: {}", out.vel);
+            assert!(out.acc.abs() <= lim.acc * 1.001, "{t}: {}", out.acc);
+
+            prev = out;
+            t += dt;
+        }
+
+        assert!((prev.pos - blend.arc_end).norm() < 1.0e-3);
+        assert!((prev.vel.norm() - v1).abs() < 1.0e-3);
+    }
 }
@@ -0,0 +1,593 @@
+//! Corner blend made of a quintic Bezier curve.
+//!
+//! The first three control points lie on the incoming line and the last three on the outgoing
+//! line, so the curve leaves and joins the lines with zero curvature (G2 continuity). Bezier curves
+//! aren't parameterised by arc length, so a table of lengths along the curve is built once and
+//! refined with Newton's method whenever a point at a given distance is needed.
+
+use alloc::vec::Vec;
+
+use crate::{
+    blend::Blend,
+    kinematics::{self, Kinematics, LimitError},
+    profile::Profile,
+    real::Real,
+    trapezoidal_non_zero_3d::{Coord, Lim, Out},
+};
+
+pub use crate::trapezoidal_non_zero_3d::Coord3;
+
+/// Number of intervals in the arc length table.
+const TABLE_LEN: usize = 32;
+
+/// Distance of each control point on the incoming line from the corner, as a proportion of the
+/// distance of the first. The outgoing line mirrors these.
+const CONTROL_POINT_SPACING: [f64; 3] = [1.0, 2.0 / 3.0, 1.0 / 3.0];
+
+/// 5 point Gauss-Legendre quadrature nodes and weights on `[-1, 1]`.
+pub(crate) const GAUSS_LEGENDRE: [(f64, f64); 5] = [
+    (0.0, 0.568_888_888_888_888_9),
+    (-0.538_469_310_105_683_1, 0.478_628_670_499_366_5),
+    (0.538_469_310_105_683_1, 0.478_628_670_499_366_5),
+    (-0.906_179_845_938_664, 0.236_926_885_056_189_1),
+    (0.906_179_845_938_664, 0.236_926_885_056_189_1),
+];
+
+#[derive(Debug, Copy, Clone)]
+pub struct BezierBlend<const N: usize = 3, T: Real = f32> {
+    pub prev: Coord<N, T>,
+    pub mid: Coord<N, T>,
+    pub next: Coord<N, T>,
+    pub max_deviation: T,
+
+    pub control_points: [Coord<N, T>; 6],
+    /// Length along the curve at evenly spaced values of the curve parameter from 0 to 1.
+    lengths: [T; TABLE_LEN + 1],
+    /// Highest curvature anywhere along the curve.
+    pub max_curvature: T,
+
+    pub velocity_limit: T,
+    pub acceleration_limit: Coord<N, T>,
+    /// Speed along the blend at its start.
+    pub v0: T,
+    /// Speed along the blend at its end.
+    pub v1: T,
+    pub time: T,
+    pub start_t: T,
+    pub is_colinear: bool,
+}
+
+impl<const N: usize, T: Real> Default for BezierBlend<N, T> {
+    fn default() -> Self {
+        Self {
+            prev: Coord::zeros(),
+            mid: Coord::zeros(),
+            next: Coord::zeros(),
+            max_deviation: T::zero(),
+            control_points: [Coord::zeros(); 6],
+            lengths: [T::zero(); TABLE_LEN + 1],
+            max_curvature: T::zero(),
+            velocity_limit: T::zero(),
+            acceleration_limit: Coord::zeros(),
+            v0: T::zero(),
+            v1: T::zero(),
+            time: T::zero(),
+            start_t: T::zero(),
+            is_colinear: false,
+        }
+    }
+}
+
+impl<const N: usize, T: Real> BezierBlend<N, T> {
+    /// Blend the corner at `mid`, staying within `max_deviation` of it. The blend starts at time
+    /// zero until [`start_t`](Self::start_t) is set.
+    pub fn new(
+        prev: Coord<N, T>,
+        mid: Coord<N, T>,
+        next: Coord<N, T>,
+        max_deviation: T,
+        Lim {
+            acc: max_acceleration,
+            vel: max_velocity,
+        }: Lim<N, T>,
+    ) -> Self {
+        let prev_delta = mid - prev;
+        let next_delta = next - mid;
+
+        // Squared norm of the cross product of `prev_delta` and `next_delta` by Lagrange's
+        // identity, so this works for any number of axes.
+        let cross_norm_squared = prev_delta.norm_squared() * next_delta.norm_squared()
+            - prev_delta.dot(&next_delta).powi(2);
+
+        let is_colinear = cross_norm_squared <= T::default_epsilon();
+
+        let speed = max_velocity.min();
+
+        if is_colinear {
+            return Self {
+                prev,
+                mid,
+                next,
+                max_deviation,
+                control_points: [mid; 6],
+                velocity_limit: speed,
+                acceleration_limit: max_acceleration,
+                v0: speed,
+                v1: speed,
+                is_colinear,
+                ..Self::default()
+            };
+        }
+
+        let two = T::lit(2.0);
+
+        let in_dir = prev_delta.normalize();
+        let out_dir = next_delta.normalize();
+
+        let half_angle = prev_delta.angle(&next_delta) / two;
+
+        let [k0, k1, k2] = CONTROL_POINT_SPACING.map(T::lit);
+
+        // The curve is symmetric, so its midpoint lies on the bisector of the corner. Per unit of
+        // distance from the corner to the first control point, its distance from the corner is
+        // `|in_dir - out_dir| * (k0 + 5 * k1 + 10 * k2) / 32`.
+        let deviation =
+            (k0 + T::lit(5.0) * k1 + T::lit(10.0) * k2) * half_angle.sin() / T::lit(16.0);
+
+        // Blend may at most use half of the smallest path segment, or be a maximum distance away
+        // from the midpoint, specified by the given configuration.
+        let setback = (max_deviation / deviation)
+            .min(prev_delta.norm() / two)
+            .min(next_delta.norm() / two);
+
+        let control_points = [
+            mid - in_dir * (setback * k0),
+            mid - in_dir * (setback * k1),
+            mid - in_dir * (setback * k2),
+            mid + out_dir * (setback * k2),
+            mid + out_dir * (setback * k1),
+            mid + out_dir * (setback * k0),
+        ];
+
+        let mut blend = Self {
+            prev,
+            mid,
+            next,
+            max_deviation,
+            control_points,
+            acceleration_limit: max_acceleration,
+            is_colinear,
+            ..Self::default()
+        };
+
+        for i in 0..TABLE_LEN {
+            let (u0, u1) = (Self::table_param(i), Self::table_param(i + 1));
+
+            blend.lengths[i + 1] = blend.lengths[i] + blend.integrate_speed(u0, u1);
+        }
+
+        // Sample the curvature at a few points per interval in the table
+        blend.max_curvature = (0..=TABLE_LEN * 4)
+            .map(|i| {
+                let u = T::lit(i as f64) / T::lit((TABLE_LEN * 4) as f64);
+
+                blend.curvature_at(u).norm()
+            })
+            .fold(T::zero(), |max, curvature| max.max(curvature));
+
+        blend.velocity_limit = if blend.max_curvature > T::zero() {
+            speed.min((max_acceleration.min() / blend.max_curvature).sqrt())
+        } else {
+            speed
+        };
+
+        blend.set_speed(blend.max_speed());
+
+        blend
+    }
+
+    /// Curve parameter at the given entry in the arc length table.
+    fn table_param(i: usize) -> T {
+        T::lit(i as f64) / T::lit(TABLE_LEN as f64)
+    }
+
+    /// Point on the curve at parameter `u` in `[0, 1]`.
+    pub fn point(&self, u: T) -> Coord<N, T> {
+        de_casteljau(self.control_points, u)
+    }
+
+    /// First derivative of the curve with respect to its parameter.
+    fn derivative(&self, u: T) -> Coord<N, T> {
+        let p = self.control_points;
+
+        de_casteljau(
+            [
+                p[1] - p[0],
+                p[2] - p[1],
+                p[3] - p[2],
+                p[4] - p[3],
+                p[5] - p[4],
+            ],
+            u,
+        ) * T::lit(5.0)
+    }
+
+    /// Second derivative of the curve with respect to its parameter.
+    fn second_derivative(&self, u: T) -> Coord<N, T> {
+        let p = self.control_points;
+        let two = T::lit(2.0);
+
+        de_casteljau(
+            [
+                p[2] - p[1] * two + p[0],
+                p[3] - p[2] * two + p[1],
+                p[4] - p[3] * two + p[2],
+                p[5] - p[4] * two + p[3],
+            ],
+            u,
+        ) * T::lit(20.0)
+    }
+
+    /// Curvature vector, the second derivative with respect to arc length, at parameter `u`.
+    fn curvature_at(&self, u: T) -> Coord<N, T> {
+        let d1 = self.derivative(u);
+        let d2 = self.second_derivative(u);
+
+        let speed_squared = d1.norm_squared();
+
+        if speed_squared <= T::zero() {
+            return Coord::zeros();
+        }
+
+        // Remove the component along the tangent, which only changes the parameterisation
+        (d2 - d1 * (d1.dot(&d2) / speed_squared)) / speed_squared
+    }
+
+    /// Length of the curve between parameters `u0` and `u1`.
+    fn integrate_speed(&self, u0: T, u1: T) -> T {
+        let half = (u1 - u0) / T::lit(2.0);
+        let center = (u0 + u1) / T::lit(2.0);
+
+        GAUSS_LEGENDRE
+            .iter()
+            .fold(T::zero(), |sum, (node, weight)| {
+                sum + T::lit(*weight) * self.derivative(center + half * T::lit(*node)).norm()
+            })
+            * half
+    }
+
+    /// Curve parameter at distance `s` along the curve. The table gives a first guess which is
+    /// refined with Newton's method, staying within the table interval so it can't diverge.
+    pub fn param(&self, s: T) -> T {
+        let s = s.max(T::zero()).min(self.len());
+
+        let i = self
+            .lengths
+            .partition_point(|len| *len <= s)
+            .clamp(1, TABLE_LEN)
+            - 1;
+
+        let (u0, u1) = (Self::table_param(i), Self::table_param(i + 1));
+        let (len0, len1) = (self.lengths[i], self.lengths[i + 1]);
+
+        let mut u = if len1 > len0 {
+            u0 + (u1 - u0) * (s - len0) / (len1 - len0)
+        } else {
+            u0
+        };
+
+        let tolerance = T::default_epsilon() * T::lit(16.0) * self.len().max(T::one());
+
+        for _ in 0..8 {
+            let error = len0 + self.integrate_speed(u0, u) - s;
+
+            let speed = self.derivative(u).norm();
+
+            if error.abs() <= tolerance || speed <= T::zero() {
+                break;
+            }
+
+            u = (u - error / speed).max(u0).min(u1);
+        }
+
+        u
+    }
+
+    /// Length of the path through the blend.
+    pub fn len(&self) -> T {
+        self.lengths[TABLE_LEN]
+    }
+
+    /// Position, unit tangent and curvature vector at distance `s` along the blend.
+    pub fn geometry(&self, s: T) -> (Coord<N, T>, Coord<N, T>, Coord<N, T>) {
+        let u = self.param(s);
+
+        let tangent = self
+            .derivative(u)
+            .try_normalize(T::zero())
+            .unwrap_or_else(Coord::zeros);
+
+        (self.point(u), tangent, self.curvature_at(u))
+    }
+
+    /// Fastest constant speed through the blend.
+    pub fn max_speed(&self) -> T {
+        self.velocity_limit
+    }
+
+    /// Move along the blend at the given constant speed, updating its duration.
+    pub fn set_speed(&mut self, speed: T) {
+        self.set_speeds(speed, speed);
+    }
+
+    /// Change speed from `v0` at the start of the blend to `v1` at its end with a constant
+    /// tangential acceleration, updating its duration. Use
+    /// [`reachable_speed`](Self::reachable_speed) to keep within the acceleration limits.
+    pub fn set_speeds(&mut self, v0: T, v1: T) {
+        debug_assert!(
+            v0 >= T::zero() && v1 >= T::zero() && v0 + v1 > T::zero(),
+            "Blend speeds must be positive, got {:?} and {:?}",
+            v0,
+            v1
+        );
+
+        self.v0 = v0;
+        self.v1 = v1;
+        self.time = (T::lit(2.0) * self.len() / (v0 + v1)).max(T::zero());
+    }
+
+    /// Tangential acceleration along the blend.
+    pub fn tangential_acc(&self) -> T {
+        if self.len() > T::zero() {
+            (self.v1.powi(2) - self.v0.powi(2)) / (T::lit(2.0) * self.len())
+        } else {
+            T::zero()
+        }
+    }
+
+    /// Fastest speed at one end of the blend when the other end is at `speed`.
+    ///
+    /// As with an arc, tangential and centripetal acceleration must add up to no more than the
+    /// lowest limit at the sharpest point of the blend.
+    pub fn reachable_speed(&self, speed: T) -> T {
+        if self.max_curvature <= T::zero() {
+            return speed.min(self.max_speed());
+        }
+
+        let two = T::lit(2.0);
+        let acc = self.acceleration_limit.min();
+        let len = self.len();
+
+        ((speed.powi(2) + two * acc * len) / (T::one() + two * len * self.max_curvature))
+            .sqrt()
+            .max(speed)
+            .min(self.max_speed())
+    }
+
+    pub fn tp(&self, t: T) -> Option<Out<N, T>> {
+        let t = t - self.start_t;
+
+        if t >= self.time || t < T::zero() {
+            return None;
+        }
+
+        let tangential_acc = self.tangential_acc();
+
+        // Distance and speed along the blend
+        let s = self.v0 * t + tangential_acc * t.powi(2) / T::lit(2.0);
+        let speed = self.v0 + tangential_acc * t;
+
+        let (pos, tangent, curvature) = self.geometry(s);
+
+        Some(Out {
+            pos,
+            vel: tangent * speed,
+            acc: tangent * tangential_acc + curvature * speed.powi(2),
+        })
+    }
+
+    /// Check this blend against the joint limits of the given kinematics, sampling every `dt`
+    /// seconds.
+    pub fn check_joint_limits<K: Kinematics<N, T> + ?Sized>(
+        &self,
+        kinematics: &K,
+        dt: T,
+    ) -> Result<(), LimitError<N, T>> {
+        kinematics::check_limits(
+            kinematics,
+            self.start_t,
+            self.start_t + self.time,
+            dt,
+            |t| self.tp(t),
+        )
+    }
+}
+
+impl<const N: usize, T: Real> Blend<N, T> for BezierBlend<N, T> {
+    fn q0(&self) -> Coord<N, T> {
+        self.control_points[0]
+    }
+
+    fn q1(&self) -> Coord<N, T> {
+        self.control_points[5]
+    }
+
+    fn path_len(&self) -> T {
+        BezierBlend::len(self)
+    }
+
+    fn set_start_t(&mut self, start_t: T) {
+        self.start_t = start_t;
+    }
+
+    fn v0(&self) -> T {
+        self.v0
+    }
+
+    fn v1(&self) -> T {
+        self.v1
+    }
+
+    fn max_speed(&self) -> T {
+        BezierBlend::max_speed(self)
+    }
+
+    fn reachable_speed(&self, speed: T) -> T {
+        BezierBlend::reachable_speed(self, speed)
+    }
+
+    fn set_speeds(&mut self, v0: T, v1: T) {
+        BezierBlend::set_speeds(self, v0, v1)
+    }
+}
+
+impl<const N: usize, T: Real> Profile<T> for BezierBlend<N, T> {
+    type Out = Out<N, T>;
+
+    fn start_t(&self) -> T {
+        self.start_t
+    }
+
+    fn duration(&self) -> T {
+        self.time
+    }
+
+    fn tp(&self, t: T) -> Option<Out<N, T>> {
+        BezierBlend::tp(self, t)
+    }
+
+    fn phase_times(&self) -> Vec<T> {
+        Vec::new()
+    }
+}
+
+/// Evaluate the Bezier curve with the given control points at parameter `u`.
+fn de_casteljau<const N: usize, const M: usize, T: Real>(
+    mut points: [Coord<N, T>; M],
+    u: T,
+) -> Coord<N, T> {
+    for len in (1..M).rev() {
+        for i in 0..len {
+            points[i] = points[i] * (T::one() - u) + points[i + 1] * u;
+        }
+    }
+
+    points[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lim() -> Lim {
+        Lim {
+            acc: Coord3::new(5.0, 5.0, 5.0),
+            vel: Coord3::new(2.0, 2.0, 2.0),
+        }
+    }
+
+    #[test]
+    fn max_deviation() {
+        let mid = Coord3::new(0.0, 0.0, 0.0);
+
+        let blend = BezierBlend::new(
+            Coord3::new(0.0, 10.0, 0.0),
+            mid,
+            Coord3::new(10.0, 0.0, 0.0),
+            0.1,
+            lim(),
+        );
+
+        let middle = blend.point(0.5);
+
+        assert!(((middle - mid).norm() - 0.1).abs() < 1.0e-5, "{}", middle);
+        assert!((middle.x - middle.y).abs() < 1.0e-5);
+
+        // Ends sit on the lines
+        let [start, .., end] = blend.control_points;
+
+        assert!(start.x.abs() < 1.0e-6 && start.y > 0.0);
+        assert!(end.y.abs() < 1.0e-6 && end.x > 0.0);
+    }
+
+    #[test]
+    fn arc_length() {
+        let blend = BezierBlend::<3, f64>::new(
+            Coord::<3, f64>::new(0.0, 0.0, 0.0),
+            Coord::<3, f64>::new(2.0, 0.0, 0.0),
+            Coord::<3, f64>::new(3.0, 1.5, 1.0),
+            f64::INFINITY,
+            Lim {
+                acc: Coord::<3, f64>::new(5.0, 5.0, 5.0),
+                vel: Coord::<3, f64>::new(2.0, 2.0, 2.0),
+            },
+        );
+
+        // Sum of many short chords
+        let chords = (1..=10_000)
+            .map(|i| {
+                let (u0, u1) = ((i - 1) as f64 / 10_000.0, i as f64 / 10_000.0);
+
+                (blend.point(u1) - blend.point(u0)).norm()
+            })
+            .sum::<f64>();
+
+        assert!((blend.len() - chords).abs() < 1.0e-6);
+
+        // Evenly spaced distances map back to evenly spaced points
+        for i in 0..=50 {
+            let s = blend.len() * i as f64 / 50.0;
+
+            let u = blend.param(s);
+
+            let along = (1..=1000)
+                .map(|j| {
+                    let (u0, u1) = (u * (j - 1) as f64 / 1000.0, u * j as f64 / 1000.0);
+
+                    (blend.point(u1) - blend.point(u0)).norm()
+                })
+                .sum::<f64>();
+
+            assert!((along - s).abs() < 1.0e-6, "{s}: {along}");
+        }
+    }
+
+    #[test]
+    fn continuous_curvature() {
+        let mut blend = BezierBlend::new(
+            Coord3::new(0.0, 0.0, 0.0),
+            Coord3::new(2.0, 0.0, 0.0),
+            Coord3::new(3.0, 1.5, 1.0),
+            0.5,
+            lim(),
+        );
+
+        blend.start_t = 1.0;
+
+        let start = blend.tp(1.0).unwrap();
+
+        // Joins the lines with no centripetal acceleration
+        assert!((start.pos - blend.control_points[0]).norm() < 1.0e-6);
+        assert!(start.acc.norm() < 1.0e-4);
+
+        let dt = 0.001;
+
+        let mut prev = start;
+        let mut t = 1.0 + dt;
+
+        while let Some(out) = blend.tp(t) {
+            // Constant speed
+            assert!((out.vel.norm() - blend.max_speed()).abs() < 1.0e-4);
+            assert!(((out.pos - prev.pos) / dt - (out.vel + prev.vel) / 2.0).norm() < 1.0e-2);
+
+            assert!(out.vel.abs() <= lim().vel * 1.001, "{t}: {}", out.vel);
+            assert!(out.acc.abs() <= lim().acc * 1.001, "{t}: {}", out.acc);
+
+            prev = out;
+            t += dt;
+        }
+
+        assert!((prev.pos - blend.control_points[5]).norm() <= blend.max_speed() * dt);
+        assert!(prev.acc.norm() < 0.1, "{}", prev.acc.norm());
+    }
+}
@@ -0,0 +1,42 @@
+//! Corner blends joining two straight line segments.
+
+use crate::{
+    profile::Profile,
+    real::Real,
+    trapezoidal_non_zero_3d::{Coord, Out},
+};
+
+/// A path around the corner between two straight line segments, replacing the end of the first
+/// line and the start of the second.
+///
+/// Every blend is built from the three points `prev`, `mid` and `next`, staying within a given
+/// `max_deviation` of the corner at `mid` and using at most half of either line. Speed changes
+/// linearly with time from [`v0`](Self::v0) to [`v1`](Self::v1) along the blend, so a blend is a
+/// single phase [`Profile`].
+pub trait Blend<const N: usize = 3, T: Real = f32>: Profile<T, Out = Out<N, T>> {
+    /// Start of the blend, on the line from `prev` to `mid`.
+    fn q0(&self) -> Coord<N, T>;
+
+    /// End of the blend, on the line from `mid` to `next`.
+    fn q1(&self) -> Coord<N, T>;
+
+    /// Length of the path through the blend.
+    fn path_len(&self) -> T;
+
+    fn set_start_t(&mut self, start_t: T);
+
+    /// Speed at the start of the blend.
+    fn v0(&self) -> T;
+
+    /// Speed at the end of the blend.
+    fn v1(&self) -> T;
+
+    /// Fastest speed anywhere in the blend.
+    fn max_speed(&self) -> T;
+
+    /// Fastest speed at one end of the blend when the other end is at `speed`.
+    fn reachable_speed(&self, speed: T) -> T;
+
+    /// Change speed from `v0` at the start of the blend to `v1` at its end, updating its duration.
+    fn set_speeds(&mut self, v0: T, v1: T);
+}
@@ -0,0 +1,511 @@
+//! Corner blend made of two clothoids (Euler spirals).
+//!
+//! Curvature rises linearly with distance from zero at the start of the blend to its peak in the
+//! middle of the corner, then falls linearly back to zero at the end. Unlike a circular
+//! [`ArcBlend`](crate::arc_blend::ArcBlend), curvature is continuous where the blend joins the
+//! lines either side of it, so centripetal acceleration ramps in and out instead of stepping.
+
+use alloc::vec::Vec;
+
+use crate::{
+    blend::Blend,
+    kinematics::{self, Kinematics, LimitError},
+    profile::Profile,
+    real::Real,
+    trapezoidal_non_zero_3d::{Coord, Lim, Out},
+};
+
+pub use crate::trapezoidal_non_zero_3d::Coord3;
+
+#[derive(Debug, Copy, Clone)]
+pub struct ClothoidBlend<const N: usize = 3, T: Real = f32> {
+    pub prev: Coord<N, T>,
+    pub mid: Coord<N, T>,
+    pub next: Coord<N, T>,
+    pub max_deviation: T,
+
+    /// Start of the blend on the line from `prev` to `mid`.
+    pub start: Coord<N, T>,
+    /// End of the blend on the line from `mid` to `next`.
+    pub end: Coord<N, T>,
+    /// Length of each of the two clothoids.
+    pub clothoid_len: T,
+    /// Rate of change of curvature with distance along each clothoid.
+    pub sharpness: T,
+    /// Curvature in the middle of the blend, where the two clothoids meet.
+    pub max_curvature: T,
+    /// Unit vector along the incoming line.
+    in_dir: Coord<N, T>,
+    /// Unit vector perpendicular to `in_dir` in the plane of the corner, pointing into the turn.
+    in_normal: Coord<N, T>,
+    /// Unit vector along the outgoing line, reversed.
+    out_dir: Coord<N, T>,
+    /// Unit vector perpendicular to `out_dir` in the plane of the corner, pointing into the turn.
+    out_normal: Coord<N, T>,
+
+    pub velocity_limit: T,
+    pub acceleration_limit: Coord<N, T>,
+    /// Whether the blend is limited by a jerk limit. Jerk limited blends are traversed at a
+    /// constant speed as a change in speed would step the tangential acceleration.
+    pub jerk_limited: bool,
+    /// Speed along the blend at its start.
+    pub v0: T,
+    /// Speed along the blend at its end.
+    pub v1: T,
+    pub time: T,
+    pub start_t: T,
+    pub is_colinear: bool,
+}
+
+impl<const N: usize, T: Real> Default for ClothoidBlend<N, T> {
+    fn default() -> Self {
+        Self {
+            prev: Coord::zeros(),
+            mid: Coord::zeros(),
+            next: Coord::zeros(),
+            max_deviation: T::zero(),
+            start: Coord::zeros(),
+            end: Coord::zeros(),
+            clothoid_len: T::zero(),
+            sharpness: T::zero(),
+            max_curvature: T::zero(),
+            in_dir: Coord::zeros(),
+            in_normal: Coord::zeros(),
+            out_dir: Coord::zeros(),
+            out_normal: Coord::zeros(),
+            velocity_limit: T::zero(),
+            acceleration_limit: Coord::zeros(),
+            jerk_limited: false,
+            v0: T::zero(),
+            v1: T::zero(),
+            time: T::zero(),
+            start_t: T::zero(),
+            is_colinear: false,
+        }
+    }
+}
+
+impl<const N: usize, T: Real> ClothoidBlend<N, T> {
+    /// Blend the corner at `mid`, staying within `max_deviation` of it. Pass a `jerk` limit to
+    /// also limit the speed so the rate of change of centripetal acceleration stays within it.
+    pub fn new(
+        prev: Coord<N, T>,
+        mid: Coord<N, T>,
+        next: Coord<N, T>,
+        max_deviation: T,
+        start_t: T,
+        Lim {
+            acc: max_acceleration,
+            vel: max_velocity,
+        }: Lim<N, T>,
+        jerk: Option<Coord<N, T>>,
+    ) -> Self {
+        let prev_delta = mid - prev;
+        let next_delta = next - mid;
+
+        // Squared norm of the cross product of `prev_delta` and `next_delta` by Lagrange's
+        // identity, so this works for any number of axes.
+        let cross_norm_squared = prev_delta.norm_squared() * next_delta.norm_squared()
+            - prev_delta.dot(&next_delta).powi(2);
+
+        let is_colinear = cross_norm_squared <= T::default_epsilon();
+
+        let speed = max_velocity.min();
+
+        if is_colinear {
+            return Self {
+                prev,
+                mid,
+                next,
+                max_deviation,
+                start: mid,
+                end: mid,
+                velocity_limit: speed,
+                acceleration_limit: max_acceleration,
+                jerk_limited: jerk.is_some(),
+                v0: speed,
+                v1: speed,
+                start_t,
+                is_colinear,
+                ..Self::default()
+            };
+        }
+
+        let two = T::lit(2.0);
+
+        let in_dir = prev_delta.normalize();
+        let next_dir = next_delta.normalize();
+
+        let in_normal = (next_dir - in_dir * next_dir.dot(&in_dir)).normalize();
+        let out_dir = -next_dir;
+        let out_normal = (-in_dir + next_dir * in_dir.dot(&next_dir)).normalize();
+
+        // Outside angle between segments in radians. Each clothoid turns through half of it.
+        let outside_angle = prev_delta.angle(&next_delta);
+        let half_angle = outside_angle / two;
+
+        // End of a clothoid of unit length turning through `half_angle`. Its sharpness is
+        // `2 * half_angle / len^2`, and the whole curve scales with its length.
+        let (x, y) = clothoid(outside_angle, T::one());
+
+        // The two clothoids meet on the bisector of the corner. Per unit of clothoid length, this
+        // is the distance from the corner to the start of the blend along the line, and the
+        // distance from the corner to the middle of the blend.
+        let setback = x + y * half_angle.tan();
+        let deviation = y / half_angle.cos();
+
+        // Blend may at most use half of the smallest path segment, or be a maximum distance away
+        // from the midpoint, specified by the given configuration.
+        let clothoid_len = (max_deviation / deviation)
+            .min(prev_delta.norm() / two / setback)
+            .min(next_delta.norm() / two / setback);
+
+        let setback = setback * clothoid_len;
+
+        let sharpness = outside_angle / clothoid_len.powi(2);
+        let max_curvature = sharpness * clothoid_len;
+
+        // Centripetal acceleration `v^2 * k` at the sharpest point of the blend. At constant speed
+        // jerk is `v^3 * (dk/ds * n - k^2 * t)`, from the curvature changing and the normal `n`
+        // turning towards the tangent `t`.
+        let velocity_limit = {
+            let acc_limit = (max_acceleration.min() / max_curvature).sqrt();
+
+            let jerk_limit = jerk
+                .map(|jerk| {
+                    (jerk.min() / (sharpness.powi(2) + max_curvature.powi(4)).sqrt()).cbrt()
+                })
+                .unwrap_or_else(|| T::lit(f64::INFINITY));
+
+            speed.min(acc_limit).min(jerk_limit)
+        };
+
+        let mut blend = Self {
+            prev,
+            mid,
+            next,
+            max_deviation,
+            start: mid - in_dir * setback,
+            end: mid + next_dir * setback,
+            clothoid_len,
+            sharpness,
+            max_curvature,
+            in_dir,
+            in_normal,
+            out_dir,
+            out_normal,
+            velocity_limit,
+            acceleration_limit: max_acceleration,
+            jerk_limited: jerk.is_some(),
+            v0: T::zero(),
+            v1: T::zero(),
+            time: T::zero(),
+            start_t,
+            is_colinear,
+        };
+
+        blend.set_speed(blend.max_speed());
+
+        blend
+    }
+
+    /// Length of the path through the blend.
+    pub fn len(&self) -> T {
+        self.clothoid_len * T::lit(2.0)
+    }
+
+    /// Fastest constant speed through the blend.
+    pub fn max_speed(&self) -> T {
+        self.velocity_limit
+    }
+
+    /// Move along the blend at the given constant speed, updating its duration.
+    pub fn set_speed(&mut self, speed: T) {
+        self.set_speeds(speed, speed);
+    }
+
+    /// Change speed from `v0` at the start of the blend to `v1` at its end with a constant
+    /// tangential acceleration, updating its duration. Use
+    /// [`reachable_speed`](Self::reachable_speed) to keep within the acceleration limits.
+    pub fn set_speeds(&mut self, v0: T, v1: T) {
+        debug_assert!(
+            v0 >= T::zero() && v1 >= T::zero() && v0 + v1 > T::zero(),
+            "Blend speeds must be positive, got {:?} and {:?}",
+            v0,
+            v1
+        );
+
+        self.v0 = v0;
+        self.v1 = v1;
+        self.time = (T::lit(2.0) * self.len() / (v0 + v1)).max(T::zero());
+    }
+
+    /// Tangential acceleration along the blend.
+    pub fn tangential_acc(&self) -> T {
+        if self.len() > T::zero() {
+            (self.v1.powi(2) - self.v0.powi(2)) / (T::lit(2.0) * self.len())
+        } else {
+            T::zero()
+        }
+    }
+
+    /// Fastest speed at one end of the blend when the other end is at `speed`.
+    ///
+    /// As with an arc, tangential and centripetal acceleration must add up to no more than the
+    /// lowest limit at the sharpest point of the blend. Jerk limited blends don't change speed.
+    pub fn reachable_speed(&self, speed: T) -> T {
+        if self.jerk_limited || self.max_curvature <= T::zero() {
+            return speed.min(self.max_speed());
+        }
+
+        let two = T::lit(2.0);
+        let acc = self.acceleration_limit.min();
+        let len = self.len();
+
+        ((speed.powi(2) + two * acc * len) / (T::one() + two * len * self.max_curvature))
+            .sqrt()
+            .max(speed)
+            .min(self.max_speed())
+    }
+
+    /// Position, unit tangent and curvature vector at distance `s` along the blend.
+    pub fn geometry(&self, s: T) -> (Coord<N, T>, Coord<N, T>, Coord<N, T>) {
+        let s = s.max(T::zero()).min(self.len());
+
+        // Each clothoid is evaluated from the end of the blend it touches the line at, so the
+        // second one is walked backwards from the end of the blend.
+        let (origin, dir, normal, local_s, sign) = if s <= self.clothoid_len {
+            (self.start, self.in_dir, self.in_normal, s, T::one())
+        } else {
+            (
+                self.end,
+                self.out_dir,
+                self.out_normal,
+                self.len() - s,
+                -T::one(),
+            )
+        };
+
+        let (x, y) = clothoid(self.sharpness, local_s);
+
+        let (sin, cos) = (self.sharpness * local_s.powi(2) / T::lit(2.0)).sin_cos();
+
+        let pos = origin + dir * x + normal * y;
+        let tangent = (dir * cos + normal * sin) * sign;
+        let curvature = (normal * cos - dir * sin) * (self.sharpness * local_s);
+
+        (pos, tangent, curvature)
+    }
+
+    pub fn tp(&self, t: T) -> Option<Out<N, T>> {
+        let t = t - self.start_t;
+
+        if t >= self.time || t < T::zero() {
+            return None;
+        }
+
+        let tangential_acc = self.tangential_acc();
+
+        // Distance and speed along the blend
+        let s = self.v0 * t + tangential_acc * t.powi(2) / T::lit(2.0);
+        let speed = self.v0 + tangential_acc * t;
+
+        let (pos, tangent, curvature) = self.geometry(s);
+
+        Some(Out {
+            pos,
+            vel: tangent * speed,
+            acc: tangent * tangential_acc + curvature * speed.powi(2),
+        })
+    }
+
+    /// Check this blend against the joint limits of the given kinematics, sampling every `dt`
+    /// seconds.
+    pub fn check_joint_limits<K: Kinematics<N, T> + ?Sized>(
+        &self,
+        kinematics: &K,
+        dt: T,
+    ) -> Result<(), LimitError<N, T>> {
+        kinematics::check_limits(
+            kinematics,
+            self.start_t,
+            self.start_t + self.time,
+            dt,
+            |t| self.tp(t),
+        )
+    }
+}
+
+impl<const N: usize, T: Real> Blend<N, T> for ClothoidBlend<N, T> {
+    fn q0(&self) -> Coord<N, T> {
+        self.start
+    }
+
+    fn q1(&self) -> Coord<N, T> {
+        self.end
+    }
+
+    fn path_len(&self) -> T {
+        ClothoidBlend::len(self)
+    }
+
+    fn set_start_t(&mut self, start_t: T) {
+        self.start_t = start_t;
+    }
+
+    fn v0(&self) -> T {
+        self.v0
+    }
+
+    fn v1(&self) -> T {
+        self.v1
+    }
+
+    fn max_speed(&self) -> T {
+        ClothoidBlend::max_speed(self)
+    }
+
+    fn reachable_speed(&self, speed: T) -> T {
+        ClothoidBlend::reachable_speed(self, speed)
+    }
+
+    fn set_speeds(&mut self, v0: T, v1: T) {
+        ClothoidBlend::set_speeds(self, v0, v1)
+    }
+}
+
+impl<const N: usize, T: Real> Profile<T> for ClothoidBlend<N, T> {
+    type Out = Out<N, T>;
+
+    fn start_t(&self) -> T {
+        self.start_t
+    }
+
+    fn duration(&self) -> T {
+        self.time
+    }
+
+    fn tp(&self, t: T) -> Option<Out<N, T>> {
+        ClothoidBlend::tp(self, t)
+    }
+
+    fn phase_times(&self) -> Vec<T> {
+        Vec::new()
+    }
+}
+
+/// Point at distance `s` along a clothoid starting at the origin heading along the X axis, with
+/// curvature `sharpness * s`. These are the Fresnel integrals of `sharpness * s^2 / 2`, summed as
+/// power series which converge quickly for the turns of up to half a circle found in a blend.
+fn clothoid<T: Real>(sharpness: T, s: T) -> (T, T) {
+    let angle = sharpness * s.powi(2) / T::lit(2.0);
+
+    let mut x = T::zero();
+    let mut y = T::zero();
+
+    // angle^k / k!
+    let mut term = T::one();
+
+    for k in 0..24 {
+        let value = term / T::lit(f64::from(2 * k + 1));
+
+        match k % 4 {
+            0 => x += value,
+            1 => y += value,
+            2 => x -= value,
+            _ => y -= value,
+        }
+
+        term = term * angle / T::lit(f64::from(k + 1));
+    }
+
+    (x * s, y * s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lim() -> Lim {
+        Lim {
+            acc: Coord3::new(5.0, 5.0, 5.0),
+            vel: Coord3::new(2.0, 2.0, 2.0),
+        }
+    }
+
+    #[test]
+    fn max_deviation() {
+        let mid = Coord3::new(0.0, 0.0, 0.0);
+
+        let blend = ClothoidBlend::new(
+            Coord3::new(0.0, 10.0, 0.0),
+            mid,
+            Coord3::new(10.0, 0.0, 0.0),
+            0.1,
+            0.0,
+            lim(),
+            None,
+        );
+
+        let (middle, tangent, _) = blend.geometry(blend.clothoid_len);
+
+        // Middle of the blend is on the bisector, exactly at the deviation limit
+        assert!(((middle - mid).norm() - 0.1).abs() < 1.0e-5, "{}", middle);
+        assert!((middle.x - middle.y).abs() < 1.0e-5);
+        assert!((tangent - Coord3::new(1.0, -1.0, 0.0).normalize()).norm() < 1.0e-5);
+
+        // Ends sit on the lines
+        assert!(blend.start.x.abs() < 1.0e-6 && blend.start.y > 0.0);
+        assert!(blend.end.y.abs() < 1.0e-6 && blend.end.x > 0.0);
+    }
+
+    #[test]
+    fn continuous_curvature() {
+        let blend = ClothoidBlend::new(
+            Coord3::new(0.0, 0.0, 0.0),
+            Coord3::new(2.0, 0.0, 0.0),
+            Coord3::new(3.0, 1.5, 1.0),
+            f32::INFINITY,
+            1.0,
+            lim(),
+            Some(Coord3::new(50.0, 50.0, 50.0)),
+        );
+
+        // Uses half of the shorter line
+        let prev_len = (blend.mid - blend.prev).norm();
+
+        assert!(((blend.end - blend.mid).norm() - prev_len / 2.0).abs() < 1.0e-5);
+        assert!(((blend.start - blend.mid).norm() - prev_len / 2.0).abs() < 1.0e-5);
+
+        let start = blend.tp(1.0).unwrap();
+
+        // Joins the lines with no centripetal acceleration
+        assert!((start.pos - blend.start).norm() < 1.0e-6);
+        assert!(start.acc.norm() < 1.0e-6);
+        assert!((start.vel.normalize() - Coord3::new(1.0, 0.0, 0.0)).norm() < 1.0e-6);
+
+        let dt = 0.0001;
+
+        let mut prev = start;
+        let mut t = 1.0 + dt;
+
+        while let Some(out) = blend.tp(t) {
+            assert!(((out.pos - prev.pos) / dt - (out.vel + prev.vel) / 2.0).norm() < 1.0e-2);
+
+            assert!(out.vel.abs() <= lim().vel * 1.001, "{t}: {}", out.vel);
+            assert!(out.acc.abs() <= lim().acc * 1.001, "{t}: {}", out.acc);
+
+            // Acceleration changes smoothly, within the jerk limit
+            assert!(
+                (out.acc - prev.acc).abs() <= Coord3::repeat(50.0 * dt * 1.01),
+                "{t}"
+            );
+
+            prev = out;
+            t += dt;
+        }
+
+        assert!((prev.pos - blend.end).norm() < 1.0e-3);
+        assert!(prev.acc.norm() < 1.0e-2);
+    }
+}
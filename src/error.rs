@@ -0,0 +1,40 @@
+//! Errors returned when a profile can't be planned.
+
+use crate::real::Real;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlanError<T: Real = f32> {
+    /// A position, velocity or time is infinite or NaN.
+    NonFinite,
+    /// A limit is zero, negative, infinite or NaN.
+    InvalidLimit,
+    /// The start and end positions are the same, so there's no direction to move in.
+    ZeroDisplacement,
+    /// A boundary velocity is over the velocity limit, or the final velocity can't be reached from
+    /// the initial velocity within the displacement.
+    InfeasibleVelocities,
+    /// The requested duration is shorter than the fastest the move can be made in.
+    DurationTooShort { duration: T, min_duration: T },
+}
+
+/// Check every input value is finite.
+pub(crate) fn check_finite<T: Real>(
+    values: impl IntoIterator<Item = T>,
+) -> Result<(), PlanError<T>> {
+    values
+        .into_iter()
+        .all(|value| value.is_finite())
+        .then_some(())
+        .ok_or(PlanError::NonFinite)
+}
+
+/// Check every limit is finite and greater than zero.
+pub(crate) fn check_limits<T: Real>(
+    limits: impl IntoIterator<Item = T>,
+) -> Result<(), PlanError<T>> {
+    limits
+        .into_iter()
+        .all(|limit| limit.is_finite() && limit > T::zero())
+        .then_some(())
+        .ok_or(PlanError::InvalidLimit)
+}
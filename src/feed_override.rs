@@ -0,0 +1,780 @@
+//! Live feed and rapid overrides, applied to a planned trajectory as it's interpolated.
+
+use crate::{
+    profile::Profile,
+    real::Real,
+    segments_blends::{Item, Trajectory},
+    trapezoidal_non_zero_3d::{Coord, Lim, Out},
+};
+
+/// Largest override that can be set, i.e. 200%.
+pub const MAX_OVERRIDE: f64 = 2.0;
+
+/// Number of samples the lookahead takes each cycle.
+const LOOKAHEAD_SAMPLES: usize = 64;
+
+/// Samples a [`Trajectory`] every `period` seconds, re-timing it on the fly by an override that
+/// can be changed between samples.
+///
+/// The trajectory is followed at a rate `r` relative to its planned time, so the path is never
+/// left and position stays continuous. Scaling time by `r` scales velocity by `r` and acceleration
+/// by `r² + ṙ`, so `r` is ramped towards the override no faster than
+/// [`max_rate_change`](Self::max_rate_change), and held below whatever keeps every axis within
+/// [`lim`](Self::lim). A lookahead over the next part of the trajectory starts slowing down early
+/// enough that a corner or a deceleration that can't be taken any faster than planned is reached
+/// at no more than 100%.
+///
+/// [`hold`](Self::hold) brings the motion to a stop on the path as quickly as the acceleration
+/// limits allow, carrying on into the following items if it can't stop within the current one.
+/// [`resume`](Self::resume) then speeds back up along the same path, again limited only by
+/// acceleration, until the trajectory is back to being followed at the override.
+///
+/// Jerk is `j·r³ + 3·a·r·ṙ + v·r̈`. When the trajectory has jerk limited
+/// [`SCurve`](Item::SCurve) lines, [`jerk`](Self::jerk) holds their limit. `r` is then also held
+/// below whatever keeps the planned jerk within it, `ṙ` is kept small enough to leave room for the
+/// planned acceleration, and `ṙ` is ramped rather than stepped, including through a hold and
+/// resume. Trapezoidal profiles already step in acceleration, so without S-curve lines jerk isn't
+/// limited.
+#[derive(Debug, Clone)]
+pub struct FeedOverride<'a, const N: usize = 3, T: Real = f32> {
+    trajectory: &'a Trajectory<N, T>,
+    /// Servo period in seconds.
+    period: T,
+    /// Limits the re-timed output is kept within. Defaults to the limits of the trajectory.
+    pub lim: Lim<N, T>,
+    /// Fastest the rate along the trajectory may change, in 1/s. Defaults to going from 0% to
+    /// 100% in half a second.
+    pub max_rate_change: T,
+    /// Jerk limits the re-timed output is kept within, if any. Defaults to the tightest jerk limit
+    /// of the trajectory's [`SCurve`](Item::SCurve) lines.
+    pub jerk: Option<Coord<N, T>>,
+    /// Override applied to feed moves, from 0 to [`MAX_OVERRIDE`].
+    feed: T,
+    /// Override applied to rapid moves, from 0 to [`MAX_OVERRIDE`].
+    rapid: T,
+    /// Current position in the trajectory's planned time.
+    tau: T,
+    /// Current rate along the trajectory, where 1 is the planned speed.
+    rate: T,
+    /// Change in rate over the last cycle, in 1/s.
+    rate_change: T,
+    /// Acceleration output by the last sample.
+    acc: Coord<N, T>,
+    /// Number of samples produced so far.
+    cycle: usize,
+    /// Index of the item covering [`tau`](Self::tau).
+    cursor: usize,
+    /// Whether a feed hold is active.
+    held: bool,
+    /// Whether the motion is speeding back up after a feed hold.
+    resuming: bool,
+    /// Whether the end of the trajectory has been output.
+    finished: bool,
+}
+
+/// Planned motion at one point in the trajectory.
+struct Sample<const N: usize, T: Real> {
+    out: Out<N, T>,
+    /// Planned jerk, or zero for items that aren't jerk limited.
+    jerk: Coord<N, T>,
+    /// Override requested for the item the sample is in.
+    target: T,
+}
+
+impl<'a, const N: usize, T: Real> FeedOverride<'a, N, T> {
+    pub fn new(trajectory: &'a Trajectory<N, T>, period: T) -> Self {
+        assert!(
+            period > T::zero(),
+            "Period must be a positive value, got {:?}",
+            period
+        );
+
+        let jerk = trajectory
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::SCurve(line) => Some(line.lim().jerk),
+                _ => None,
+            })
+            .reduce(|a, b| a.inf(&b));
+
+        Self {
+            trajectory,
+            period,
+            lim: trajectory.limits,
+            max_rate_change: T::lit(2.0),
+            jerk,
+            feed: T::one(),
+            rapid: T::one(),
+            tau: T::zero(),
+            rate: T::zero(),
+            rate_change: T::zero(),
+            acc: Coord::zeros(),
+            cycle: 0,
+            cursor: 0,
+            held: false,
+            resuming: false,
+            finished: trajectory.items.is_empty(),
+        }
+    }
+
+    /// Set the feed override, where 1 is 100%. Clamped to between 0 and [`MAX_OVERRIDE`].
+    pub fn set_feed(&mut self, feed: T) {
+        self.feed = Self::clamp_override(feed);
+    }
+
+    /// Set the rapid override, where 1 is 100%. Clamped to between 0 and [`MAX_OVERRIDE`].
+    pub fn set_rapid(&mut self, rapid: T) {
+        self.rapid = Self::clamp_override(rapid);
+    }
+
+    pub fn feed(&self) -> T {
+        self.feed
+    }
+
+    pub fn rapid(&self) -> T {
+        self.rapid
+    }
+
+    /// Start a feed hold, decelerating to a stop on the path.
+    pub fn hold(&mut self) {
+        self.held = true;
+        self.resuming = false;
+    }
+
+    /// Release a feed hold, speeding back up along the path to the planned motion.
+    pub fn resume(&mut self) {
+        if self.held {
+            self.held = false;
+            self.resuming = true;
+        }
+    }
+
+    /// Whether a feed hold is active, even if the motion is still slowing down.
+    pub fn is_held(&self) -> bool {
+        self.held
+    }
+
+    /// Whether a feed hold has brought the motion to a complete stop.
+    pub fn is_stopped(&self) -> bool {
+        self.held && self.rate <= T::zero()
+    }
+
+    /// Current rate along the trajectory, where 1 is the planned speed.
+    pub fn rate(&self) -> T {
+        self.rate
+    }
+
+    /// Position in the trajectory's planned time the next sample is taken at.
+    pub fn tau(&self) -> T {
+        self.tau
+    }
+
+    /// Index into [`Trajectory::items`] of the item the next sample is taken from.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    fn clamp_override(value: T) -> T {
+        value.max(T::zero()).min(T::lit(MAX_OVERRIDE))
+    }
+
+    /// Planned motion at `tau`, searching forward from item `from`. Returns the index of the item
+    /// the sample was taken from, or `None` past the end of the trajectory.
+    fn sample(&self, from: usize, tau: T) -> Option<(usize, Sample<N, T>)> {
+        let items = &self.trajectory.items;
+
+        let index = (from..items.len()).find(|&index| tau < items[index].end_t())?;
+        let item = &items[index];
+
+        let tau = tau.max(item.start_t());
+        let out = item.tp(tau)?;
+
+        let jerk = match item {
+            _ if self.jerk.is_none() => Coord::zeros(),
+            Item::SCurve(line) => line.tp(tau)?.jerk,
+            // Constant speed along a curve that only changes gradually, so the difference between
+            // two nearby samples is close enough
+            Item::ClothoidBlend(blend) if blend.jerk_limited => {
+                let dt = (item.end_t() - item.start_t()) * T::lit(1.0e-3);
+                let (a, b) = if tau + dt <= item.end_t() {
+                    (tau, tau + dt)
+                } else {
+                    (tau - dt, tau)
+                };
+
+                (item.tp(b)?.acc - item.tp(a)?.acc) / dt
+            }
+            _ => Coord::zeros(),
+        };
+
+        let target = if self.held {
+            T::zero()
+        } else if self.trajectory.is_rapid(index) {
+            self.rapid
+        } else {
+            self.feed
+        };
+
+        Some((index, Sample { out, jerk, target }))
+    }
+
+    /// Fastest rate the planned motion in `sample` can be followed at without exceeding the
+    /// limits.
+    fn rate_limit(&self, sample: &Sample<N, T>) -> T {
+        let eps = T::default_epsilon();
+        let out = &sample.out;
+
+        (0..N).fold(T::lit(MAX_OVERRIDE), |limit, axis| {
+            let vel = out.vel[axis].abs();
+            let acc = out.acc[axis].abs();
+            let jerk = sample.jerk[axis].abs();
+
+            let limit = match self.jerk {
+                Some(lim) if jerk > eps => limit.min((lim[axis] / jerk).cbrt()),
+                _ => limit,
+            };
+
+            let limit = if vel > eps {
+                limit.min(self.lim.vel[axis] / vel)
+            } else {
+                limit
+            };
+
+            if acc > eps {
+                limit.min((self.lim.acc[axis] / acc).sqrt())
+            } else {
+                limit
+            }
+        })
+    }
+
+    /// Fastest `r` may change. Under a jerk limit this is also kept low enough that the `3·a·r·ṙ`
+    /// term of the jerk takes no more than half the limit, even at full acceleration and the
+    /// fastest override.
+    fn rate_change_limit(&self) -> T {
+        let Some(lim) = self.jerk else {
+            return self.max_rate_change;
+        };
+
+        (0..N).fold(self.max_rate_change, |limit, axis| {
+            limit.min(lim[axis] / (T::lit(6.0 * MAX_OVERRIDE) * self.lim.acc[axis]))
+        })
+    }
+
+    /// Range of `ṙ` that keeps acceleration within the limits at `rate`, and within `max_change`
+    /// either way. Acceleration is `a·r² + v·ṙ` on each axis.
+    fn rate_change_bounds(&self, out: &Out<N, T>, rate: T, max_change: T) -> (T, T) {
+        let eps = T::default_epsilon();
+
+        (0..N).fold((-max_change, max_change), |(lo, hi), axis| {
+            let vel = out.vel[axis];
+
+            if vel.abs() <= eps {
+                return (lo, hi);
+            }
+
+            let acc = out.acc[axis] * rate.powi(2);
+            let a = (-self.lim.acc[axis] - acc) / vel;
+            let b = (self.lim.acc[axis] - acc) / vel;
+
+            (lo.max(a.min(b)), hi.min(a.max(b)))
+        })
+    }
+
+    /// Range of `ṙ` that keeps the change in acceleration since the last sample within the jerk
+    /// limits at `rate`, and the largest `r̈` the limits always allow. Acceleration is
+    /// `a·r² + v·ṙ` on each axis, which is linear in `ṙ`.
+    fn rate_jerk_bounds(&self, out: &Out<N, T>, rate: T) -> (T, T, T) {
+        let eps = T::default_epsilon();
+        let infinity = T::lit(f64::INFINITY);
+
+        let Some(lim) = self.jerk else {
+            return (-infinity, infinity, infinity);
+        };
+
+        (0..N).fold((-infinity, infinity, infinity), |(lo, hi, max), axis| {
+            let vel = out.vel[axis];
+
+            if vel.abs() <= eps {
+                return (lo, hi, max);
+            }
+
+            let step = lim[axis] * self.period;
+            let acc = self.acc[axis] - out.acc[axis] * rate.powi(2);
+            let a = (acc - step) / vel;
+            let b = (acc + step) / vel;
+
+            (
+                lo.max(a.min(b)),
+                hi.min(a.max(b)),
+                max.min(lim[axis] / vel.abs()),
+            )
+        })
+    }
+
+    /// Fastest rate allowed at each of the next few samples, so that `r` can always slow down in
+    /// time for the slowest of them. Samples are spaced `step` apart in planned time.
+    fn lookahead(&self, step: T) -> [T; LOOKAHEAD_SAMPLES] {
+        let two = T::lit(2.0);
+        let max_change = self.rate_change_limit();
+
+        let mut samples: [Option<Sample<N, T>>; LOOKAHEAD_SAMPLES] = core::array::from_fn(|_| None);
+        let mut cursor = self.cursor;
+
+        for (k, sample) in samples.iter_mut().enumerate() {
+            let tau = self.tau + step * T::lit(k as f64);
+
+            if let Some((index, s)) = self.sample(cursor, tau) {
+                cursor = index;
+                *sample = Some(s);
+            }
+        }
+
+        let mut allowed = [T::zero(); LOOKAHEAD_SAMPLES];
+
+        // Past the lookahead, only the planned rate is known to be safe
+        let mut next = T::one();
+
+        for (k, sample) in samples.iter().enumerate().rev() {
+            let Some(sample) = sample else {
+                // Past the end of the trajectory, which finishes at rest
+                allowed[k] = T::one();
+                next = T::one();
+
+                continue;
+            };
+
+            let cap = self.rate_limit(sample).min(sample.target);
+
+            // Fastest this sample could be if it slowed down at the full rate, then limited by how
+            // quickly the axes can actually slow down at that speed. `d(r²)/dτ = 2ṙ`.
+            let fastest = (next.powi(2) + two * max_change * step).sqrt().min(cap);
+            let (lo, _hi) = self.rate_change_bounds(&sample.out, fastest, max_change);
+            let decel = (-lo).max(T::zero());
+
+            // Leave some of the deceleration spare for ramping `ṙ` up and down under a jerk limit
+            let decel = if self.jerk.is_some() {
+                decel / two
+            } else {
+                decel
+            };
+
+            allowed[k] = (next.powi(2) + two * decel * step).sqrt().min(cap);
+            next = allowed[k];
+        }
+
+        allowed
+    }
+}
+
+impl<'a, const N: usize, T: Real> Iterator for FeedOverride<'a, N, T> {
+    type Item = Out<N, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let Some((index, current)) = self.sample(self.cursor, self.tau) else {
+            self.finished = true;
+            self.cursor = self.trajectory.items.len();
+
+            let last = self
+                .trajectory
+                .items
+                .last()
+                .expect("Trajectory has at least one item");
+
+            return Some(Out {
+                pos: last.q1(),
+                vel: Coord::zeros(),
+                acc: Coord::zeros(),
+            });
+        };
+
+        self.cursor = index;
+
+        // Far enough ahead to slow down from the fastest override to 100%
+        let horizon = T::lit(MAX_OVERRIDE) * T::lit(MAX_OVERRIDE) / self.rate_change_limit();
+        let step = horizon / T::lit((LOOKAHEAD_SAMPLES - 1) as f64);
+        let allowed = self.lookahead(step);
+
+        if self.cycle == 0 {
+            // Start straight away at whatever rate is allowed
+            self.rate = allowed[0];
+        }
+
+        // Allowed rate where this cycle will end up, interpolated between lookahead samples
+        let ahead = self.rate * self.period / step;
+        let goal = {
+            let k = ahead.floor();
+            let frac = ahead - k;
+            let k = k.to_subset().unwrap_or(0.0) as usize;
+
+            let a = allowed[k.min(LOOKAHEAD_SAMPLES - 1)];
+            let b = allowed[(k + 1).min(LOOKAHEAD_SAMPLES - 1)];
+
+            a + (b - a) * frac
+        };
+
+        // Holding and resuming change speed as quickly as the axes allow, like a newly planned move.
+        // Under a jerk limit `ṙ` has to ramp, so they're kept to the rate the lookahead plans for.
+        let max_change = if (self.held || self.resuming) && self.jerk.is_none() {
+            T::lit(f64::INFINITY)
+        } else {
+            self.rate_change_limit()
+        };
+
+        let rate_change = if self.jerk.is_some() {
+            let infinity = T::lit(f64::INFINITY);
+            let (lo, hi) = self.rate_change_bounds(&current.out, self.rate, infinity);
+            let (jerk_lo, jerk_hi, max_jerk) = self.rate_jerk_bounds(&current.out, self.rate);
+
+            // `ṙ` takes a while to ramp back to zero, so anything allowed within that time has to
+            // be met now
+            let k = ahead.floor().to_subset().unwrap_or(0.0) as usize;
+            let ramp = self.rate_change.abs() / max_jerk * self.rate / step;
+            let ramp = ramp.ceil().to_subset().unwrap_or(0.0) as usize;
+
+            let goal = allowed
+                [(k + 1).min(LOOKAHEAD_SAMPLES - 1)..=(k + 1 + ramp).min(LOOKAHEAD_SAMPLES - 1)]
+                .iter()
+                .fold(goal, |goal, allowed| goal.min(*allowed));
+
+            // Head for the goal no faster than `ṙ` can be ramped back down to zero when it gets
+            // there
+            let error = goal - self.rate;
+            let approach = (max_jerk * error.abs()).sqrt();
+            let wanted = (error / self.period).min(approach).max(-approach);
+
+            // Acceleration takes priority if the two can't both be met
+            wanted
+                .min(max_change)
+                .max(-max_change)
+                .min(jerk_hi)
+                .max(jerk_lo)
+                .min(hi)
+                .max(lo)
+        } else {
+            let (lo, hi) = self.rate_change_bounds(&current.out, self.rate, max_change);
+
+            ((goal - self.rate) / self.period).min(hi).max(lo)
+        };
+
+        let out = Out {
+            pos: current.out.pos,
+            vel: current.out.vel * self.rate,
+            acc: current.out.acc * self.rate.powi(2) + current.out.vel * rate_change,
+        };
+
+        let rate = (self.rate + rate_change * self.period).max(T::zero());
+
+        if self.resuming && rate >= goal {
+            self.resuming = false;
+        }
+
+        self.tau += (self.rate + rate) / T::lit(2.0) * self.period;
+        self.rate = rate;
+        self.rate_change = rate_change;
+        self.acc = out.acc;
+        self.cycle += 1;
+
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{segments_blends::LinearProfile, trapezoidal_non_zero_3d::Coord3};
+
+    fn trajectory() -> Trajectory {
+        let mut trajectory = Trajectory::new();
+
+        trajectory.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+        trajectory.push_point(Coord3::new(5.0, 0.0, 0.0)).unwrap();
+        trajectory.push_point(Coord3::new(5.0, 5.0, 0.0)).unwrap();
+        trajectory.push_point(Coord3::new(5.0, 5.0, 3.0)).unwrap();
+
+        trajectory
+    }
+
+    fn assert_within_limits(samples: &[Out], lim: &Lim) {
+        for (i, sample) in samples.iter().enumerate() {
+            for axis in 0..3 {
+                assert!(
+                    sample.vel[axis].abs() <= lim.vel[axis] * 1.01,
+                    "vel {i} {:?}",
+                    sample.vel
+                );
+                assert!(
+                    sample.acc[axis].abs() <= lim.acc[axis] * 1.01,
+                    "acc {i} {:?}",
+                    sample.acc
+                );
+            }
+        }
+    }
+
+    fn assert_continuous(samples: &[Out], period: f32) {
+        for pair in samples.windows(2) {
+            let avg = (pair[0].vel + pair[1].vel) / 2.0;
+
+            assert!(
+                (pair[1].pos - pair[0].pos - avg * period).norm() < 1.0e-3,
+                "{:?} {:?}",
+                pair[0],
+                pair[1]
+            );
+        }
+    }
+
+    #[test]
+    fn full_speed_matches_plan() {
+        let trajectory = trajectory();
+        let period = 0.001;
+
+        let samples = trajectory
+            .interpolate_with_override(period)
+            .collect::<Vec<_>>();
+        let planned = trajectory.interpolate(period).collect::<Vec<_>>();
+
+        assert_eq!(samples.len(), planned.len());
+        assert_eq!(samples.last().unwrap().pos, Coord3::new(5.0, 5.0, 3.0));
+    }
+
+    #[test]
+    fn half_speed() {
+        let trajectory = trajectory();
+        let period = 0.001;
+
+        let mut interpolator = trajectory.interpolate_with_override(period);
+        interpolator.set_feed(0.5);
+
+        let samples = interpolator.collect::<Vec<_>>();
+        let duration = samples.len() as f32 * period;
+
+        // Slower than half speed as corners are taken at the same speed, but no slower than that
+        assert!(duration > trajectory.total_time * 1.5, "{duration}");
+        assert!(
+            duration < trajectory.total_time * 2.0 + period,
+            "{duration}"
+        );
+        assert_continuous(&samples, period);
+        assert_eq!(samples.last().unwrap().pos, Coord3::new(5.0, 5.0, 3.0));
+    }
+
+    #[test]
+    fn double_speed_within_limits() {
+        let mut trajectory = Trajectory::new();
+        trajectory.limits.vel = Coord3::new(10.0, 10.0, 10.0);
+        trajectory.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+        trajectory
+            .push_point_with_limits(
+                Coord3::new(20.0, 0.0, 0.0),
+                Lim {
+                    vel: Coord3::new(4.0, 4.0, 4.0),
+                    acc: trajectory.limits.acc,
+                },
+            )
+            .unwrap();
+
+        let period = 0.001;
+
+        let mut interpolator = trajectory.interpolate_with_override(period);
+        interpolator.set_feed(2.0);
+
+        let samples = interpolator.collect::<Vec<_>>();
+        let duration = samples.len() as f32 * period;
+
+        assert!(duration < trajectory.total_time * 0.75, "{duration}");
+        assert!(samples.iter().any(|sample| sample.vel.x > 7.9));
+        assert_within_limits(&samples, &trajectory.limits);
+        assert_continuous(&samples, period);
+        assert_eq!(samples.last().unwrap().pos, Coord3::new(20.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn jerk_limited_ramp() {
+        let jerk = Coord3::new(100.0, 100.0, 100.0);
+
+        let mut trajectory = Trajectory::new();
+        trajectory.limits.vel = Coord3::new(10.0, 10.0, 10.0);
+        trajectory.profile = LinearProfile::SCurve { jerk };
+        trajectory.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+        trajectory
+            .push_point_with_limits(
+                Coord3::new(40.0, 0.0, 0.0),
+                Lim {
+                    vel: Coord3::new(4.0, 4.0, 4.0),
+                    acc: trajectory.limits.acc,
+                },
+            )
+            .unwrap();
+
+        let period = 0.001;
+
+        let mut interpolator = trajectory.interpolate_with_override(period);
+
+        assert_eq!(interpolator.jerk, Some(jerk));
+
+        // Cruising at 100%, then straight up to 200%
+        let mut samples = interpolator.by_ref().take(1500).collect::<Vec<_>>();
+
+        interpolator.set_feed(2.0);
+
+        samples.extend(interpolator);
+
+        assert_within_limits(&samples, &trajectory.limits);
+        assert_continuous(&samples, period);
+        assert_eq!(samples.last().unwrap().pos, Coord3::new(40.0, 0.0, 0.0));
+        assert!(samples.iter().any(|sample| sample.vel.x > 7.9));
+
+        // Through the ramp up and the planned deceleration at the end
+        for (i, pair) in samples.windows(2).enumerate() {
+            assert!(
+                (pair[1].acc - pair[0].acc).abs() <= jerk * period * 1.05,
+                "{i}: {} -> {}",
+                pair[0].acc,
+                pair[1].acc
+            );
+        }
+    }
+
+    #[test]
+    fn change_while_running() {
+        let trajectory = trajectory();
+        let period = 0.001;
+
+        let mut interpolator = trajectory.interpolate_with_override(period);
+        let mut samples = Vec::new();
+
+        // Step the override around mid move, including stopping completely for a while
+        for (i, feed) in [1.0, 2.0, 0.3, 0.0, 1.5, 1.0].into_iter().enumerate() {
+            interpolator.set_feed(feed);
+
+            samples.extend(interpolator.by_ref().take(if i == 3 { 1500 } else { 700 }));
+        }
+
+        samples.extend(interpolator);
+
+        assert_within_limits(&samples, &trajectory.limits);
+        assert_continuous(&samples, period);
+        assert_eq!(samples.last().unwrap().pos, Coord3::new(5.0, 5.0, 3.0));
+
+        // Stopped while the override was zero
+        assert!(samples[3000..3100]
+            .iter()
+            .all(|sample| sample.vel.norm() < 1.0e-6));
+    }
+
+    #[test]
+    fn hold_and_resume() {
+        let mut trajectory = Trajectory::new();
+        trajectory.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+        trajectory.push_point(Coord3::new(20.0, 0.0, 0.0)).unwrap();
+
+        let period = 0.001;
+
+        let mut interpolator = trajectory.interpolate_with_override(period);
+
+        // Cruising at full speed
+        let mut samples = interpolator.by_ref().take(1500).collect::<Vec<_>>();
+        let held_at = *samples.last().unwrap();
+
+        interpolator.hold();
+
+        while !interpolator.is_stopped() {
+            samples.push(interpolator.next().unwrap());
+        }
+
+        let stopped_at = *samples.last().unwrap();
+
+        // Stops as quickly as the acceleration limit allows
+        let distance = stopped_at.pos.x - held_at.pos.x;
+        let expected = held_at.vel.x.powi(2) / (2.0 * trajectory.limits.acc.x);
+
+        assert!((distance - expected).abs() < expected * 0.02, "{distance}");
+
+        // Stays put until resumed
+        let stopped = interpolator.by_ref().take(500).collect::<Vec<_>>();
+
+        assert!(stopped.iter().all(|sample| sample.pos == stopped_at.pos));
+        assert!(stopped.iter().all(|sample| sample.vel == Coord3::zeros()));
+
+        samples.extend(stopped);
+
+        interpolator.resume();
+
+        samples.extend(interpolator.by_ref());
+
+        assert_within_limits(&samples, &trajectory.limits);
+        assert_continuous(&samples, period);
+        assert_eq!(samples.last().unwrap().pos, Coord3::new(20.0, 0.0, 0.0));
+
+        // Back up to full speed between resuming and the end of the move
+        assert!(samples[3000..].iter().any(|sample| sample.vel.x > 4.99));
+    }
+
+    #[test]
+    fn hold_across_items() {
+        let trajectory = trajectory();
+        let period = 0.001;
+
+        let mut interpolator = trajectory.interpolate_with_override(period);
+        let mut samples = Vec::new();
+
+        // Shortly before the first corner, too late to stop before it
+        while interpolator.tau() < trajectory.items[0].end_t() - 0.05 {
+            samples.push(interpolator.next().unwrap());
+        }
+
+        let held_in = interpolator.cursor();
+
+        interpolator.hold();
+
+        while !interpolator.is_stopped() {
+            samples.push(interpolator.next().unwrap());
+        }
+
+        assert!(interpolator.cursor() > held_in);
+
+        interpolator.resume();
+
+        samples.extend(interpolator);
+
+        assert_within_limits(&samples, &trajectory.limits);
+        assert_continuous(&samples, period);
+        assert_eq!(samples.last().unwrap().pos, Coord3::new(5.0, 5.0, 3.0));
+    }
+
+    #[test]
+    fn rapid_override() {
+        let mut trajectory = Trajectory::new();
+        trajectory.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+        trajectory.rapid = true;
+        trajectory.push_point(Coord3::new(5.0, 0.0, 0.0)).unwrap();
+        trajectory.rapid = false;
+        trajectory.push_point(Coord3::new(5.0, 5.0, 0.0)).unwrap();
+
+        assert!(trajectory.is_rapid(0));
+        assert!(!trajectory.is_rapid(trajectory.items.len() - 1));
+
+        let period = 0.001;
+
+        let mut interpolator = trajectory.interpolate_with_override(period);
+        interpolator.set_rapid(0.0);
+
+        // Rapid held still, feed override doesn't apply
+        let samples = interpolator.by_ref().take(1000).collect::<Vec<_>>();
+
+        assert!(samples.iter().all(|sample| sample.pos == Coord3::zeros()));
+
+        interpolator.set_rapid(1.0);
+
+        let samples = interpolator.collect::<Vec<_>>();
+
+        assert_eq!(samples.last().unwrap().pos, Coord3::new(5.0, 5.0, 0.0));
+    }
+}
@@ -0,0 +1,961 @@
+//! G-code front end.
+//!
+//! Parses a subset of LinuxCNC flavoured G-code into a [`Trajectory`]. Supported words are:
+//!
+//! - `G0`, `G1`, `G2`, `G3` motion
+//! - `G5` cubic and `G5.1` quadratic splines in the XY plane, and `G6.2` NURBS curves
+//! - `G17`, `G18`, `G19` arc plane selection
+//! - `G20`, `G21` inch/millimeter units. Everything is converted to millimeters.
+//! - `G61`, `G61.1`, `G64` and `G64 P<tolerance>` path control modes
+//! - `G90`, `G91` absolute/incremental distance mode
+//! - `F` feed rate in units per minute
+//! - `X`, `Y` and `Z` axis words, `I`, `J` and `K` incremental arc center offsets, `R` arc radius
+//!   and `P` number of arc turns
+//! - `I`, `J`, `P` and `Q` spline control point offsets
+//! - `L` order, `P` weight and `K` knot of NURBS curves
+//!
+//! A `G6.2` block starts a NURBS curve with its first control point, which must be the current
+//! position. Following blocks with axis words add control points, and blocks with only a `K` word
+//! add the remaining knots. The curve is pushed once another motion mode is selected or the
+//! program ends, and any error with it is reported there.
+//!
+//! `N` line numbers and `M`, `S` and `T` words are accepted but ignored.
+
+use crate::{
+    arc::{ArcCenter, Direction, Plane},
+    segments_blends::{PathMode, Trajectory},
+    trapezoidal_non_zero_3d::{Coord3, Lim},
+};
+use core::fmt;
+
+const MM_PER_INCH: f32 = 25.4;
+
+/// Largest distance in millimeters between the first control point of a `G6.2` curve and the
+/// position it starts from.
+const SPLINE_START_TOLERANCE: f32 = 1.0e-4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// Line number, starting from 1.
+    pub line: usize,
+    /// Column number in characters, starting from 1.
+    pub column: usize,
+    pub kind: ErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.kind)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    /// A character that can't start a word.
+    UnexpectedCharacter(char),
+    /// A word letter wasn't followed by a valid number.
+    InvalidNumber(char),
+    /// A word letter this parser doesn't understand.
+    UnknownWord(char),
+    /// A G-code this parser doesn't understand.
+    UnsupportedGCode(f32),
+    /// A `(` comment that isn't closed before the end of the line.
+    UnterminatedComment,
+    /// Axis words were given before any motion mode was set.
+    NoMotionMode,
+    /// A feed move was programmed before any `F` word.
+    MissingFeedRate,
+    /// An `F` word with a feed rate that isn't a positive number.
+    InvalidFeedRate(f32),
+    /// An arc has neither `I`/`J`/`K` center offsets nor an `R` radius.
+    MissingArcCenter,
+    /// The arc center is not the same distance from the start and end points, or the radius is
+    /// too small to reach the end point.
+    InvalidArc,
+    /// A spline is missing control point offsets, isn't in the XY plane, or its control points,
+    /// weights and knots don't make a valid curve.
+    InvalidSpline,
+    /// A straight line move can't be planned within the machine limits and feed rate.
+    InvalidMove,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedCharacter(c) => write!(f, "unexpected character '{}'", c),
+            Self::InvalidNumber(letter) => write!(f, "invalid number after '{}'", letter),
+            Self::UnknownWord(letter) => write!(f, "unknown word '{}'", letter),
+            Self::UnsupportedGCode(code) => write!(f, "unsupported code G{}", code),
+            Self::UnterminatedComment => f.write_str("unterminated comment"),
+            Self::NoMotionMode => f.write_str("axis words given with no active motion mode"),
+            Self::MissingFeedRate => f.write_str("feed move with no feed rate set"),
+            Self::InvalidFeedRate(feed) => write!(f, "feed rate F{} must be positive", feed),
+            Self::MissingArcCenter => f.write_str("arc has no center offsets or radius"),
+            Self::InvalidArc => f.write_str("arc end point is not on the arc"),
+            Self::InvalidSpline => f.write_str("invalid spline"),
+            Self::InvalidMove => f.write_str("move can't be planned within the limits"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Motion {
+    /// `G0`
+    Rapid,
+    /// `G1`
+    Linear,
+    /// `G2`
+    ArcCw,
+    /// `G3`
+    ArcCcw,
+    /// `G5`
+    CubicSpline,
+    /// `G5.1`
+    QuadraticSpline,
+    /// `G6.2`
+    Nurbs,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Word {
+    letter: char,
+    value: f32,
+    column: usize,
+}
+
+/// Modal state carried between blocks.
+struct State {
+    position: Coord3,
+    motion: Option<Motion>,
+    plane: Plane,
+    /// Multiplier to convert program units to millimeters.
+    units: f32,
+    incremental: bool,
+    /// Feed rate in millimeters per second.
+    feed: Option<f32>,
+    /// Offset of the second control point of the last move from its end, if it was a `G5`. A
+    /// following `G5` without `I` and `J` mirrors it to continue smoothly.
+    spline_offset: Option<Coord3>,
+    /// `G6.2` curve being collected.
+    nurbs: Option<Nurbs>,
+}
+
+/// Control points, weights and knots of a `G6.2` curve collected so far.
+struct Nurbs {
+    /// Order of the curve, which is one more than its degree.
+    order: usize,
+    /// Position the curve starts from.
+    start: Coord3,
+    points: Vec<Coord3>,
+    weights: Vec<f32>,
+    knots: Vec<f32>,
+    limits: Lim,
+}
+
+/// Parse a G-code program into a blended trajectory.
+///
+/// Rapid moves use the given machine `limits`. Feed moves additionally clamp every axis velocity
+/// to the programmed feed rate.
+pub fn parse(program: &str, limits: &Lim) -> Result<Trajectory, ParseError> {
+    let mut trajectory = Trajectory::new();
+
+    trajectory.limits = *limits;
+    // Same as LinuxCNC: best possible speed, no matter the deviation from programmed points
+    trajectory.path_mode = PathMode::Blend(None);
+
+    let mut state = State {
+        position: Coord3::zeros(),
+        motion: None,
+        plane: Plane::Xy,
+        units: 1.0,
+        incremental: false,
+        feed: None,
+        spline_offset: None,
+        nurbs: None,
+    };
+
+    trajectory
+        .push_point(state.position)
+        .expect("The first point doesn't plan a move");
+
+    for (idx, line) in program.lines().enumerate() {
+        let line_no = idx + 1;
+
+        let words = words(line).map_err(|(column, kind)| ParseError {
+            line: line_no,
+            column,
+            kind,
+        })?;
+
+        block(&words, &mut state, &mut trajectory).map_err(|(column, kind)| ParseError {
+            line: line_no,
+            column,
+            kind,
+        })?;
+    }
+
+    // A curve still being collected ends with the program
+    finish_nurbs(&mut state, &mut trajectory).map_err(|kind| ParseError {
+        line: program.lines().count(),
+        column: 1,
+        kind,
+    })?;
+
+    Ok(trajectory)
+}
+
+/// Split a single line into words, skipping comments.
+fn words(line: &str) -> Result<Vec<Word>, (usize, ErrorKind)> {
+    let mut words = Vec::new();
+
+    // 1-based column numbers
+    let mut chars = line.chars().zip(1..).peekable();
+
+    while let Some((c, column)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => (),
+            // Program start/end marker
+            '%' => (),
+            // Rest of line is a comment
+            ';' => break,
+            '(' => {
+                if !chars.by_ref().any(|(c, _)| c == ')') {
+                    return Err((column, ErrorKind::UnterminatedComment));
+                }
+            }
+            c if c.is_ascii_alphabetic() => {
+                let letter = c.to_ascii_uppercase();
+
+                let mut number = String::new();
+
+                while let Some((c, _)) = chars.peek() {
+                    if c.is_ascii_digit() || matches!(c, '.' | '-' | '+') {
+                        number.push(*c);
+                    } else if !c.is_whitespace() {
+                        break;
+                    }
+
+                    chars.next();
+                }
+
+                let value = number
+                    .parse()
+                    .map_err(|_| (column, ErrorKind::InvalidNumber(letter)))?;
+
+                words.push(Word {
+                    letter,
+                    value,
+                    column,
+                });
+            }
+            c => return Err((column, ErrorKind::UnexpectedCharacter(c))),
+        }
+    }
+
+    Ok(words)
+}
+
+/// Apply a single block (line) of words to the modal state, pushing any resulting motion onto the
+/// trajectory.
+/// Split a G-code number into its major number and minor decimal, e.g. `(61, 1)` for `G61.1`, or
+/// `None` if it's negative or has more than one decimal place.
+fn g_code(value: f32) -> Option<(u32, u32)> {
+    let tenths = (value * 10.0).round();
+
+    // Numbers are parsed from decimal text, so anything more than rounding error is a second
+    // decimal place
+    if tenths < 0.0 || (value * 10.0 - tenths).abs() > 1.0e-3 {
+        return None;
+    }
+
+    let tenths = tenths as u32;
+
+    Some((tenths / 10, tenths % 10))
+}
+
+fn block(
+    words: &[Word],
+    state: &mut State,
+    trajectory: &mut Trajectory,
+) -> Result<(), (usize, ErrorKind)> {
+    let mut axes: [Option<f32>; 3] = [None; 3];
+    let mut offsets: [Option<f32>; 3] = [None; 3];
+    let mut radius = None;
+    let mut feed = None;
+    // Blend tolerance, number of arc turns, spline control point offset or NURBS weight
+    let mut p = None;
+    let mut q = None;
+    let mut knot = None;
+    let mut order = None;
+    let mut blend_mode = false;
+    let mut new_nurbs = false;
+    // Column of the first word that causes motion, used for error reporting
+    let mut motion_column = None;
+
+    // Modal codes are applied before any values are read, regardless of their order in the block
+    for word in words.iter().filter(|word| word.letter == 'G') {
+        let unsupported = (word.column, ErrorKind::UnsupportedGCode(word.value));
+
+        let Some(code) = g_code(word.value) else {
+            return Err(unsupported);
+        };
+
+        match code {
+            (0, 0) => state.motion = Some(Motion::Rapid),
+            (1, 0) => state.motion = Some(Motion::Linear),
+            (2, 0) => state.motion = Some(Motion::ArcCw),
+            (3, 0) => state.motion = Some(Motion::ArcCcw),
+            (5, 0) => state.motion = Some(Motion::CubicSpline),
+            (5, 1) => state.motion = Some(Motion::QuadraticSpline),
+            (6, 2) => {
+                state.motion = Some(Motion::Nurbs);
+                new_nurbs = true;
+            }
+            (17, 0) => state.plane = Plane::Xy,
+            (18, 0) => state.plane = Plane::Zx,
+            (19, 0) => state.plane = Plane::Yz,
+            (20, 0) => state.units = MM_PER_INCH,
+            (21, 0) => state.units = 1.0,
+            (61, 0) => trajectory.path_mode = PathMode::ExactPath,
+            (61, 1) => trajectory.path_mode = PathMode::ExactStop,
+            // Tolerance is read from the `P` word once units are known
+            (64, 0) => blend_mode = true,
+            (90, 0) => state.incremental = false,
+            (91, 0) => state.incremental = true,
+            _ => return Err(unsupported),
+        }
+
+        if matches!(code, (0 | 1 | 2 | 3 | 5, 0) | (5, 1)) {
+            motion_column.get_or_insert(word.column);
+        }
+    }
+
+    for word in words {
+        let value = word.value * state.units;
+
+        match word.letter {
+            'G' => (),
+            'X' | 'Y' | 'Z' => {
+                axes[(word.letter as u8 - b'X') as usize] = Some(value);
+                motion_column.get_or_insert(word.column);
+            }
+            'I' | 'J' => offsets[(word.letter as u8 - b'I') as usize] = Some(value),
+            // Arc center offset, or unitless NURBS knot
+            'K' => {
+                offsets[2] = Some(value);
+                knot = Some(word.value);
+            }
+            'R' => radius = Some(value),
+            'P' => p = Some(word.value),
+            'Q' => q = Some(value),
+            'L' => order = Some(word.value),
+            // Units per minute to units per second. Nothing can move at a zero or negative feed.
+            'F' if !(value.is_finite() && value > 0.0) => {
+                return Err((word.column, ErrorKind::InvalidFeedRate(word.value)))
+            }
+            'F' => feed = Some(value / 60.0),
+            'N' | 'M' | 'S' | 'T' => (),
+            letter => return Err((word.column, ErrorKind::UnknownWord(letter))),
+        }
+    }
+
+    if let Some(feed) = feed {
+        state.feed = Some(feed);
+    }
+
+    if blend_mode {
+        // `G64 P0` is the same as no tolerance at all
+        let tolerance = p.map(|p| p * state.units);
+
+        trajectory.path_mode = PathMode::Blend(tolerance.filter(|tolerance| *tolerance > 0.0));
+    }
+
+    let first_column = words.first().map_or(1, |word| word.column);
+
+    // Selecting another motion mode or starting a new curve ends the last one
+    if state.motion != Some(Motion::Nurbs) || new_nurbs {
+        finish_nurbs(state, trajectory).map_err(|kind| (first_column, kind))?;
+    }
+
+    if state.motion == Some(Motion::Nurbs) {
+        if new_nurbs {
+            let Some(limits) = feed_limits(state, trajectory, None) else {
+                return Err((first_column, ErrorKind::MissingFeedRate));
+            };
+
+            state.nurbs = Some(Nurbs {
+                order: order.map_or(0, |order| order.round() as usize),
+                start: state.position,
+                points: Vec::new(),
+                weights: Vec::new(),
+                knots: Vec::new(),
+                limits,
+            });
+        }
+
+        let Some(nurbs) = state.nurbs.as_mut() else {
+            return Err((first_column, ErrorKind::InvalidSpline));
+        };
+
+        if axes.iter().any(Option::is_some) {
+            let point = target(state.position, &axes, state.incremental);
+
+            nurbs.points.push(point);
+            nurbs.weights.push(p.unwrap_or(1.0));
+            state.position = point;
+        }
+
+        nurbs.knots.extend(knot);
+
+        return Ok(());
+    }
+
+    let Some(column) = motion_column else {
+        return Ok(());
+    };
+
+    // A motion G-code on its own with no axis words doesn't move
+    if axes.iter().all(Option::is_none) {
+        return Ok(());
+    }
+
+    let Some(motion) = state.motion else {
+        return Err((column, ErrorKind::NoMotionMode));
+    };
+
+    let start = state.position;
+
+    let mut end = target(start, &axes, state.incremental);
+
+    let limits = match motion {
+        Motion::Rapid => trajectory.limits,
+        _ => {
+            let direction = (motion == Motion::Linear).then(|| end - start);
+
+            let Some(limits) = feed_limits(state, trajectory, direction) else {
+                return Err((column, ErrorKind::MissingFeedRate));
+            };
+
+            limits
+        }
+    };
+
+    // Rapids follow the rapid override rather than the feed override
+    trajectory.rapid = matches!(motion, Motion::Rapid);
+
+    // Only a `G5` straight after another can leave out its first control point
+    let spline_offset = state.spline_offset.take();
+
+    match motion {
+        Motion::Rapid | Motion::Linear => {
+            // Zero length moves would produce a degenerate segment
+            if end != start && trajectory.push_point_with_limits(end, limits).is_err() {
+                return Err((column, ErrorKind::InvalidMove));
+            }
+        }
+        Motion::ArcCw | Motion::ArcCcw => {
+            let (a, b, _) = state.plane.axes();
+
+            let center = if offsets[a].is_some() || offsets[b].is_some() {
+                let mut center = start;
+
+                center[a] += offsets[a].unwrap_or(0.0);
+                center[b] += offsets[b].unwrap_or(0.0);
+
+                ArcCenter::Point(center)
+            } else if let Some(radius) = radius {
+                ArcCenter::Radius(radius)
+            } else {
+                return Err((column, ErrorKind::MissingArcCenter));
+            };
+
+            let direction = if motion == Motion::ArcCw {
+                Direction::Clockwise
+            } else {
+                Direction::Counterclockwise
+            };
+
+            // `P` counts the turns including the last partial one
+            let turns = p.map_or(0, |turns| (turns.round() as usize).saturating_sub(1));
+
+            if !trajectory.push_arc_with_limits(end, center, state.plane, direction, turns, limits)
+            {
+                return Err((column, ErrorKind::InvalidArc));
+            }
+
+            // Carry on from where the arc actually ends
+            end = trajectory.points.last().copied().unwrap_or(end);
+        }
+        Motion::CubicSpline | Motion::QuadraticSpline => {
+            if state.plane != Plane::Xy {
+                return Err((column, ErrorKind::InvalidSpline));
+            }
+
+            let start_offset = (offsets[0].is_some() || offsets[1].is_some())
+                .then(|| Coord3::new(offsets[0].unwrap_or(0.0), offsets[1].unwrap_or(0.0), 0.0));
+
+            let control_points = if motion == Motion::CubicSpline {
+                let (Some(p), Some(q)) = (p, q) else {
+                    return Err((column, ErrorKind::InvalidSpline));
+                };
+
+                let first = match (start_offset, spline_offset) {
+                    (Some(offset), _) => start + offset,
+                    (None, Some(offset)) => start - offset,
+                    (None, None) => return Err((column, ErrorKind::InvalidSpline)),
+                };
+
+                let end_offset = Coord3::new(p * state.units, q, 0.0);
+
+                state.spline_offset = Some(end_offset);
+
+                vec![first, end + end_offset, end]
+            } else {
+                let Some(offset) = start_offset else {
+                    return Err((column, ErrorKind::InvalidSpline));
+                };
+
+                let mut control = start + offset;
+
+                control.z = (start.z + end.z) / 2.0;
+
+                vec![control, end]
+            };
+
+            // The start is a control point too, so the degree is the number of others
+            let degree = control_points.len();
+
+            if !trajectory.push_spline_with_limits(degree, &control_points, None, None, limits) {
+                return Err((column, ErrorKind::InvalidSpline));
+            }
+        }
+        Motion::Nurbs => unreachable!("NURBS blocks are handled above"),
+    }
+
+    state.position = end;
+
+    Ok(())
+}
+
+/// Position the axis words of a block move to from `start`.
+fn target(start: Coord3, axes: &[Option<f32>; 3], incremental: bool) -> Coord3 {
+    let mut end = start;
+
+    for (axis, value) in axes.iter().enumerate() {
+        if let Some(value) = value {
+            end[axis] = if incremental {
+                start[axis] + value
+            } else {
+                *value
+            };
+        }
+    }
+
+    end
+}
+
+/// Machine limits with the programmed feed rate applied, or `None` if no feed rate has been set.
+///
+/// The feed rate is the speed along the path. For a straight line along `direction` each axis
+/// gets its share of it, so the line's path speed is the feed rate. Curves change direction, so
+/// every axis velocity is clamped to the feed rate instead and the curve limits its path speed
+/// from those.
+fn feed_limits(state: &State, trajectory: &Trajectory, direction: Option<Coord3>) -> Option<Lim> {
+    let feed = state.feed?;
+
+    let share = |axis: usize| match direction {
+        Some(direction) if direction[axis] != 0.0 => direction[axis].abs() / direction.norm(),
+        // Axes that don't move keep a non-zero limit
+        _ => 1.0,
+    };
+
+    Some(Lim {
+        vel: Coord3::from_fn(|axis, _| trajectory.limits.vel[axis].min(feed * share(axis))),
+        acc: trajectory.limits.acc,
+    })
+}
+
+/// Push the `G6.2` curve being collected, if any.
+fn finish_nurbs(state: &mut State, trajectory: &mut Trajectory) -> Result<(), ErrorKind> {
+    let Some(nurbs) = state.nurbs.take() else {
+        return Ok(());
+    };
+
+    let Some((first, rest)) = nurbs.points.split_first() else {
+        return Err(ErrorKind::InvalidSpline);
+    };
+
+    if (first - nurbs.start).norm() > SPLINE_START_TOLERANCE || nurbs.order < 2 {
+        return Err(ErrorKind::InvalidSpline);
+    }
+
+    if !trajectory.push_spline_with_limits(
+        nurbs.order - 1,
+        rest,
+        Some(&nurbs.weights),
+        Some(&nurbs.knots),
+        nurbs.limits,
+    ) {
+        return Err(ErrorKind::InvalidSpline);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{profile::Profile, segments_blends::Item};
+    use core::f32::consts::PI;
+    use float_cmp::assert_approx_eq;
+
+    fn limits() -> Lim {
+        Lim {
+            vel: Coord3::new(100.0, 100.0, 100.0),
+            acc: Coord3::new(500.0, 500.0, 500.0),
+        }
+    }
+
+    #[test]
+    fn square() {
+        let program = "G21\n\ng0 x0y0\n\n; make a square\ng0 y100\ng0 x100\ng0 y0\ng0 x0\n\nm2\n";
+
+        let traj = parse(program, &limits()).unwrap();
+
+        assert_eq!(
+            traj.points,
+            vec![
+                Coord3::new(0.0, 0.0, 0.0),
+                Coord3::new(0.0, 100.0, 0.0),
+                Coord3::new(100.0, 100.0, 0.0),
+                Coord3::new(100.0, 0.0, 0.0),
+                Coord3::new(0.0, 0.0, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn incremental_inches() {
+        let traj = parse("G20 G91 (one inch steps)\nG0 X1\nX1 Z-0.5", &limits()).unwrap();
+
+        assert_eq!(traj.points.last(), Some(&Coord3::new(50.8, 0.0, -12.7)));
+    }
+
+    #[test]
+    fn feed_limits() {
+        let traj = parse("G0 X10\nG1 Y10 F600", &limits()).unwrap();
+
+        let Some(Item::Linear(rapid)) = traj.items.first() else {
+            panic!("Expected linear item");
+        };
+        let Some(Item::Linear(feed)) = traj.items.last() else {
+            panic!("Expected linear item");
+        };
+
+        assert_eq!(rapid.lim(), limits());
+        assert_eq!(feed.lim().vel, Coord3::new(10.0, 10.0, 10.0));
+    }
+
+    #[test]
+    fn diagonal_feed() {
+        let traj = parse("G1 X100 Y100 F600", &limits()).unwrap();
+
+        let Some(Item::Linear(line)) = traj.items.last() else {
+            panic!("Expected linear item");
+        };
+
+        // Each moving axis gets its share of the feed rate
+        let share = 10.0 / 2.0f32.sqrt();
+
+        assert!((line.lim().vel - Coord3::new(share, share, 10.0)).norm() < 1.0e-4);
+
+        let mut t = 0.0;
+
+        while let Some((out, _)) = traj.tp(t) {
+            assert!(out.vel.norm() <= 10.0 * 1.001, "{t}: {}", out.vel.norm());
+
+            t += 0.01;
+        }
+
+        // Reaches the programmed feed rate on the way
+        assert!((traj.tp(t / 2.0).unwrap().0.vel.norm() - 10.0).abs() < 1.0e-3);
+    }
+
+    #[test]
+    fn arc_center_format() {
+        // Half circle, center at (10, 0)
+        let traj = parse("G0 X0 Y0\nG2 X20 I10 F100", &limits()).unwrap();
+
+        let Some(Item::Arc(arc)) = traj.items.last() else {
+            panic!("Expected arc item");
+        };
+
+        assert_eq!(traj.points.last(), Some(&Coord3::new(20.0, 0.0, 0.0)));
+        assert_approx_eq!(f32, arc.sweep, -PI);
+
+        for i in 0..=10 {
+            let (point, ..) = arc.geometry(arc.len * i as f32 / 10.0);
+
+            assert_approx_eq!(
+                f32,
+                (point - Coord3::new(10.0, 0.0, 0.0)).norm(),
+                10.0,
+                epsilon = 0.001
+            );
+
+            // Clockwise from (0, 0) goes through positive Y
+            assert!(point.y >= -0.001);
+        }
+    }
+
+    #[test]
+    fn arc_radius_format() {
+        let traj = parse("G3 X10 Y10 R10 F100", &limits()).unwrap();
+
+        let Some(Item::Arc(arc)) = traj.items.last() else {
+            panic!("Expected arc item");
+        };
+
+        // Quarter circle with center at (0, 10) bulges towards positive X
+        assert!((arc.center - Coord3::new(0.0, 10.0, 0.0)).norm() < 0.001);
+        assert_approx_eq!(f32, arc.sweep, PI / 2.0);
+    }
+
+    #[test]
+    fn helix_turns() {
+        let traj = parse("G91 G1 X10 F600\nG3 Z-5 I-10 P3", &limits()).unwrap();
+
+        let Some(Item::Arc(arc)) = traj.items.last() else {
+            panic!("Expected arc item");
+        };
+
+        assert_approx_eq!(f32, arc.sweep, 6.0 * PI, epsilon = 1.0e-5);
+        assert_eq!(traj.points.last(), Some(&Coord3::new(10.0, 0.0, -5.0)));
+
+        // Helix leaves the plane of the line, so the corner between them can't be blended
+        assert_eq!(traj.items.len(), 2);
+    }
+
+    #[test]
+    fn cubic_splines() {
+        let traj = parse("G1 F600\nG5 X10 I2 J2 P-2 Q2\nG5 X20 P-2 Q-2", &limits()).unwrap();
+
+        let [Item::Spline(first), Item::Spline(second)] = traj.items.as_slice() else {
+            panic!("Expected two splines");
+        };
+
+        assert_eq!(first.degree, 3);
+        assert_eq!(
+            first.control_points,
+            vec![
+                Coord3::new(0.0, 0.0, 0.0),
+                Coord3::new(2.0, 2.0, 0.0),
+                Coord3::new(8.0, 2.0, 0.0),
+                Coord3::new(10.0, 0.0, 0.0),
+            ]
+        );
+
+        // Second spline mirrors the last control point of the first, so carries on without stopping
+        assert_eq!(second.control_points[1], Coord3::new(12.0, -2.0, 0.0));
+        assert!(first.v1 > 0.0);
+        assert_eq!(traj.points.last(), Some(&Coord3::new(20.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn quadratic_spline() {
+        let traj = parse("G5.1 X10 Z2 I5 J5 F600", &limits()).unwrap();
+
+        let Some(Item::Spline(spline)) = traj.items.last() else {
+            panic!("Expected spline item");
+        };
+
+        assert_eq!(spline.degree, 2);
+        assert_eq!(spline.control_points[1], Coord3::new(5.0, 5.0, 1.0));
+
+        // Halfway along a symmetric curve is the middle of the hull
+        assert!((spline.geometry(spline.len / 2.0).0 - Coord3::new(5.0, 2.5, 1.0)).norm() < 1.0e-3);
+    }
+
+    #[test]
+    fn nurbs() {
+        // Quarter circle of radius 10 around (0, 10), then a line back to the Y axis
+        let program = "G1 F600
+            G6.2 L3 P1 K0 X0 Y0
+            P0.70710678 K0 X10
+            P1 K0 Y10
+            K1
+            K1
+            K1
+            G1 X0";
+
+        let traj = parse(program, &limits()).unwrap();
+
+        let Some(Item::Spline(spline)) = traj.items.first() else {
+            panic!("Expected spline item");
+        };
+
+        assert_eq!(spline.degree, 2);
+
+        for i in 0..=10 {
+            let (point, ..) = spline.geometry(spline.len * i as f32 / 10.0);
+
+            assert_approx_eq!(
+                f32,
+                (point - Coord3::new(0.0, 10.0, 0.0)).norm(),
+                10.0,
+                epsilon = 1.0e-3
+            );
+        }
+
+        assert_eq!(traj.points.last(), Some(&Coord3::new(0.0, 10.0, 0.0)));
+
+        // Curve still being collected at the end of the program
+        assert!(parse("G1 F600\nG6.2 L2 P1 K0 X0\nK0\nP1 K1 X10\nK1", &limits()).is_ok());
+    }
+
+    #[test]
+    fn path_modes() {
+        let traj = parse(
+            "G0 X10\nG61.1 X20\nG61 Y10\nG20 G64 P0.5 X0\nG64 P0 Y0\nG64 X10",
+            &limits(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            traj.path_modes,
+            vec![
+                PathMode::Blend(None),
+                PathMode::Blend(None),
+                PathMode::ExactStop,
+                PathMode::ExactPath,
+                PathMode::Blend(Some(12.7)),
+                PathMode::Blend(None),
+                PathMode::Blend(None),
+            ]
+        );
+    }
+
+    #[test]
+    fn finite_at_item_boundaries() {
+        for program in [
+            // Line into a spline and out again
+            "G1 X10 F600\nG5 I2 J2 P-2 Q2 X20 Y0\nG1 X30",
+            // Line into a tangent arc and out again
+            "G1 X10 F600\nG3 X20 Y10 J10\nG1 Y20",
+            // Blended corners, and colinear lines passed through at full speed
+            "G64\nG1 X10 F600\nG1 X20 Y5\nG1 X30\nG1 X40 Y5",
+            "G61\nG1 X10 F600\nG1 X20\nG1 X30",
+        ] {
+            let traj = parse(program, &limits()).unwrap();
+
+            for (i, item) in traj.items.iter().enumerate() {
+                let end = item.end();
+
+                assert!(
+                    end.pos.iter().chain(end.vel.iter()).all(|v| v.is_finite()),
+                    "{program:?} item {i} ends at {end:?}"
+                );
+
+                for t in [item.start_t(), item.start_t() + item.duration()] {
+                    let (out, _) = traj.tp(t).unwrap();
+
+                    assert!(
+                        out.pos.iter().chain(out.vel.iter()).all(|v| v.is_finite()),
+                        "{program:?} item {i} at {t}: {out:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            parse("G0 X10\nG1 X20", &limits()).unwrap_err(),
+            ParseError {
+                line: 2,
+                column: 1,
+                kind: ErrorKind::MissingFeedRate
+            }
+        );
+
+        // Close to G1 and G61.1, but not the same code
+        for (program, code) in [("G1.05 X10", 1.05), ("G61.15", 61.15), ("G4", 4.0)] {
+            assert_eq!(
+                parse(program, &limits()).unwrap_err(),
+                ParseError {
+                    line: 1,
+                    column: 1,
+                    kind: ErrorKind::UnsupportedGCode(code)
+                }
+            );
+        }
+
+        assert_eq!(
+            parse("G1 X10 F0", &limits()).unwrap_err(),
+            ParseError {
+                line: 1,
+                column: 8,
+                kind: ErrorKind::InvalidFeedRate(0.0)
+            }
+        );
+
+        assert_eq!(
+            parse("G1 F100\nG1 X10 F-100", &limits()).unwrap_err(),
+            ParseError {
+                line: 2,
+                column: 8,
+                kind: ErrorKind::InvalidFeedRate(-100.0)
+            }
+        );
+
+        assert_eq!(
+            parse("G0 X10\n  G1 X$", &limits()).unwrap_err(),
+            ParseError {
+                line: 2,
+                column: 6,
+                kind: ErrorKind::InvalidNumber('X')
+            }
+        );
+
+        assert_eq!(
+            parse("G0 X10 (unterminated", &limits()).unwrap_err(),
+            ParseError {
+                line: 1,
+                column: 8,
+                kind: ErrorKind::UnterminatedComment
+            }
+        );
+
+        assert_eq!(
+            parse("G1 F100\nG2 X10 Y10 R1", &limits()).unwrap_err(),
+            ParseError {
+                line: 2,
+                column: 1,
+                kind: ErrorKind::InvalidArc
+            }
+        );
+
+        // Nothing to mirror the first control point from
+        assert_eq!(
+            parse("G1 F100\nG5 X10 P1 Q1", &limits()).unwrap_err(),
+            ParseError {
+                line: 2,
+                column: 1,
+                kind: ErrorKind::InvalidSpline
+            }
+        );
+
+        // Curve doesn't start at the current position, which is found when it ends
+        assert_eq!(
+            parse("G1 F100\nG6.2 L2 K0 X5\nK0\nK1 X10\nK1\nG0 X0", &limits()).unwrap_err(),
+            ParseError {
+                line: 6,
+                column: 1,
+                kind: ErrorKind::InvalidSpline
+            }
+        );
+    }
+}
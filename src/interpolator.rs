@@ -0,0 +1,170 @@
+//! Fixed rate interpolation of a trajectory, e.g. for a servo loop.
+
+use crate::{
+    profile::Profile, real::Real, segments_blends::Trajectory, trapezoidal_non_zero_3d::Out,
+};
+
+/// Samples a [`Trajectory`] every `period` seconds, starting at zero.
+///
+/// A cursor into the trajectory's items is kept between samples so each step is O(1). When an item
+/// finishes part way through a cycle, the sample is taken from whichever item covers that time.
+/// The last sample is always the end of the trajectory.
+#[derive(Debug, Clone)]
+pub struct Interpolator<'a, const N: usize = 3, T: Real = f32> {
+    trajectory: &'a Trajectory<N, T>,
+    /// Servo period in seconds.
+    period: T,
+    /// Number of samples produced so far. Times are computed from this instead of summing
+    /// periods so rounding errors don't accumulate.
+    cycle: usize,
+    /// Index of the item the next sample is taken from.
+    cursor: usize,
+    /// Whether the end of the trajectory has been output.
+    finished: bool,
+}
+
+impl<'a, const N: usize, T: Real> Interpolator<'a, N, T> {
+    pub fn new(trajectory: &'a Trajectory<N, T>, period: T) -> Self {
+        assert!(
+            period > T::zero(),
+            "Period must be a positive value, got {:?}",
+            period
+        );
+
+        Self {
+            trajectory,
+            period,
+            cycle: 0,
+            cursor: 0,
+            finished: trajectory.items.is_empty(),
+        }
+    }
+
+    /// Time of the next sample.
+    pub fn t(&self) -> T {
+        T::lit(self.cycle as f64) * self.period
+    }
+
+    /// Index into [`Trajectory::items`] of the item the next sample is taken from.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+}
+
+impl<'a, const N: usize, T: Real> Iterator for Interpolator<'a, N, T> {
+    type Item = Out<N, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let t = self.t();
+        let items = &self.trajectory.items;
+
+        // Skip any items that finished before this cycle, including ones shorter than a period
+        while self.cursor < items.len() && t >= items[self.cursor].end_t() {
+            self.cursor += 1;
+        }
+
+        self.cycle += 1;
+
+        let out = items
+            .get(self.cursor)
+            .and_then(|item| item.tp(t.max(item.start_t())));
+
+        Some(out.unwrap_or_else(|| {
+            // Past the end of the last item, or rounding put `t` just outside it
+            self.finished = true;
+            self.cursor = items.len();
+
+            items
+                .last()
+                .expect("Trajectory has at least one item")
+                .end()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trapezoidal_non_zero_3d::Coord3;
+
+    fn trajectory() -> Trajectory {
+        let mut trajectory = Trajectory::new();
+
+        trajectory.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+        trajectory.push_point(Coord3::new(5.0, 0.0, 0.0)).unwrap();
+        trajectory.push_point(Coord3::new(5.0, 5.0, 0.0)).unwrap();
+        trajectory.push_point(Coord3::new(5.0, 5.0, 3.0)).unwrap();
+
+        trajectory
+    }
+
+    #[test]
+    fn matches_tp() {
+        let trajectory = trajectory();
+
+        let period = 0.001;
+
+        let samples = trajectory.interpolate(period).collect::<Vec<_>>();
+
+        // One sample per cycle, plus the end point
+        assert_eq!(
+            samples.len(),
+            (trajectory.total_time / period).ceil() as usize + 1
+        );
+
+        for (i, sample) in samples.iter().enumerate() {
+            let t = i as f32 * period;
+
+            if let Some((out, _)) = trajectory.tp(t) {
+                assert!((out.pos - sample.pos).norm() < 1.0e-4, "{t}");
+            }
+        }
+
+        assert_eq!(samples.last().unwrap().pos, Coord3::new(5.0, 5.0, 3.0));
+        assert_eq!(samples.last().unwrap().vel, Coord3::zeros());
+    }
+
+    #[test]
+    fn items_finish_mid_cycle() {
+        let trajectory = trajectory();
+
+        // Not a divisor of any item duration
+        let period = 0.0137;
+
+        let mut interpolator = trajectory.interpolate(period);
+        let mut cursors = Vec::new();
+
+        loop {
+            let t = interpolator.t();
+            let cursor = interpolator.cursor();
+
+            let Some(out) = interpolator.next() else {
+                break;
+            };
+
+            // Sampled from the item covering `t`, even if the previous item ended part way
+            // through the last cycle
+            if let Some(item) = trajectory.items.get(interpolator.cursor()) {
+                assert!(item.start_t() <= t && t < item.end_t(), "{t}");
+                assert_eq!(Some(out.pos), item.tp(t).map(|out| out.pos));
+            }
+
+            cursors.push(cursor);
+        }
+
+        // Every item was visited in order
+        assert!(cursors.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(interpolator.cursor(), trajectory.items.len());
+    }
+
+    #[test]
+    fn empty() {
+        let trajectory = Trajectory::<3>::new();
+
+        assert_eq!(trajectory.interpolate(0.001).count(), 0);
+    }
+}
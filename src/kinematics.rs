@@ -0,0 +1,430 @@
+//! Machine kinematics.
+//!
+//! Trajectories are planned in cartesian space, then checked against the limits of each joint
+//! (motor, actuator) by transforming samples through a [`Kinematics`] implementation.
+
+use crate::{
+    real::Real,
+    trapezoidal_non_zero_3d::{Coord, Lim, Out},
+};
+
+/// Joint positions, one per motor or actuator.
+pub type Joints<const N: usize = 3, T = f32> = Coord<N, T>;
+
+/// Relative amount a joint limit can be exceeded by before it's reported. This absorbs rounding
+/// errors for trajectories planned right up to the limit.
+const LIMIT_TOLERANCE: f64 = 1.0e-3;
+
+/// Conversion between cartesian and joint space for a machine with `N` axes and `N` joints.
+pub trait Kinematics<const N: usize = 3, T: Real = f32> {
+    /// Velocity and acceleration limits of each joint.
+    fn joint_limits(&self) -> Lim<N, T>;
+
+    /// Convert joint positions into a cartesian position.
+    fn forward(&self, joints: Joints<N, T>) -> Option<Coord<N, T>>;
+
+    /// Convert a cartesian position into joint positions, or `None` if the position can't be
+    /// reached.
+    fn inverse(&self, pos: Coord<N, T>) -> Option<Joints<N, T>>;
+
+    /// Convert a cartesian position, velocity and acceleration into joint space.
+    ///
+    /// The default implementation numerically differentiates [`inverse`](Self::inverse) along the
+    /// local path described by `out`. Implementations with a known Jacobian should override this.
+    fn inverse_out(&self, out: &Out<N, T>) -> Option<Out<N, T>> {
+        // Large enough step to keep `f32` rounding out of the second difference
+        let dt = T::lit(1.0e-2);
+        let two = T::lit(2.0);
+
+        let pos_at = |t: T| out.pos + out.vel * t + out.acc * (t.powi(2) / two);
+
+        let prev = self.inverse(pos_at(-dt))?;
+        let pos = self.inverse(out.pos)?;
+        let next = self.inverse(pos_at(dt))?;
+
+        Some(Out {
+            pos,
+            vel: (next - prev) / (two * dt),
+            acc: (next - pos * two + prev) / dt.powi(2),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimitError<const N: usize = 3, T: Real = f32> {
+    /// The cartesian position at time `t` can't be reached by the machine.
+    Unreachable { t: T, pos: Coord<N, T> },
+    /// A joint's velocity limit is exceeded at time `t`.
+    Velocity { t: T, joint: usize, vel: T },
+    /// A joint's acceleration limit is exceeded at time `t`.
+    Acceleration { t: T, joint: usize, acc: T },
+}
+
+/// Check a single cartesian sample at time `t` against the joint limits.
+pub fn check_sample<const N: usize, T: Real, K: Kinematics<N, T> + ?Sized>(
+    kinematics: &K,
+    t: T,
+    out: &Out<N, T>,
+) -> Result<Out<N, T>, LimitError<N, T>> {
+    let joints = kinematics
+        .inverse_out(out)
+        .ok_or(LimitError::Unreachable { t, pos: out.pos })?;
+
+    let lim = kinematics.joint_limits();
+    let tolerance = T::one() + T::lit(LIMIT_TOLERANCE);
+
+    for joint in 0..joints.pos.len() {
+        let vel = joints.vel[joint];
+        let acc = joints.acc[joint];
+
+        if vel.abs() > lim.vel[joint] * tolerance {
+            return Err(LimitError::Velocity { t, joint, vel });
+        }
+
+        if acc.abs() > lim.acc[joint] * tolerance {
+            return Err(LimitError::Acceleration { t, joint, acc });
+        }
+    }
+
+    Ok(joints)
+}
+
+/// Sample `tp` every `dt` seconds from `start_t` to `end_t` inclusive, checking each sample
+/// against the joint limits.
+pub fn check_limits<const N: usize, T: Real, K: Kinematics<N, T> + ?Sized>(
+    kinematics: &K,
+    start_t: T,
+    end_t: T,
+    dt: T,
+    tp: impl Fn(T) -> Option<Out<N, T>>,
+) -> Result<(), LimitError<N, T>> {
+    let num_samples = nalgebra::try_convert::<T, f64>(((end_t - start_t) / dt).ceil())
+        .unwrap_or(0.0)
+        .max(0.0) as usize;
+
+    for i in 0..=num_samples {
+        let t = (start_t + T::lit(i as f64) * dt).min(end_t);
+
+        // Some profiles don't include their end time
+        if let Some(out) = tp(t) {
+            check_sample(kinematics, t, &out)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Cartesian machine where each joint drives a single axis directly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cartesian<const N: usize = 3, T: Real = f32> {
+    pub limits: Lim<N, T>,
+}
+
+impl<const N: usize, T: Real> Kinematics<N, T> for Cartesian<N, T> {
+    fn joint_limits(&self) -> Lim<N, T> {
+        self.limits
+    }
+
+    fn forward(&self, joints: Joints<N, T>) -> Option<Coord<N, T>> {
+        Some(joints)
+    }
+
+    fn inverse(&self, pos: Coord<N, T>) -> Option<Joints<N, T>> {
+        Some(pos)
+    }
+
+    fn inverse_out(&self, out: &Out<N, T>) -> Option<Out<N, T>> {
+        Some(*out)
+    }
+}
+
+/// CoreXY machine. Joints are the A and B belt motors and a directly driven Z axis, where
+/// `A = X + Y` and `B = X - Y`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoreXy<T: Real = f32> {
+    pub limits: Lim<3, T>,
+}
+
+impl<T: Real> CoreXy<T> {
+    fn to_joints(v: Coord<3, T>) -> Joints<3, T> {
+        Joints::<3, T>::new(v.x + v.y, v.x - v.y, v.z)
+    }
+}
+
+impl<T: Real> Kinematics<3, T> for CoreXy<T> {
+    fn joint_limits(&self) -> Lim<3, T> {
+        self.limits
+    }
+
+    fn forward(&self, joints: Joints<3, T>) -> Option<Coord<3, T>> {
+        let two = T::lit(2.0);
+
+        Some(Coord::<3, T>::new(
+            (joints.x + joints.y) / two,
+            (joints.x - joints.y) / two,
+            joints.z,
+        ))
+    }
+
+    fn inverse(&self, pos: Coord<3, T>) -> Option<Joints<3, T>> {
+        Some(Self::to_joints(pos))
+    }
+
+    // Transform is linear so derivatives map the same way as positions
+    fn inverse_out(&self, out: &Out<3, T>) -> Option<Out<3, T>> {
+        Some(Out {
+            pos: Self::to_joints(out.pos),
+            vel: Self::to_joints(out.vel),
+            acc: Self::to_joints(out.acc),
+        })
+    }
+}
+
+/// Which way a SCARA arm's elbow bends. Most positions can be reached with either.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Elbow {
+    /// Positive elbow angle.
+    Left,
+    /// Negative elbow angle.
+    Right,
+}
+
+/// Two link SCARA arm with a linear Z axis.
+///
+/// Joints are the shoulder angle, elbow angle (both in radians, zero when the arm is fully
+/// extended along X) and Z position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scara<T: Real = f32> {
+    /// Length of the shoulder to elbow link.
+    pub l1: T,
+    /// Length of the elbow to tool link.
+    pub l2: T,
+    pub elbow: Elbow,
+    pub limits: Lim<3, T>,
+}
+
+impl<T: Real> Kinematics<3, T> for Scara<T> {
+    fn joint_limits(&self) -> Lim<3, T> {
+        self.limits
+    }
+
+    fn forward(&self, joints: Joints<3, T>) -> Option<Coord<3, T>> {
+        let Self { l1, l2, .. } = *self;
+
+        let (shoulder, elbow) = (joints.x, joints.y);
+
+        Some(Coord::<3, T>::new(
+            l1 * shoulder.cos() + l2 * (shoulder + elbow).cos(),
+            l1 * shoulder.sin() + l2 * (shoulder + elbow).sin(),
+            joints.z,
+        ))
+    }
+
+    fn inverse(&self, pos: Coord<3, T>) -> Option<Joints<3, T>> {
+        let Self { l1, l2, .. } = *self;
+
+        let elbow_cos =
+            (pos.x.powi(2) + pos.y.powi(2) - l1.powi(2) - l2.powi(2)) / (T::lit(2.0) * l1 * l2);
+
+        // Out of reach
+        if elbow_cos.abs() > T::one() {
+            return None;
+        }
+
+        let elbow_sin = match self.elbow {
+            Elbow::Left => (T::one() - elbow_cos.powi(2)).sqrt(),
+            Elbow::Right => -(T::one() - elbow_cos.powi(2)).sqrt(),
+        };
+
+        let elbow = elbow_sin.atan2(elbow_cos);
+        let shoulder = pos.y.atan2(pos.x) - (l2 * elbow_sin).atan2(l1 + l2 * elbow_cos);
+
+        Some(Joints::<3, T>::new(shoulder, elbow, pos.z))
+    }
+
+    fn inverse_out(&self, out: &Out<3, T>) -> Option<Out<3, T>> {
+        let Self { l1, l2, .. } = *self;
+
+        let pos = self.inverse(out.pos)?;
+
+        let (s1, c1) = pos.x.sin_cos();
+        let (s12, c12) = (pos.x + pos.y).sin_cos();
+
+        // Jacobian determinant. Zero when the arm is fully extended or folded.
+        let det = l1 * l2 * pos.y.sin();
+
+        if det.abs() <= T::default_epsilon() {
+            return None;
+        }
+
+        // Solve J * q' = x' for the two planar joints
+        let solve = |x: T, y: T| {
+            (
+                (l2 * c12 * x + l2 * s12 * y) / det,
+                (-(l1 * c1 + l2 * c12) * x - (l1 * s1 + l2 * s12) * y) / det,
+            )
+        };
+
+        let (shoulder_vel, elbow_vel) = solve(out.vel.x, out.vel.y);
+
+        // q'' = J^-1 (x'' - J' q')
+        let outer_vel = shoulder_vel + elbow_vel;
+        let (shoulder_acc, elbow_acc) = solve(
+            out.acc.x + l1 * c1 * shoulder_vel.powi(2) + l2 * c12 * outer_vel.powi(2),
+            out.acc.y + l1 * s1 * shoulder_vel.powi(2) + l2 * s12 * outer_vel.powi(2),
+        );
+
+        Some(Out {
+            pos,
+            vel: Joints::<3, T>::new(shoulder_vel, elbow_vel, out.vel.z),
+            acc: Joints::<3, T>::new(shoulder_acc, elbow_acc, out.acc.z),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trapezoidal_non_zero_3d::{Coord3, Segment};
+    use float_cmp::assert_approx_eq;
+
+    fn limits() -> Lim {
+        Lim {
+            vel: Coord3::new(1.5, 1.5, 1.5),
+            acc: Coord3::new(5.0, 5.0, 5.0),
+        }
+    }
+
+    #[test]
+    fn corexy_round_trip() {
+        let kinematics = CoreXy { limits: limits() };
+
+        let pos = Coord3::new(3.0, -2.0, 1.0);
+        let joints = kinematics.inverse(pos).unwrap();
+
+        assert_eq!(joints, Coord3::new(1.0, 5.0, 1.0));
+        assert_eq!(kinematics.forward(joints), Some(pos));
+    }
+
+    #[test]
+    fn corexy_diagonal_limits() {
+        let kinematics = CoreXy { limits: limits() };
+
+        // Cartesian limits allow both X and Y to move at full speed, but that's twice the speed
+        // for the A motor.
+        let seg = Segment::new(
+            Coord3::zeros(),
+            Coord3::new(10.0, 10.0, 0.0),
+            Coord3::zeros(),
+            Coord3::zeros(),
+            0.0,
+            &Lim {
+                vel: Coord3::new(1.0, 1.0, 1.0),
+                acc: Coord3::new(2.0, 2.0, 2.0),
+            },
+        );
+
+        assert!(matches!(
+            seg.check_joint_limits(&kinematics, 0.01),
+            Err(LimitError::Velocity { joint: 0, .. })
+        ));
+
+        // Moving along X alone drives both motors at the X speed
+        let seg = Segment::new(
+            Coord3::zeros(),
+            Coord3::new(10.0, 0.0, 0.0),
+            Coord3::zeros(),
+            Coord3::zeros(),
+            0.0,
+            &Lim {
+                vel: Coord3::new(1.0, 1.0, 1.0),
+                acc: Coord3::new(2.0, 2.0, 2.0),
+            },
+        );
+
+        assert_eq!(seg.check_joint_limits(&kinematics, 0.01), Ok(()));
+    }
+
+    #[test]
+    fn scara_round_trip() {
+        for elbow in [Elbow::Left, Elbow::Right] {
+            let kinematics = Scara {
+                l1: 2.0,
+                l2: 1.5,
+                elbow,
+                limits: limits(),
+            };
+
+            let pos = Coord3::new(1.0, 2.0, 0.5);
+            let joints = kinematics.inverse(pos).unwrap();
+            let round_trip = kinematics.forward(joints).unwrap();
+
+            assert_approx_eq!(f32, round_trip.x, pos.x, epsilon = 1.0e-5);
+            assert_approx_eq!(f32, round_trip.y, pos.y, epsilon = 1.0e-5);
+            assert_approx_eq!(f32, round_trip.z, pos.z, epsilon = 1.0e-5);
+        }
+    }
+
+    #[test]
+    fn scara_unreachable() {
+        let kinematics = Scara {
+            l1: 2.0,
+            l2: 1.5,
+            elbow: Elbow::Left,
+            limits: limits(),
+        };
+
+        assert_eq!(kinematics.inverse(Coord3::new(4.0, 0.0, 0.0)), None);
+    }
+
+    #[test]
+    fn scara_derivatives() {
+        let kinematics = Scara {
+            l1: 2.0,
+            l2: 1.5,
+            elbow: Elbow::Left,
+            limits: limits(),
+        };
+
+        let out = Out {
+            pos: Coord3::new(1.0, 2.0, 0.0),
+            vel: Coord3::new(0.5, -0.3, 0.1),
+            acc: Coord3::new(-1.0, 2.0, 0.0),
+        };
+
+        // Analytic Jacobian should agree with the numerical default implementation
+        struct Numeric(Scara);
+
+        impl Kinematics for Numeric {
+            fn joint_limits(&self) -> Lim {
+                self.0.joint_limits()
+            }
+
+            fn forward(&self, joints: Joints) -> Option<Coord3> {
+                self.0.forward(joints)
+            }
+
+            fn inverse(&self, pos: Coord3) -> Option<Joints> {
+                self.0.inverse(pos)
+            }
+        }
+
+        let analytic = kinematics.inverse_out(&out).unwrap();
+        let numeric = Numeric(kinematics).inverse_out(&out).unwrap();
+
+        for joint in 0..3 {
+            assert_approx_eq!(
+                f32,
+                analytic.vel[joint],
+                numeric.vel[joint],
+                epsilon = 1.0e-3
+            );
+            assert_approx_eq!(
+                f32,
+                analytic.acc[joint],
+                numeric.acc[joint],
+                epsilon = 1.0e-2
+            );
+        }
+    }
+}
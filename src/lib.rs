@@ -1,8 +1,34 @@
+//! Trajectory planning.
+//!
+//! The planners only need `alloc`. Disable the default `std` feature to use them in `no_std`
+//! firmware.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+pub mod arc;
 pub mod arc_blend;
+pub mod bezier_blend;
+pub mod blend;
+pub mod clothoid_blend;
+pub mod error;
+pub mod feed_override;
+#[cfg(feature = "std")]
+pub mod gcode;
+pub mod interpolator;
+pub mod kinematics;
+pub mod profile;
+pub mod real;
 pub mod scurve;
+pub mod scurve_3d;
 pub mod trapezoidal;
 // pub mod trapezoidal_arc_blends;
 pub mod segments_blends;
+pub mod spline;
 pub mod synchronised;
+pub mod topp;
 pub mod trapezoidal_non_zero;
 pub mod trapezoidal_non_zero_3d;
+#[cfg(feature = "urdf")]
+pub mod urdf;
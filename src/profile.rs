@@ -0,0 +1,299 @@
+//! Common interface to every segment and blend, so they can be sampled, plotted and joined
+//! together without knowing which kind of profile they are.
+
+use crate::real::Real;
+use alloc::vec::Vec;
+
+/// A motion that runs from [`start_t`](Self::start_t) for [`duration`](Self::duration) seconds.
+///
+/// `Out` is whatever the profile produces at one instant, e.g. a single axis
+/// [`trapezoidal_non_zero::Out`](crate::trapezoidal_non_zero::Out) or a multi-axis
+/// [`trapezoidal_non_zero_3d::Out`](crate::trapezoidal_non_zero_3d::Out).
+pub trait Profile<T: Real = f32> {
+    /// Position, velocity, etc at one instant.
+    type Out;
+
+    /// Time this profile starts at.
+    fn start_t(&self) -> T;
+
+    /// Duration of this profile.
+    fn duration(&self) -> T;
+
+    /// Time this profile finishes at.
+    fn end_t(&self) -> T {
+        self.start_t() + self.duration()
+    }
+
+    /// Get trajectory parameters at the given time `t`, or `None` if `t` is outside this profile.
+    fn tp(&self, t: T) -> Option<Self::Out>;
+
+    /// Times the profile moves from one phase to the next, e.g. from accelerating to cruising, in
+    /// order. Phases with no duration are skipped, and the start and end times aren't included.
+    fn phase_times(&self) -> Vec<T>;
+
+    /// State at the start of this profile.
+    fn start(&self) -> Self::Out {
+        self.tp(self.start_t())
+            .expect("Profile covers its own start time")
+    }
+
+    /// State at the end of this profile. Profiles that don't include their end time are sampled
+    /// just before it instead.
+    fn end(&self) -> Self::Out {
+        let end_t = self.end_t();
+
+        self.tp(end_t)
+            .or_else(|| {
+                let before = end_t - end_t.abs().max(T::one()) * T::default_epsilon() * T::lit(4.0);
+
+                self.tp(before.max(self.start_t()))
+            })
+            .expect("Profile covers its own end time")
+    }
+}
+
+/// Phase change times of a profile starting at `start_t` made of phases with the given durations.
+pub(crate) fn phase_times<T: Real>(start_t: T, durations: &[T]) -> Vec<T> {
+    let mut t = start_t;
+
+    let mut times = durations
+        .iter()
+        .filter(|duration| **duration > T::zero())
+        .map(|duration| {
+            t += *duration;
+
+            t
+        })
+        .collect::<Vec<_>>();
+
+    // The end of the last phase is the end of the profile
+    times.pop();
+
+    times
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        arc::{Arc, ArcCenter, Direction, Plane},
+        arc_blend::ArcBlend,
+        scurve, scurve_3d,
+        segments_blends::Trajectory,
+        synchronised, trapezoidal, trapezoidal_non_zero,
+        trapezoidal_non_zero_3d::{self, Coord},
+    };
+
+    /// Check the parts of the profile every implementation has to agree on, returning its phase
+    /// change times.
+    fn check<P: Profile<f64> + ?Sized>(profile: &P) -> Vec<f64> {
+        let start_t = profile.start_t();
+        let end_t = profile.end_t();
+
+        assert!(profile.duration() > 0.0);
+        assert!(profile.tp(start_t).is_some());
+        assert!(profile.tp((start_t + end_t) / 2.0).is_some());
+        assert!(profile.tp(start_t - 0.1).is_none());
+        assert!(profile.tp(end_t + 0.1).is_none());
+
+        profile.start();
+        profile.end();
+
+        let times = profile.phase_times();
+
+        assert!(
+            times.iter().all(|t| *t > start_t && *t < end_t),
+            "{times:?}"
+        );
+        assert!(times.windows(2).all(|pair| pair[0] < pair[1]), "{times:?}");
+
+        times
+    }
+
+    #[test]
+    fn single_axis() {
+        let lim = trapezoidal::Lim {
+            vel: 2.0,
+            acc: 5.0,
+            jerk: 0.0,
+        };
+
+        let segment = trapezoidal::Segment::try_new(0.0, 10.0, 0.0, 0.0, &lim).unwrap();
+
+        // Accelerate, cruise, decelerate
+        assert_eq!(check(&segment).len(), 2);
+        assert!((segment.end().pos - 10.0).abs() < 1.0e-9);
+
+        let segment = trapezoidal_non_zero::Segment::new(
+            0.0,
+            10.0,
+            1.0,
+            0.5,
+            &trapezoidal_non_zero::Lim { vel: 2.0, acc: 5.0 },
+        );
+
+        assert_eq!(check(&segment).len(), 2);
+        assert!((segment.start().vel - 1.0).abs() < 1.0e-9);
+        assert!((segment.end().vel - 0.5).abs() < 1.0e-9);
+
+        let lim = scurve::Lim {
+            vel: 10.0,
+            acc: 10.0,
+            jerk: 40.0,
+        };
+
+        let segment = scurve::Segment::new(1.0, 0.0, 20.0, 0.0, 0.0, &lim);
+
+        // Every jerk, acceleration and cruise phase
+        assert_eq!(check(&segment).len(), 6);
+        assert!((segment.end().pos - 20.0).abs() < 1.0e-6);
+
+        let stop = scurve::Stop::new(1.0, 0.0, 10.0, 0.0, &lim);
+
+        assert_eq!(check(&stop).len(), 2);
+        assert!(stop.end().vel.abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn multi_axis() {
+        let q0 = Coord::<3, f64>::new(1.0, 2.0, 3.0);
+        let q1 = Coord::<3, f64>::new(11.0, -3.0, 3.0);
+
+        let segment = trapezoidal_non_zero_3d::Segment::new(
+            q0,
+            q1,
+            Coord::zeros(),
+            Coord::zeros(),
+            1.0,
+            &trapezoidal_non_zero_3d::Lim {
+                vel: Coord::repeat(2.0),
+                acc: Coord::repeat(5.0),
+            },
+        );
+
+        assert_eq!(check(&segment).len(), 2);
+        assert!((segment.start().pos - q0).norm() < 1.0e-9);
+        assert!((segment.end().pos - q1).norm() < 1.0e-9);
+
+        let segment = synchronised::Segment::new(
+            q0,
+            q1,
+            Coord::zeros(),
+            Coord::zeros(),
+            1.0,
+            &synchronised::Lim {
+                vel: Coord::repeat(2.0),
+                acc: Coord::repeat(5.0),
+            },
+        );
+
+        check(&segment);
+
+        let segment = scurve_3d::Segment::new(
+            q0,
+            q1,
+            Coord::zeros(),
+            Coord::zeros(),
+            1.0,
+            &scurve_3d::Lim {
+                vel: Coord::repeat(2.0),
+                acc: Coord::repeat(5.0),
+                jerk: Coord::repeat(20.0),
+            },
+        );
+
+        assert_eq!(check(&segment).len(), 6);
+        assert!((segment.end().pos - q1).norm() < 1.0e-6);
+    }
+
+    #[test]
+    fn paths() {
+        let lim = trapezoidal_non_zero_3d::Lim {
+            vel: Coord::<3, f64>::repeat(2.0),
+            acc: Coord::repeat(5.0),
+        };
+
+        let mut blend = ArcBlend::new(
+            Coord::zeros(),
+            Coord::<3, f64>::new(2.0, 0.0, 0.0),
+            Coord::<3, f64>::new(2.0, 2.0, 0.0),
+            0.1,
+            1.0,
+            lim,
+        );
+        blend.set_speeds(0.5, 0.5);
+
+        // Constant speed throughout
+        assert!(check(&blend).is_empty());
+
+        let mut arc = Arc::new(
+            Coord::zeros(),
+            Coord::<3, f64>::new(2.0, 0.0, 0.0),
+            ArcCenter::Point(Coord::<3, f64>::new(1.0, 0.0, 0.0)),
+            Plane::Xy,
+            Direction::Clockwise,
+            0,
+            &lim,
+        )
+        .unwrap();
+        arc.set_speeds(0.0, 0.0);
+
+        check(&arc);
+        assert!((arc.end().pos - arc.end).norm() < 1.0e-6);
+
+        let mut trajectory = Trajectory::<3, f64>::new();
+        trajectory.limits = lim;
+
+        trajectory.push_point(Coord::zeros()).unwrap();
+        trajectory
+            .push_point(Coord::<3, f64>::new(5.0, 0.0, 0.0))
+            .unwrap();
+        trajectory
+            .push_point(Coord::<3, f64>::new(5.0, 5.0, 0.0))
+            .unwrap();
+
+        let times = check(&trajectory);
+
+        // Every join between items is a phase change
+        for item in &trajectory.items[..trajectory.items.len() - 1] {
+            assert!(times.contains(&item.end_t()));
+        }
+
+        assert!((trajectory.end().pos - Coord::<3, f64>::new(5.0, 5.0, 0.0)).norm() < 1.0e-9);
+    }
+
+    #[test]
+    fn mixed() {
+        let lim = trapezoidal_non_zero_3d::Lim {
+            vel: Coord::<3, f64>::repeat(2.0),
+            acc: Coord::repeat(5.0),
+        };
+
+        let mut blend = ArcBlend::new(
+            Coord::zeros(),
+            Coord::<3, f64>::new(2.0, 0.0, 0.0),
+            Coord::<3, f64>::new(2.0, 2.0, 0.0),
+            0.1,
+            0.0,
+            lim,
+        );
+        blend.set_speeds(0.5, 0.5);
+
+        let segment = trapezoidal_non_zero_3d::Segment::new(
+            Coord::zeros(),
+            Coord::<3, f64>::new(1.0, 2.0, 3.0),
+            Coord::zeros(),
+            Coord::zeros(),
+            0.0,
+            &lim,
+        );
+
+        // Any kind of profile with the same output can be treated the same way
+        let profiles: [&dyn Profile<f64, Out = trapezoidal_non_zero_3d::Out<3, f64>>; 2] =
+            [&blend, &segment];
+
+        for profile in profiles {
+            check(profile);
+        }
+    }
+}
@@ -0,0 +1,14 @@
+//! Scalar type used by the planners.
+
+use nalgebra::RealField;
+
+/// Floating point type trajectories are planned with, usually `f32` on embedded targets and `f64`
+/// on the host.
+pub trait Real: RealField + Copy + Default {
+    /// Convert a constant to this type.
+    fn lit(value: f64) -> Self {
+        nalgebra::convert(value)
+    }
+}
+
+impl<T: RealField + Copy + Default> Real for T {}
@@ -1,225 +1,447 @@
+use crate::{
+    error::{self, PlanError},
+    profile::{self, Profile},
+    real::Real,
+};
+use alloc::vec::Vec;
+
 #[derive(Default, Debug, Clone, Copy)]
-pub struct Lim {
-    pub vel: f32,
-    pub acc: f32,
-    pub jerk: f32,
+pub struct Lim<T = f32> {
+    pub vel: T,
+    pub acc: T,
+    pub jerk: T,
 }
 
 #[derive(Debug, Default, Clone, Copy)]
-pub struct Out {
-    pub pos: f32,
-    pub vel: f32,
-    pub acc: f32,
-    pub jerk: f32,
+pub struct Out<T = f32> {
+    pub pos: T,
+    pub vel: T,
+    pub acc: T,
+    pub jerk: T,
 }
 
 #[derive(Debug, Default, Clone, Copy)]
-pub struct Times {
-    pub t_j1: f32,
-    pub t_j2: f32,
-    pub t_d: f32,
-    pub t_a: f32,
-    pub t_v: f32,
-    pub total_time: f32,
+pub struct Times<T = f32> {
+    pub t_j1: T,
+    pub t_j2: T,
+    pub t_d: T,
+    pub t_a: T,
+    pub t_v: T,
+    pub total_time: T,
 }
 
-fn is_feasible(q0: f32, q1: f32, v0: f32, v1: f32, lim: &Lim) -> bool {
-    let Lim {
-        acc: amax,
-        jerk: jmax,
-        ..
-    } = lim;
+/// Peak acceleration of a change in speed of `dv`, ramped up at `jmax` and back down at `-jmin`,
+/// that doesn't exceed `amax`.
+fn peak_acc<T: Real>(dv: T, amax: T, jmax: T, jmin: T) -> T {
+    amax.min((T::lit(2.0) * dv / (T::one() / jmax - T::one() / jmin)).sqrt())
+}
 
-    let t_j_star = (f32::abs(v1 - v0) / jmax).sqrt().min(amax / jmax);
+/// Duration and distance of a change in speed from `v` up to `vlim`, with acceleration ramped up
+/// to `acc` at `jmax`, held, then ramped back down to zero at `-jmin`. A change down in speed takes
+/// the same time and distance backwards, with the ramps the other way around.
+fn speed_change<T: Real>(v: T, vlim: T, acc: T, jmax: T, jmin: T) -> (T, T) {
+    if acc <= T::zero() {
+        return (T::zero(), T::zero());
+    }
 
-    let delta = q1 - q0;
+    let up = acc / jmax;
+    let down = -acc / jmin;
+    let duration = (vlim - v) / acc + (up + down) / T::lit(2.0);
 
-    let limit = amax / jmax;
+    (duration, ramp_distance(v, vlim, duration, acc, up, down))
+}
 
-    let comp = if t_j_star < limit {
-        t_j_star * (v0 + v1)
-    } else if t_j_star == limit {
-        0.5 * (v0 + v1) * (t_j_star + (v1 - v0).abs() / amax)
-    } else {
-        return false;
-    };
+/// Distance covered by a change in speed from `v` up to `vlim` over `duration`, ramping
+/// acceleration up to `acc` for `up` and back down for `down`. This is `(v + vlim) / 2 * duration`
+/// when the two ramps are the same length.
+fn ramp_distance<T: Real>(v: T, vlim: T, duration: T, acc: T, up: T, down: T) -> T {
+    let hold = duration - up - down;
 
-    delta > comp
+    (v + vlim) / T::lit(2.0) * duration
+        + acc * (down - up) * ((up + down) / T::lit(12.0) + hold / T::lit(4.0))
 }
 
-#[derive(Debug, Default)]
-pub struct Segment {
+/// Peak acceleration of a change in speed from `v` up to `vlim` that covers exactly `distance`,
+/// for when there's only room for one of acceleration or deceleration (3.28, 3.29). The distance
+/// shrinks as the peak grows, so it's found by bisection.
+fn peak_for_distance<T: Real>(v: T, vlim: T, distance: T, jmax: T, jmin: T) -> T {
+    let mut lo = T::zero();
+    let mut hi = peak_acc(vlim - v, T::lit(f64::INFINITY), jmax, jmin);
+
+    for _ in 0..BISECTIONS {
+        let mid = (lo + hi) / T::lit(2.0);
+
+        if speed_change(v, vlim, mid, jmax, jmin).1 > distance {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+
+    (lo + hi) / T::lit(2.0)
+}
+
+/// Whether `v1` can be reached from `v0` within the displacement `delta`, given the acceleration
+/// limit of whichever of acceleration or deceleration the change needs (3.17).
+fn is_feasible<T: Real>(delta: T, v0: T, v1: T, amax: T, jmax: T, jmin: T) -> bool {
+    let (low, high) = if v0 < v1 { (v0, v1) } else { (v1, v0) };
+    let acc = peak_acc(high - low, amax, jmax, jmin);
+
+    delta > speed_change(low, high, acc, jmax, jmin).1
+}
+
+/// Number of times the acceleration limit is reduced when it can't be reached before giving up.
+/// Each reduction is 1%, so this gets to well under a thousandth of the original limit.
+const MAX_REDUCTIONS: usize = 1000;
+
+/// Number of halvings when solving for a peak acceleration, enough to get down to rounding error.
+const BISECTIONS: usize = 100;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Segment<T = f32> {
     /// Start time of this segment.
-    start_t: f32,
+    start_t: T,
     /// Duration of this segment.
-    t: f32,
+    t: T,
+    /// Direction of travel, `1` or `-1`. Everything else is stored as if travelling forwards.
+    sign: T,
     /// Initial position.
-    q0: f32,
+    q0: T,
     /// Final position.
-    q1: f32,
+    q1: T,
     /// Initial velocity.
-    v0: f32,
+    v0: T,
     /// Final velocity.
-    v1: f32,
+    v1: T,
 
-    t_j1: f32,
+    /// Duration of the jerk phase that starts acceleration. The one that ends it lasts
+    /// `a_lim_a / -jmin`.
+    t_j1: T,
     /// Acceleration duration.
-    t_a: f32,
+    t_a: T,
     /// Maximum accel reached during acceleration phase.
-    a_lim_a: f32,
+    a_lim_a: T,
     /// Maximum accel reached during deceleration phase.
-    a_lim_d: f32,
-    t_j2: f32,
+    a_lim_d: T,
+    /// Duration of the jerk phase that starts deceleration. The one that ends it lasts
+    /// `a_lim_d / -jmax`.
+    t_j2: T,
     /// Deceleration duration.
-    t_d: f32,
+    t_d: T,
     /// Constant velocity duration.
-    t_v: f32,
+    t_v: T,
 
     /// Highest velocity reached in this segment.
-    vlim: f32,
+    vlim: T,
+    /// Jerk of the phases that increase acceleration.
+    jmax: T,
+    /// Jerk of the phases that decrease acceleration, which is negative.
+    jmin: T,
 
-    /// Limits provided by the user.
-    lim: Lim,
+    /// Tightest symmetrical limits that satisfy the limits provided by the user, used when
+    /// stopping.
+    lim: Lim<T>,
     /// Whether this segment is feasible/valid or not.
     feasible: bool,
 }
 
-impl Segment {
-    fn new(start_t: f32, q0: f32, q1: f32, v0: f32, v1: f32, lim: &Lim) -> Self {
-        let delta = q1 - q0;
+impl<T: Real> Segment<T> {
+    /// Like [`new`](Self::new), but returns an error for inputs that can't be planned instead of
+    /// an empty segment.
+    pub fn try_new(
+        start_t: T,
+        q0: T,
+        q1: T,
+        v0: T,
+        v1: T,
+        lim: &Lim<T>,
+    ) -> Result<Self, PlanError<T>> {
+        error::check_finite([start_t, q0, q1, v0, v1])?;
+        error::check_limits([lim.vel, lim.acc, lim.jerk])?;
+
+        if q0 == q1 {
+            return Err(PlanError::ZeroDisplacement);
+        }
+
+        if v0.abs() > lim.vel || v1.abs() > lim.vel {
+            return Err(PlanError::InfeasibleVelocities);
+        }
+
+        let segment = Self::new(start_t, q0, q1, v0, v1, lim);
+
+        if !segment.is_feasible() {
+            return Err(PlanError::InfeasibleVelocities);
+        }
+
+        Ok(segment)
+    }
+
+    pub fn new(start_t: T, q0: T, q1: T, v0: T, v1: T, lim: &Lim<T>) -> Self {
+        let min = Lim {
+            vel: -lim.vel,
+            acc: -lim.acc,
+            jerk: -lim.jerk,
+        };
+
+        Self::new_asymmetric(start_t, q0, q1, v0, v1, lim, &min)
+    }
+
+    /// Plan a segment with different limits in each direction, e.g. for an axis that can
+    /// decelerate harder than it can accelerate.
+    ///
+    /// `max` holds the upper bound of each value and `min` the lower bound, so all of `min` must be
+    /// negative. Acceleration is limited by `max.acc` while speeding up in the positive direction
+    /// and by `min.acc` while slowing down, and the other way around when travelling in the
+    /// negative direction. Likewise jerk is `max.jerk` while acceleration increases and `min.jerk`
+    /// while it decreases.
+    pub fn new_asymmetric(
+        start_t: T,
+        q0: T,
+        q1: T,
+        v0: T,
+        v1: T,
+        max: &Lim<T>,
+        min: &Lim<T>,
+    ) -> Self {
+        let two = T::lit(2.0);
+
+        let lim = Lim {
+            vel: max.vel.min(-min.vel),
+            acc: max.acc.min(-min.acc),
+            jerk: max.jerk.min(-min.jerk),
+        };
 
         // 3.31
         // ---
-        let sign = delta.signum();
+        let sign = if q1 < q0 { -T::one() } else { T::one() };
 
         let q0 = sign * q0;
         let q1 = sign * q1;
         let v0 = sign * v0;
         let v1 = sign * v1;
 
-        let lim = Lim {
-            vel: sign * lim.vel,
-            acc: sign * lim.acc,
-            jerk: sign * lim.jerk,
+        let (max, min) = if sign > T::zero() {
+            (*max, *min)
+        } else {
+            (
+                Lim {
+                    vel: -min.vel,
+                    acc: -min.acc,
+                    jerk: -min.jerk,
+                },
+                Lim {
+                    vel: -max.vel,
+                    acc: -max.acc,
+                    jerk: -max.jerk,
+                },
+            )
+        };
+
+        let vmax = max.vel;
+        // Acceleration limits while speeding up and slowing down
+        let amax_a = max.acc;
+        let amax_d = -min.acc;
+        // Jerk limits while acceleration is increasing and decreasing
+        let jmax = max.jerk;
+        let jmin = min.jerk;
+
+        let delta = q1 - q0;
+
+        let feasible = if v1 >= v0 {
+            is_feasible(delta, v0, v1, amax_a, jmax, jmin)
+        } else {
+            is_feasible(delta, v0, v1, amax_d, jmax, jmin)
         };
 
-        if !is_feasible(q0, q1, v0, v1, &lim) {
+        if !feasible {
             return Self::default();
         }
 
-        let Lim {
-            vel: vmax,
-            acc: amax,
-            jerk: jmax,
-        } = lim;
+        // Case 1: maximum velocity is reached
+        // ---
 
-        // Symmetrical profiles for now
-        let _vmin = -vmax;
-        let _amin = -amax;
-        let _jmin = -jmax;
+        // Peak acceleration and deceleration, with each ramp at its own jerk limit
+        let mut a_a = peak_acc(vmax - v0, amax_a, jmax, jmin);
+        let mut a_d = peak_acc(vmax - v1, amax_d, jmax, jmin);
 
-        let max_accel_not_reached = (vmax - v0) * jmax < amax.powi(2);
-        let max_decel_not_reached = (vmax - v1) * jmax < amax.powi(2);
+        let (mut t_a, s_a) = speed_change(v0, vmax, a_a, jmax, jmin);
+        let (mut t_d, s_d) = speed_change(v1, vmax, a_d, jmax, jmin);
 
-        // Acceleration time Ta
-        let (mut t_j1, mut t_a) = if max_accel_not_reached {
-            // The time that jerk is constant during accel
-            let t_j1 = f32::sqrt((vmax - v0) / jmax);
-            // Acceleration period
-            let t_a = 2.0 * t_j1;
+        // 3.25 duration of constant velocity
+        let mut t_v = (delta - s_a - s_d) / vmax;
 
-            (t_j1, t_a)
-        } else {
-            // The time that jerk is constant during accel
-            let t_j1 = amax / jmax;
-            // Acceleration period
-            let t_a = t_j1 + ((vmax - v0) / amax);
+        // Greatest velocity reached
+        let mut vlim = vmax;
 
-            (t_j1, t_a)
-        };
+        // Case 2: no constant velocity section
+        // ---
+        if t_v <= T::zero() {
+            t_v = T::zero();
 
-        // Deceleration time Td
-        let (mut t_j2, mut t_d) = if max_decel_not_reached {
-            // The time that jerk is constant during accel
-            let t_j2 = f32::sqrt((vmax - v1) / jmax);
-            // Deceleration period
-            let t_d = 2.0 * t_j2;
+            a_a = amax_a;
+            a_d = amax_d;
 
-            (t_j2, t_d)
-        } else {
-            // The time that jerk is constant during accel
-            let t_j2 = amax / jmax;
-            // Deceleration period
-            let t_d = t_j2 + ((vmax - v1) / amax);
+            // Half the time spent ramping acceleration up and back down, per unit of acceleration
+            let k = (T::one() / jmax - T::one() / jmin) / two;
 
-            (t_j2, t_d)
-        };
+            // Reduce the acceleration limits until both of them can be reached, or until one of
+            // the phases disappears altogether
+            for _ in 0..MAX_REDUCTIONS {
+                // Difference in length between the ramps down and up
+                let c_a = a_a * (-T::one() / jmin - T::one() / jmax);
+                let c_d = a_d * (-T::one() / jmin - T::one() / jmax);
 
-        // 3.25 duration of constant velocity
-        let mut t_v =
-            (delta / vmax) - (t_a / 2.0) * (1.0 + v0 / vmax) - (t_d / 2.0) * (1.0 + v1 / vmax);
+                // 3.26a/b, generalised to different acceleration and deceleration limits and
+                // ramps by solving `delta` = the distance of both changes in speed for `vlim`.
+                let a = (T::one() / a_a + T::one() / a_d) / two;
+                let b = (a_a + a_d) * k / two + (c_a + c_d) / T::lit(4.0);
+                let c = (v0 * a_a + v1 * a_d) * k / two
+                    - v0.powi(2) / (two * a_a)
+                    - v1.powi(2) / (two * a_d)
+                    - (c_a * v0 + c_d * v1) / T::lit(4.0)
+                    - (c_a * a_a.powi(2) + c_d * a_d.powi(2)) * k / T::lit(12.0)
+                    - delta;
 
-        // Greatest velocity reached
-        let vlim;
+                vlim = (-b + (b.powi(2) - T::lit(4.0) * a * c).sqrt()) / (two * a);
 
-        // No constant velocity section
-        if t_v < 0.0 {
-            t_j1 = amax / jmax;
-            t_j2 = amax / jmax;
+                t_a = a_a * k + (vlim - v0) / a_a;
+                t_d = a_d * k + (vlim - v1) / a_d;
 
-            let delta = amax.powi(4) / jmax.powi(2)
-                + 2.0 * (v0.powi(2) + v1.powi(2))
-                + amax * (4.0 * (q1 - q0) - 2.0 * amax / jmax * (v0 + v1));
+                if t_a < T::zero()
+                    || t_d < T::zero()
+                    || (t_a >= two * a_a * k && t_d >= two * a_d * k)
+                {
+                    break;
+                }
 
-            t_a = (amax.powi(2) / jmax - 2.0 * v0 + delta.sqrt()) / 2.0 * amax;
-            t_d = (amax.powi(2) / jmax - 2.0 * v1 + delta.sqrt()) / 2.0 * amax;
+                let gamma = T::lit(0.99);
 
-            t_v = 0.0;
+                a_a *= gamma;
+                a_d *= gamma;
+            }
 
-            vlim = v0 + (t_a - t_j1) * jmax * t_j1;
-        } else {
-            vlim = vmax;
+            // Only a deceleration phase (3.28)
+            if t_a < T::zero() {
+                vlim = v0;
+
+                a_a = T::zero();
+                t_a = T::zero();
+                a_d = peak_for_distance(v1, v0, delta, jmax, jmin);
+                t_d = speed_change(v1, v0, a_d, jmax, jmin).0;
+            }
+            // Only an acceleration phase (3.29)
+            else if t_d < T::zero() {
+                vlim = v1;
+
+                a_d = T::zero();
+                t_d = T::zero();
+                a_a = peak_for_distance(v0, v1, delta, jmax, jmin);
+                t_a = speed_change(v0, v1, a_a, jmax, jmin).0;
+            }
         }
 
         let total_time = t_a + t_v + t_d;
 
-        // Acceleration reached
-        let a_lim_a = jmax * t_j1;
-        let a_lim_d = -jmax * t_j2;
-
         Self {
             q0,
             q1,
             v0,
             v1,
-            t_j1,
+            sign,
+            t_j1: a_a / jmax,
             t_a,
-            a_lim_a,
-            a_lim_d,
-            t_j2,
+            a_lim_a: a_a,
+            a_lim_d: -a_d,
+            t_j2: a_d / -jmin,
             t_d,
             t_v,
             feasible: true,
             lim,
             vlim,
+            jmax,
+            jmin,
             start_t,
             t: total_time,
         }
     }
 
-    fn tp(&self, t: f32) -> Option<Out> {
-        let t = t - self.start_t;
+    /// Durations of the jerk phases that end acceleration and deceleration.
+    fn end_ramps(&self) -> (T, T) {
+        (self.a_lim_a / -self.jmin, self.a_lim_d / -self.jmax)
+    }
+
+    /// Distances covered while accelerating and decelerating.
+    fn ramp_distances(&self) -> (T, T) {
+        let (t_j1_end, t_j2_end) = self.end_ramps();
 
-        if t < 0.0 {
+        (
+            ramp_distance(
+                self.v0,
+                self.vlim,
+                self.t_a,
+                self.a_lim_a,
+                self.t_j1,
+                t_j1_end,
+            ),
+            ramp_distance(
+                self.v1,
+                self.vlim,
+                self.t_d,
+                -self.a_lim_d,
+                t_j2_end,
+                self.t_j2,
+            ),
+        )
+    }
+
+    /// Duration of this segment.
+    pub fn total_time(&self) -> T {
+        self.t
+    }
+
+    /// Whether the given boundary velocities can be reached within the segment's displacement.
+    /// Infeasible segments have zero duration.
+    pub fn is_feasible(&self) -> bool {
+        self.feasible
+    }
+
+    /// Fastest stop from the state this segment is in at time `t`, e.g. when a limit switch trips.
+    /// `None` if `t` is outside this segment.
+    pub fn stop(&self, t: T) -> Option<Stop<T>> {
+        let out = self.tp(t)?;
+
+        Some(Stop::new(t, out.pos, out.vel, out.acc, &self.lim))
+    }
+
+    /// Get trajectory parameters at the given time `t`.
+    pub fn tp(&self, t: T) -> Option<Out<T>> {
+        let out = self.tp_forwards(t - self.start_t)?;
+
+        // 3.32
+        Some(Out {
+            pos: self.sign * out.pos,
+            vel: self.sign * out.vel,
+            acc: self.sign * out.acc,
+            jerk: self.sign * out.jerk,
+        })
+    }
+
+    /// Trajectory parameters at time `t` from the start of the segment, in the direction of
+    /// travel.
+    fn tp_forwards(&self, t: T) -> Option<Out<T>> {
+        if t < T::zero() {
             return None;
         }
 
+        let two = T::lit(2.0);
+        let three = T::lit(3.0);
+        let six = T::lit(6.0);
+
         let Self {
             q0,
             q1,
             v0,
             v1,
-            lim,
             t_j1,
             t_a,
             a_lim_a,
@@ -229,24 +451,18 @@ impl Segment {
             t_v,
             t: total_time,
             vlim,
+            jmax,
+            jmin,
             ..
         } = *self;
 
-        let Lim {
-            vel: vmax,
-            acc: amax,
-            jerk: jmax,
-        } = lim;
-
-        // Symmetrical profiles for now
-        let _vmin = -vmax;
-        let _amin = -amax;
-        let jmin = -jmax;
+        let (t_j1_end, t_j2_end) = self.end_ramps();
+        let (s_a, s_d) = self.ramp_distances();
 
         // Accel phase, max jerk
         if t < t_j1 {
-            let pos = q0 + (v0 * t) + (jmax * t.powi(3) / 6.0);
-            let vel = v0 + jmax * t.powi(2) / 2.0;
+            let pos = q0 + (v0 * t) + (jmax * t.powi(3) / six);
+            let vel = v0 + jmax * t.powi(2) / two;
             let acc = jmax * t;
             let jerk = jmax;
 
@@ -258,12 +474,13 @@ impl Segment {
             })
         }
         // Accel phase, zero jerk
-        else if t < (t_a - t_j1) {
-            let pos =
-                q0 + (v0 * t) + (a_lim_a / 6.0) * (3.0 * t.powi(2) - 3.0 * t_j1 * t + t_j1.powi(2));
-            let vel = v0 + a_lim_a * (t - t_j1 / 2.0);
+        else if t < (t_a - t_j1_end) {
+            let pos = q0
+                + (v0 * t)
+                + (a_lim_a / six) * (three * t.powi(2) - three * t_j1 * t + t_j1.powi(2));
+            let vel = v0 + a_lim_a * (t - t_j1 / two);
             let acc = a_lim_a;
-            let jerk = 0.0;
+            let jerk = T::zero();
 
             Some(Out {
                 pos,
@@ -274,9 +491,8 @@ impl Segment {
         }
         // Accel phase, min jerk
         else if t < t_a {
-            let pos =
-                q0 + (vlim + v0) * t_a / 2.0 - vlim * (t_a - t) - jmin * (t_a - t).powi(3) / 6.0;
-            let vel = vlim + jmin * (t_a - t).powi(2) / 2.0;
+            let pos = q0 + s_a - vlim * (t_a - t) - jmin * (t_a - t).powi(3) / six;
+            let vel = vlim + jmin * (t_a - t).powi(2) / two;
             let acc = -jmin * (t_a - t);
             let jerk = jmin;
 
@@ -289,10 +505,10 @@ impl Segment {
         }
         // Coast
         else if t < t_a + t_v {
-            let pos = q0 + (vlim + v0) * t_a / 2.0 + vlim * (t - t_a);
+            let pos = q0 + s_a + vlim * (t - t_a);
             let vel = vlim;
-            let acc = 0.0;
-            let jerk = 0.0;
+            let acc = T::zero();
+            let jerk = T::zero();
 
             Some(Out {
                 pos,
@@ -301,13 +517,14 @@ impl Segment {
                 jerk,
             })
         }
-        // Decel, max jerk
+        // Decel, min jerk
         else if t < total_time - t_d + t_j2 {
-            let pos = q1 - (vlim + v1) * t_d / 2.0 + vlim * (t - total_time + t_d)
-                - jmax * (t - total_time + t_d).powi(3) / 6.0;
-            let vel = vlim - jmax * (t - total_time + t_d).powi(2) / 2.0;
-            let acc = -jmax * (t - total_time + t_d);
-            let jerk = jmax;
+            let pos = q1 - s_d
+                + vlim * (t - total_time + t_d)
+                + jmin * (t - total_time + t_d).powi(3) / six;
+            let vel = vlim + jmin * (t - total_time + t_d).powi(2) / two;
+            let acc = jmin * (t - total_time + t_d);
+            let jerk = jmin;
 
             Some(Out {
                 pos,
@@ -317,15 +534,16 @@ impl Segment {
             })
         }
         // Decel, zero jerk
-        else if t < total_time - t_j2 {
-            let pos = q1 - (vlim + v1) * t_d / 2.0
+        else if t < total_time - t_j2_end {
+            let pos = q1 - s_d
                 + vlim * (t - total_time + t_d)
-                + a_lim_d / 6.0
-                    * (3.0 * (t - total_time + t_d).powi(2) - 3.0 * t_j2 * (t - total_time + t_d)
+                + a_lim_d / six
+                    * (three * (t - total_time + t_d).powi(2)
+                        - three * t_j2 * (t - total_time + t_d)
                         + t_j2.powi(2));
-            let vel = vlim + a_lim_d * (t - total_time + t_d - t_j2 / 2.0);
+            let vel = vlim + a_lim_d * (t - total_time + t_d - t_j2 / two);
             let acc = a_lim_d;
-            let jerk = 0.0;
+            let jerk = T::zero();
 
             Some(Out {
                 pos,
@@ -334,12 +552,12 @@ impl Segment {
                 jerk,
             })
         }
-        // Decel, min jerk
+        // Decel, max jerk
         else if t <= total_time {
-            let pos = q1 - v1 * (total_time - t) - jmax * (total_time - t).powi(3) / 6.0;
-            let vel = v1 + jmax * (total_time - t).powi(2) / 2.0;
+            let pos = q1 - v1 * (total_time - t) - jmax * (total_time - t).powi(3) / six;
+            let vel = v1 + jmax * (total_time - t).powi(2) / two;
             let acc = -jmax * (total_time - t);
-            let jerk = jmin;
+            let jerk = jmax;
 
             Some(Out {
                 pos,
@@ -355,8 +573,225 @@ impl Segment {
     }
 }
 
-pub fn tp(t: f32, q0: f32, q1: f32, v0: f32, v1: f32, lim: &Lim, times: &mut Times) -> (f32, Out) {
-    let segment = Segment::new(0.0, q0, q1, v0, v1, &lim);
+/// Fastest jerk limited stop from an arbitrary position, velocity and acceleration.
+///
+/// Acceleration is ramped at the jerk limit to a peak deceleration, held there if it reaches the
+/// acceleration limit, then ramped back to zero so the stop finishes at rest with no acceleration.
+/// If the initial state is already decelerating hard enough that releasing the deceleration would
+/// overshoot zero velocity, the motion reverses by a small amount before coming to rest, which is
+/// still the quickest way to get to a standstill.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Stop<T = f32> {
+    /// Start time of the stop.
+    pub start_t: T,
+    /// Duration of the stop.
+    pub total_time: T,
+    /// Signed distance travelled before coming to rest.
+    pub distance: T,
+    /// Initial position.
+    q0: T,
+    /// Duration and jerk of each phase, along with the state it starts in relative to `q0`.
+    phases: [(T, T, Out<T>); 3],
+}
+
+impl<T: Real> Stop<T> {
+    /// Like [`new`](Self::new), but returns an error for non-finite inputs or invalid limits.
+    pub fn try_new(start_t: T, q0: T, v0: T, a0: T, lim: &Lim<T>) -> Result<Self, PlanError<T>> {
+        error::check_finite([start_t, q0, v0, a0])?;
+        error::check_limits([lim.acc, lim.jerk])?;
+
+        Ok(Self::new(start_t, q0, v0, a0, lim))
+    }
+
+    pub fn new(start_t: T, q0: T, v0: T, a0: T, lim: &Lim<T>) -> Self {
+        let two = T::lit(2.0);
+
+        let Lim {
+            acc: amax,
+            jerk: jmax,
+            ..
+        } = *lim;
+
+        // Work in the direction of travel so the stop always decelerates
+        let sign = if v0 < T::zero() { -T::one() } else { T::one() };
+
+        let v = v0 * sign;
+        let a = a0 * sign;
+
+        // Change in velocity from ramping acceleration from `a` to `peak` then back to zero
+        let ramps = |peak: T| ((a + peak) * (peak - a).abs() + peak * peak.abs()) / (two * jmax);
+
+        let peak = if a >= T::zero() || v >= a.powi(2) / (two * jmax) {
+            -(jmax * v + a.powi(2) / two).sqrt()
+        }
+        // Already decelerating too hard to stop without reversing
+        else {
+            (a.powi(2) / two - jmax * v).sqrt()
+        };
+
+        let clamped = peak.max(-amax).min(amax);
+
+        // Hold the acceleration limit for long enough to lose the rest of the velocity
+        let t_hold = if clamped != peak {
+            (-(v + ramps(clamped)) / clamped).max(T::zero())
+        } else {
+            T::zero()
+        };
+
+        let peak = clamped;
+
+        let mut phases = [
+            (
+                (peak - a).abs() / jmax,
+                (peak - a).signum() * jmax,
+                Out::default(),
+            ),
+            (t_hold, T::zero(), Out::default()),
+            (peak.abs() / jmax, -peak.signum() * jmax, Out::default()),
+        ];
+
+        let mut state = Out {
+            pos: T::zero(),
+            vel: v,
+            acc: a,
+            jerk: T::zero(),
+        };
+
+        for (duration, jerk, start) in phases.iter_mut() {
+            state.jerk = *jerk;
+            *start = state;
+            state = Self::integrate(&state, *duration);
+        }
+
+        // Flip back to the original direction
+        for (_, jerk, start) in phases.iter_mut() {
+            *jerk *= sign;
+            start.pos *= sign;
+            start.vel *= sign;
+            start.acc *= sign;
+            start.jerk *= sign;
+        }
+
+        Self {
+            start_t,
+            total_time: phases
+                .iter()
+                .fold(T::zero(), |total, phase| total + phase.0),
+            distance: state.pos * sign,
+            q0,
+            phases,
+        }
+    }
+
+    /// Position the stop comes to rest at.
+    pub fn q1(&self) -> T {
+        self.q0 + self.distance
+    }
+
+    /// State after moving from `state` at constant jerk for time `t`.
+    fn integrate(state: &Out<T>, t: T) -> Out<T> {
+        let Out {
+            pos,
+            vel,
+            acc,
+            jerk,
+        } = *state;
+
+        Out {
+            pos: pos + vel * t + acc * t.powi(2) / T::lit(2.0) + jerk * t.powi(3) / T::lit(6.0),
+            vel: vel + acc * t + jerk * t.powi(2) / T::lit(2.0),
+            acc: acc + jerk * t,
+            jerk,
+        }
+    }
+
+    /// Get trajectory parameters at the given time `t`.
+    pub fn tp(&self, t: T) -> Option<Out<T>> {
+        if t < self.start_t || t > self.start_t + self.total_time {
+            return None;
+        }
+
+        let mut t = t - self.start_t;
+
+        for (index, (duration, _, start)) in self.phases.iter().enumerate() {
+            // The last phase covers the end of the stop
+            if t <= *duration || index == self.phases.len() - 1 {
+                let mut out = Self::integrate(start, t.min(*duration));
+                out.pos += self.q0;
+
+                return Some(out);
+            }
+
+            t -= *duration;
+        }
+
+        None
+    }
+}
+
+impl<T: Real> Profile<T> for Segment<T> {
+    type Out = Out<T>;
+
+    fn start_t(&self) -> T {
+        self.start_t
+    }
+
+    fn duration(&self) -> T {
+        self.t
+    }
+
+    fn tp(&self, t: T) -> Option<Out<T>> {
+        Segment::tp(self, t)
+    }
+
+    fn phase_times(&self) -> Vec<T> {
+        let (t_j1_end, t_j2_end) = self.end_ramps();
+
+        profile::phase_times(
+            self.start_t,
+            &[
+                self.t_j1,
+                self.t_a - self.t_j1 - t_j1_end,
+                t_j1_end,
+                self.t_v,
+                self.t_j2,
+                self.t_d - self.t_j2 - t_j2_end,
+                t_j2_end,
+            ],
+        )
+    }
+}
+
+impl<T: Real> Profile<T> for Stop<T> {
+    type Out = Out<T>;
+
+    fn start_t(&self) -> T {
+        self.start_t
+    }
+
+    fn duration(&self) -> T {
+        self.total_time
+    }
+
+    fn tp(&self, t: T) -> Option<Out<T>> {
+        Stop::tp(self, t)
+    }
+
+    fn phase_times(&self) -> Vec<T> {
+        profile::phase_times(self.start_t, &self.phases.map(|(duration, _, _)| duration))
+    }
+}
+
+pub fn tp<T: Real>(
+    t: T,
+    q0: T,
+    q1: T,
+    v0: T,
+    v1: T,
+    lim: &Lim<T>,
+    times: &mut Times<T>,
+) -> (T, Out<T>) {
+    let segment = Segment::new(T::zero(), q0, q1, v0, v1, lim);
 
     let total_time = segment.t;
 
@@ -375,6 +810,7 @@ pub fn tp(t: f32, q0: f32, q1: f32, v0: f32, v1: f32, lim: &Lim, times: &mut Tim
 #[cfg(test)]
 mod tests {
     use super::*;
+    use float_cmp::assert_approx_eq;
 
     #[test]
     fn it() {
@@ -406,4 +842,434 @@ mod tests {
             t += 0.1;
         }
     }
+
+    /// Check the segment joins its boundary conditions smoothly without exceeding `max` or `min`.
+    fn check_segment(
+        segment: &Segment<f64>,
+        q0: f64,
+        q1: f64,
+        v0: f64,
+        v1: f64,
+        max: &Lim<f64>,
+        min: &Lim<f64>,
+    ) {
+        assert!(segment.is_feasible());
+
+        let start = segment.tp(segment.start_t).unwrap();
+
+        assert!((start.pos - q0).abs() < 1.0e-9, "{start:?}");
+        assert!((start.vel - v0).abs() < 1.0e-9, "{start:?}");
+        assert!(start.acc.abs() < 1.0e-9, "{start:?}");
+
+        let end = segment.tp(segment.start_t + segment.total_time()).unwrap();
+
+        assert!((end.pos - q1).abs() < 1.0e-6, "{end:?}");
+        assert!((end.vel - v1).abs() < 1.0e-6, "{end:?}");
+        assert!(end.acc.abs() < 1.0e-6, "{end:?}");
+
+        let dt = 0.0001;
+        let mut t = segment.start_t;
+
+        while t + dt <= segment.start_t + segment.total_time() {
+            let a = segment.tp(t).unwrap();
+            let b = segment.tp(t + dt).unwrap();
+
+            assert!(
+                a.vel <= max.vel + 1.0e-9 && a.vel >= min.vel - 1.0e-9,
+                "{t} {a:?}"
+            );
+            assert!(
+                a.acc <= max.acc + 1.0e-9 && a.acc >= min.acc - 1.0e-9,
+                "{t} {a:?}"
+            );
+            assert!(
+                ((b.pos - a.pos) / dt - (a.vel + b.vel) / 2.0).abs() < 1.0e-3,
+                "{t}"
+            );
+            assert!(
+                ((b.vel - a.vel) / dt - (a.acc + b.acc) / 2.0).abs() < 1.0e-2,
+                "{t}"
+            );
+            assert!(
+                b.acc - a.acc <= max.jerk * dt + 1.0e-9 && b.acc - a.acc >= min.jerk * dt - 1.0e-9,
+                "{t}"
+            );
+
+            t += dt;
+        }
+    }
+
+    fn symmetrical(lim: &Lim<f64>) -> Lim<f64> {
+        Lim {
+            vel: -lim.vel,
+            acc: -lim.acc,
+            jerk: -lim.jerk,
+        }
+    }
+
+    /// Check the phase durations against the values given in the book.
+    fn check_times(segment: &Segment<f64>, t_a: f64, t_v: f64, t_d: f64, t_j1: f64, t_j2: f64) {
+        assert_approx_eq!(f64, segment.t_a, t_a, epsilon = 1.0e-4);
+        assert_approx_eq!(f64, segment.t_v, t_v, epsilon = 1.0e-4);
+        assert_approx_eq!(f64, segment.t_d, t_d, epsilon = 1.0e-4);
+        assert_approx_eq!(f64, segment.t_j1, t_j1, epsilon = 1.0e-4);
+        assert_approx_eq!(f64, segment.t_j2, t_j2, epsilon = 1.0e-4);
+    }
+
+    #[test]
+    fn book_example_3_9() {
+        let lim = Lim {
+            vel: 5.0,
+            acc: 10.0,
+            jerk: 30.0,
+        };
+
+        let segment = Segment::new(0.0, 0.0, 10.0, 1.0, 0.0, &lim);
+
+        check_segment(&segment, 0.0, 10.0, 1.0, 0.0, &lim, &symmetrical(&lim));
+        check_times(&segment, 0.7333, 1.1433, 0.8333, 0.3333, 0.3333);
+
+        assert_approx_eq!(f64, segment.vlim, 5.0);
+        assert_approx_eq!(f64, segment.total_time(), 2.71, epsilon = 1.0e-4);
+    }
+
+    /// Maximum velocity not reached.
+    #[test]
+    fn book_example_3_10() {
+        let lim = Lim {
+            vel: 10.0,
+            acc: 10.0,
+            jerk: 30.0,
+        };
+
+        let segment = Segment::new(0.0, 0.0, 10.0, 1.0, 0.0, &lim);
+
+        check_segment(&segment, 0.0, 10.0, 1.0, 0.0, &lim, &symmetrical(&lim));
+        check_times(&segment, 1.0747, 0.0, 1.1747, 0.3333, 0.3333);
+
+        assert_approx_eq!(f64, segment.vlim, 8.4136, epsilon = 1.0e-4);
+        assert_approx_eq!(f64, segment.total_time(), 2.2494, epsilon = 1.0e-4);
+    }
+
+    /// Maximum acceleration not reached either, so it's reduced until the profile can be
+    /// realised.
+    #[test]
+    fn book_example_3_11() {
+        let lim = Lim {
+            vel: 10.0,
+            acc: 10.0,
+            jerk: 30.0,
+        };
+
+        let segment = Segment::new(0.0, 0.0, 10.0, 7.0, 0.0, &lim);
+
+        check_segment(&segment, 0.0, 10.0, 7.0, 0.0, &lim, &symmetrical(&lim));
+        check_times(&segment, 0.4666, 0.0, 1.4718, 0.2321, 0.2321);
+
+        assert_approx_eq!(f64, segment.a_lim_a, 6.9641, epsilon = 1.0e-4);
+        assert_approx_eq!(f64, segment.a_lim_d, -6.9641, epsilon = 1.0e-4);
+        assert_approx_eq!(f64, segment.vlim, 8.6329, epsilon = 1.0e-4);
+        assert_approx_eq!(f64, segment.total_time(), 1.9384, epsilon = 1.0e-4);
+    }
+
+    /// Acceleration phase disappears altogether.
+    #[test]
+    fn book_example_3_12() {
+        let lim = Lim {
+            vel: 10.0,
+            acc: 10.0,
+            jerk: 30.0,
+        };
+
+        let segment = Segment::new(0.0, 0.0, 10.0, 7.5, 0.0, &lim);
+
+        check_segment(&segment, 0.0, 10.0, 7.5, 0.0, &lim, &symmetrical(&lim));
+        check_times(&segment, 0.0, 0.0, 2.6667, 0.0, 0.0973);
+
+        assert_approx_eq!(f64, segment.a_lim_a, 0.0);
+        assert_approx_eq!(f64, segment.a_lim_d, -2.9190, epsilon = 1.0e-4);
+        assert_approx_eq!(f64, segment.vlim, 7.5);
+        assert_approx_eq!(f64, segment.total_time(), 2.6667, epsilon = 1.0e-4);
+    }
+
+    #[test]
+    fn no_deceleration() {
+        let lim = Lim {
+            vel: 10.0,
+            acc: 10.0,
+            jerk: 30.0,
+        };
+
+        // Book example 3.12 backwards in time
+        let segment = Segment::new(0.0, 0.0, 10.0, 0.0, 7.5, &lim);
+
+        check_segment(&segment, 0.0, 10.0, 0.0, 7.5, &lim, &symmetrical(&lim));
+        check_times(&segment, 2.6667, 0.0, 0.0, 0.0973, 0.0);
+    }
+
+    #[test]
+    fn negative_direction() {
+        let lim = Lim {
+            vel: 10.0,
+            acc: 10.0,
+            jerk: 30.0,
+        };
+
+        // Book example 3.10 mirrored
+        let segment = Segment::new(1.0, 5.0, -5.0, -1.0, 0.0, &lim);
+
+        check_segment(&segment, 5.0, -5.0, -1.0, 0.0, &lim, &symmetrical(&lim));
+        check_times(&segment, 1.0747, 0.0, 1.1747, 0.3333, 0.3333);
+
+        assert!(segment.tp(2.0).unwrap().vel < 0.0);
+    }
+
+    #[test]
+    fn asymmetric_limits() {
+        let max = Lim {
+            vel: 5.0,
+            acc: 5.0,
+            jerk: 100.0,
+        };
+        let min = Lim {
+            vel: -8.0,
+            acc: -20.0,
+            jerk: -150.0,
+        };
+
+        // Accelerates gently but stops hard
+        let segment = Segment::new_asymmetric(0.0, 0.0, 20.0, 0.0, 0.0, &max, &min);
+
+        check_segment(&segment, 0.0, 20.0, 0.0, 0.0, &max, &min);
+
+        assert_approx_eq!(f64, segment.vlim, 5.0);
+        assert_approx_eq!(f64, segment.a_lim_a, 5.0);
+        assert_approx_eq!(f64, segment.a_lim_d, -20.0);
+        assert!(segment.t_d < segment.t_a);
+
+        // The other way, accelerating hard and stopping gently with a higher speed limit
+        let segment = Segment::new_asymmetric(0.0, 20.0, 0.0, 0.0, 0.0, &max, &min);
+
+        check_segment(&segment, 20.0, 0.0, 0.0, 0.0, &max, &min);
+
+        assert_approx_eq!(f64, segment.vlim, 8.0);
+        assert!(segment.t_d > segment.t_a);
+
+        // Not enough room to reach either acceleration limit
+        let segment = Segment::new_asymmetric(0.0, 0.0, 1.0, 2.0, 0.0, &max, &min);
+
+        check_segment(&segment, 0.0, 1.0, 2.0, 0.0, &max, &min);
+    }
+
+    #[test]
+    fn asymmetric_jerk() {
+        let max = Lim {
+            vel: 10.0,
+            acc: 10.0,
+            jerk: 40.0,
+        };
+        let min = Lim {
+            vel: -10.0,
+            acc: -10.0,
+            jerk: -200.0,
+        };
+
+        // Each ramp runs at its own jerk limit, instead of both at the tighter of the two
+        let jerks = |segment: &Segment<f64>| {
+            (0..=1000)
+                .map(|i| {
+                    segment
+                        .tp(segment.total_time() * i as f64 / 1000.0)
+                        .unwrap()
+                        .jerk
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for (q0, q1, v0, v1) in [
+            (0.0, 20.0, 0.0, 0.0),
+            (0.0, 5.0, 0.0, 0.0),
+            (0.0, 1.0, 3.0, 0.0),
+            (0.0, 1.0, 0.0, 3.0),
+            (20.0, 0.0, 0.0, 0.0),
+        ] {
+            let segment = Segment::new_asymmetric(0.0, q0, q1, v0, v1, &max, &min);
+
+            check_segment(&segment, q0, q1, v0, v1, &max, &min);
+
+            let jerks = jerks(&segment);
+
+            assert!(jerks.contains(&40.0), "{q0} {q1} {v0} {v1}");
+            assert!(jerks.contains(&-200.0), "{q0} {q1} {v0} {v1}");
+
+            // Quicker than the tighter jerk everywhere, except with only one phase where the
+            // distance sets its length
+            if v0 == 0.0 && v1 == 0.0 {
+                let slower = Segment::new(0.0, q0, q1, v0, v1, &max);
+
+                assert!(segment.total_time() < slower.total_time());
+            }
+        }
+    }
+
+    fn check_stop(stop: &Stop<f64>, q0: f64, v0: f64, a0: f64, lim: &Lim<f64>) {
+        let start = stop.tp(stop.start_t).unwrap();
+
+        assert!((start.pos - q0).abs() < 1.0e-9);
+        assert!((start.vel - v0).abs() < 1.0e-9);
+        assert!((start.acc - a0).abs() < 1.0e-9);
+
+        let end = stop.tp(stop.start_t + stop.total_time).unwrap();
+
+        assert!((end.pos - stop.q1()).abs() < 1.0e-9);
+        assert!(end.vel.abs() < 1.0e-9, "{end:?}");
+        assert!(end.acc.abs() < 1.0e-9, "{end:?}");
+
+        let dt = 0.001;
+        let mut t = stop.start_t;
+
+        while t + dt <= stop.start_t + stop.total_time {
+            let a = stop.tp(t).unwrap();
+            let b = stop.tp(t + dt).unwrap();
+
+            assert!(a.acc.abs() <= lim.acc.max(a0.abs()) + 1.0e-9, "{t} {a:?}");
+            assert!(((b.pos - a.pos) / dt - (a.vel + b.vel) / 2.0).abs() < 1.0e-3);
+            assert!(((b.vel - a.vel) / dt - (a.acc + b.acc) / 2.0).abs() < 1.0e-2);
+
+            // Jerk limited, so acceleration is continuous
+            assert!((b.acc - a.acc).abs() <= lim.jerk * dt + 1.0e-9);
+
+            t += dt;
+        }
+
+        assert!(stop.tp(stop.start_t + stop.total_time + dt).is_none());
+    }
+
+    #[test]
+    fn stop_from_cruise() {
+        let lim = Lim {
+            vel: 10.0,
+            acc: 10.0,
+            jerk: 40.0,
+        };
+
+        let stop = Stop::new(1.0, 3.0, 10.0, 0.0, &lim);
+
+        check_stop(&stop, 3.0, 10.0, 0.0, &lim);
+
+        // Symmetrical profile reaching the acceleration limit
+        assert!((stop.total_time - 1.25).abs() < 1.0e-9);
+        assert!((stop.distance - 6.25).abs() < 1.0e-9);
+
+        // Same stop the other way
+        let stop = Stop::new(1.0, 3.0, -10.0, 0.0, &lim);
+
+        check_stop(&stop, 3.0, -10.0, 0.0, &lim);
+
+        assert!((stop.total_time - 1.25).abs() < 1.0e-9);
+        assert!((stop.distance + 6.25).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn stop_while_accelerating() {
+        let lim = Lim {
+            vel: 10.0,
+            acc: 10.0,
+            jerk: 40.0,
+        };
+
+        // Long enough to reach the acceleration limit
+        let stop = Stop::new(0.0, 0.0, 5.0, 8.0, &lim);
+
+        check_stop(&stop, 0.0, 5.0, 8.0, &lim);
+
+        // Too slow to reach it
+        let stop = Stop::new(0.0, 0.0, 0.5, 2.0, &lim);
+
+        check_stop(&stop, 0.0, 0.5, 2.0, &lim);
+
+        assert!(stop.phases[1].0 == 0.0);
+    }
+
+    #[test]
+    fn stop_while_decelerating() {
+        let lim = Lim {
+            vel: 10.0,
+            acc: 10.0,
+            jerk: 40.0,
+        };
+
+        // Releasing the deceleration overshoots zero velocity, so the motion reverses a little
+        let stop = Stop::new(0.0, 0.0, 0.5, -10.0, &lim);
+
+        check_stop(&stop, 0.0, 0.5, -10.0, &lim);
+
+        let min_vel = stop.tp(stop.phases[0].0).unwrap().vel;
+
+        assert!(min_vel < 0.0, "{min_vel}");
+
+        // Already stopped
+        let stop = Stop::new(0.0, 2.0, 0.0, 0.0, &lim);
+
+        assert_eq!(stop.total_time, 0.0);
+        assert_eq!(stop.q1(), 2.0);
+    }
+
+    #[test]
+    fn stop_segment() {
+        let lim = Lim {
+            vel: 10.0,
+            acc: 10.0,
+            jerk: 40.0,
+        };
+
+        let segment = Segment::new(0.0, 0.0, 20.0, 0.0, 0.0, &lim);
+
+        for t in [0.1, 0.5, 1.0, 2.5] {
+            let out = segment.tp(t).unwrap();
+            let stop = segment.stop(t).unwrap();
+
+            check_stop(&stop, out.pos, out.vel, out.acc, &lim);
+
+            // Never further than carrying on with the planned move
+            assert!(stop.q1() <= 20.0);
+        }
+
+        assert!(segment.stop(segment.total_time() + 1.0).is_none());
+    }
+
+    #[test]
+    fn invalid_inputs() {
+        let lim = Lim {
+            vel: 10.0,
+            acc: 10.0,
+            jerk: 40.0,
+        };
+
+        assert!(Segment::try_new(0.0, 0.0, 20.0, 0.0, 0.0, &lim).is_ok());
+
+        assert_eq!(
+            Segment::try_new(0.0, 0.0, f32::INFINITY, 0.0, 0.0, &lim).unwrap_err(),
+            PlanError::NonFinite
+        );
+        assert_eq!(
+            Segment::try_new(0.0, 0.0, 20.0, 0.0, 0.0, &Lim { jerk: 0.0, ..lim }).unwrap_err(),
+            PlanError::InvalidLimit
+        );
+        assert_eq!(
+            Segment::try_new(0.0, 5.0, 5.0, 0.0, 0.0, &lim).unwrap_err(),
+            PlanError::ZeroDisplacement
+        );
+
+        // Too fast to stop within the segment
+        assert_eq!(
+            Segment::try_new(0.0, 0.0, 1.0, 10.0, 0.0, &lim).unwrap_err(),
+            PlanError::InfeasibleVelocities
+        );
+
+        assert_eq!(
+            Stop::try_new(0.0, 0.0, f32::NAN, 0.0, &lim).unwrap_err(),
+            PlanError::NonFinite
+        );
+    }
 }
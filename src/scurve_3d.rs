@@ -0,0 +1,789 @@
+//! Jerk limited (double S) segment with synchronised axes.
+//!
+//! The single axis [`scurve`](crate::scurve) profile is planned along the length of the segment,
+//! then projected back onto each axis so every axis starts and finishes together.
+
+use crate::{
+    error::{self, PlanError},
+    kinematics::{self, Kinematics, LimitError},
+    profile::Profile,
+    real::Real,
+    scurve, trapezoidal_non_zero_3d,
+};
+
+use alloc::vec::Vec;
+
+pub use crate::trapezoidal_non_zero_3d::{Coord, Coord3};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lim<const N: usize = 3, T: Real = f32> {
+    pub vel: Coord<N, T>,
+    pub acc: Coord<N, T>,
+    pub jerk: Coord<N, T>,
+}
+
+// `Default` can't be derived for any `N` as nalgebra requires `[T; N]: Default`.
+impl<const N: usize, T: Real> Default for Lim<N, T> {
+    fn default() -> Self {
+        Self {
+            vel: Coord::zeros(),
+            acc: Coord::zeros(),
+            jerk: Coord::zeros(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Out<const N: usize = 3, T: Real = f32> {
+    pub pos: Coord<N, T>,
+    pub vel: Coord<N, T>,
+    pub acc: Coord<N, T>,
+    pub jerk: Coord<N, T>,
+}
+
+impl<const N: usize, T: Real> Default for Out<N, T> {
+    fn default() -> Self {
+        Self {
+            pos: Coord::zeros(),
+            vel: Coord::zeros(),
+            acc: Coord::zeros(),
+            jerk: Coord::zeros(),
+        }
+    }
+}
+
+impl<const N: usize, T: Real> From<Out<N, T>> for trapezoidal_non_zero_3d::Out<N, T> {
+    fn from(out: Out<N, T>) -> Self {
+        Self {
+            pos: out.pos,
+            vel: out.vel,
+            acc: out.acc,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Segment<const N: usize = 3, T: Real = f32> {
+    /// Start time of this segment.
+    pub start_t: T,
+    /// Initial position.
+    q0: Coord<N, T>,
+    /// Final position.
+    q1: Coord<N, T>,
+    /// Initial velocity.
+    v0: Coord<N, T>,
+    /// Final velocity.
+    v1: Coord<N, T>,
+
+    /// Total time.
+    pub total_time: T,
+
+    /// Unit vector pointing from `q0` to `q1`.
+    direction: Coord<N, T>,
+
+    /// Profile along the path, from zero to the length of the segment.
+    profile: scurve::Segment<T>,
+
+    /// Limits provided by the user.
+    lim: Lim<N, T>,
+}
+
+impl<const N: usize, T: Real> Default for Segment<N, T> {
+    fn default() -> Self {
+        Self {
+            start_t: T::zero(),
+            q0: Coord::zeros(),
+            q1: Coord::zeros(),
+            v0: Coord::zeros(),
+            v1: Coord::zeros(),
+            total_time: T::zero(),
+            direction: Coord::zeros(),
+            profile: scurve::Segment::default(),
+            lim: Lim::default(),
+        }
+    }
+}
+
+impl<const N: usize, T: Real> Segment<N, T> {
+    /// Like [`new`](Self::new), but returns an error for inputs that can't be planned instead of
+    /// panicking or planning an empty segment.
+    pub fn try_new(
+        q0: Coord<N, T>,
+        q1: Coord<N, T>,
+        v0: Coord<N, T>,
+        v1: Coord<N, T>,
+        start_t: T,
+        lim: &Lim<N, T>,
+    ) -> Result<Self, PlanError<T>> {
+        error::check_finite(
+            q0.iter()
+                .chain(q1.iter())
+                .chain(v0.iter())
+                .chain(v1.iter())
+                .copied()
+                .chain([start_t]),
+        )?;
+        error::check_limits(
+            lim.vel
+                .iter()
+                .chain(lim.acc.iter())
+                .chain(lim.jerk.iter())
+                .copied(),
+        )?;
+
+        if q0 == q1 {
+            return Err(PlanError::ZeroDisplacement);
+        }
+
+        let segment = Self::new(q0, q1, v0, v1, start_t, lim);
+
+        if !segment.is_feasible() {
+            return Err(PlanError::InfeasibleVelocities);
+        }
+
+        Ok(segment)
+    }
+
+    /// Only the components of `v0` and `v1` along the direction of travel are used, clamped to the
+    /// velocity limit.
+    pub fn new(
+        q0: Coord<N, T>,
+        q1: Coord<N, T>,
+        v0: Coord<N, T>,
+        v1: Coord<N, T>,
+        start_t: T,
+        lim: &Lim<N, T>,
+    ) -> Self {
+        assert!(
+            lim.acc > Coord::zeros() && lim.vel > Coord::zeros() && lim.jerk > Coord::zeros(),
+            "Limits must all be positive values, got {:?}",
+            lim
+        );
+
+        let delta = q1 - q0;
+        let len = delta.norm();
+
+        // Nothing to do, and no direction to do it in
+        if len <= T::zero() {
+            return Self {
+                start_t,
+                q0,
+                q1,
+                lim: *lim,
+                ..Self::default()
+            };
+        }
+
+        let direction = delta / len;
+
+        let path_lim = path_lim(direction, lim);
+
+        let speed = |vel: Coord<N, T>| vel.dot(&direction).max(T::zero()).min(path_lim.vel);
+
+        let (s0, s1) = (speed(v0), speed(v1));
+
+        let profile = scurve::Segment::new(start_t, T::zero(), len, s0, s1, &path_lim);
+
+        Self {
+            start_t,
+            q0,
+            q1,
+            v0: direction * s0,
+            v1: direction * s1,
+            total_time: profile.total_time(),
+            direction,
+            profile,
+            lim: *lim,
+        }
+    }
+
+    /// Fastest stop from the state this segment is in at time `t`, staying on the line. `None` if
+    /// `t` is outside this segment.
+    pub fn stop(&self, t: T) -> Option<Stop<N, T>> {
+        let out = self.tp(t)?;
+
+        Some(Stop::new(t, &out, &self.lim))
+    }
+
+    /// Get trajectory parameters at the given time `t`.
+    pub fn tp(&self, t: T) -> Option<Out<N, T>> {
+        if t < self.start_t || t > self.start_t + self.total_time {
+            return None;
+        }
+
+        let out = self.profile.tp(t)?;
+
+        Some(Out {
+            pos: self.q0 + self.direction * out.pos,
+            vel: self.direction * out.vel,
+            acc: self.direction * out.acc,
+            jerk: self.direction * out.jerk,
+        })
+    }
+
+    pub fn q0(&self) -> Coord<N, T> {
+        self.q0
+    }
+
+    pub fn q1(&self) -> Coord<N, T> {
+        self.q1
+    }
+
+    pub fn v0(&self) -> Coord<N, T> {
+        self.v0
+    }
+
+    pub fn v1(&self) -> Coord<N, T> {
+        self.v1
+    }
+
+    pub fn lim(&self) -> Lim<N, T> {
+        self.lim
+    }
+
+    /// Whether the boundary velocities can be reached within the length of the segment. Infeasible
+    /// segments have zero duration.
+    pub fn is_feasible(&self) -> bool {
+        self.profile.is_feasible() || self.q0 == self.q1
+    }
+
+    /// Check this segment against the joint limits of the given kinematics, sampling every `dt`
+    /// seconds.
+    pub fn check_joint_limits<K: Kinematics<N, T> + ?Sized>(
+        &self,
+        kinematics: &K,
+        dt: T,
+    ) -> Result<(), LimitError<N, T>> {
+        kinematics::check_limits(
+            kinematics,
+            self.start_t,
+            self.start_t + self.total_time,
+            dt,
+            |t| self.tp(t).map(Into::into),
+        )
+    }
+}
+
+/// Fastest jerk limited stop from an arbitrary multi-axis state, e.g. for an E-stop or a tripped
+/// limit switch.
+///
+/// When velocity and acceleration point along the same line, as they do anywhere along a straight
+/// segment, the stop is planned along that line with the [`scurve::Stop`] profile so the path is
+/// kept. Otherwise, e.g. part way round a curve, there's no line to stay on, so each axis stops as
+/// quickly as its own limits allow.
+#[derive(Debug, Clone, Copy)]
+pub struct Stop<const N: usize = 3, T: Real = f32> {
+    /// Start time of the stop.
+    pub start_t: T,
+    /// Duration of the stop, i.e. of the slowest axis.
+    pub total_time: T,
+    /// Distance travelled by each axis before coming to rest.
+    pub distance: Coord<N, T>,
+    /// Initial position.
+    q0: Coord<N, T>,
+    profile: StopProfile<N, T>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum StopProfile<const N: usize, T: Real> {
+    /// Stop along the unit vector `direction`.
+    Path {
+        direction: Coord<N, T>,
+        profile: scurve::Stop<T>,
+    },
+    /// Every axis stops independently.
+    Axes([scurve::Stop<T>; N]),
+}
+
+impl<const N: usize, T: Real> Stop<N, T> {
+    /// Like [`new`](Self::new), but returns an error for non-finite inputs or invalid limits
+    /// instead of panicking.
+    pub fn try_new(start_t: T, out: &Out<N, T>, lim: &Lim<N, T>) -> Result<Self, PlanError<T>> {
+        error::check_finite(
+            out.pos
+                .iter()
+                .chain(out.vel.iter())
+                .chain(out.acc.iter())
+                .copied()
+                .chain([start_t]),
+        )?;
+        error::check_limits(lim.acc.iter().chain(lim.jerk.iter()).copied())?;
+
+        Ok(Self::new(start_t, out, lim))
+    }
+
+    pub fn new(start_t: T, out: &Out<N, T>, lim: &Lim<N, T>) -> Self {
+        assert!(
+            lim.acc > Coord::zeros() && lim.jerk > Coord::zeros(),
+            "Limits must all be positive values, got {:?}",
+            lim
+        );
+
+        let eps = T::default_epsilon().sqrt();
+
+        // Direction of travel, or of acceleration when starting from rest
+        let direction = out
+            .vel
+            .try_normalize(T::zero())
+            .or_else(|| out.acc.try_normalize(T::zero()));
+
+        let profile = match direction {
+            Some(direction)
+                if (out.acc - direction * out.acc.dot(&direction)).norm()
+                    <= eps * (T::one() + out.acc.norm()) =>
+            {
+                let profile = scurve::Stop::new(
+                    start_t,
+                    T::zero(),
+                    out.vel.dot(&direction),
+                    out.acc.dot(&direction),
+                    &path_lim(direction, lim),
+                );
+
+                StopProfile::Path { direction, profile }
+            }
+            _ => StopProfile::Axes(core::array::from_fn(|axis| {
+                scurve::Stop::new(
+                    start_t,
+                    T::zero(),
+                    out.vel[axis],
+                    out.acc[axis],
+                    &scurve::Lim {
+                        vel: lim.vel[axis],
+                        acc: lim.acc[axis],
+                        jerk: lim.jerk[axis],
+                    },
+                )
+            })),
+        };
+
+        let (total_time, distance) = match &profile {
+            StopProfile::Path { direction, profile } => {
+                (profile.total_time, *direction * profile.distance)
+            }
+            StopProfile::Axes(axes) => (
+                axes.iter()
+                    .fold(T::zero(), |total, axis| total.max(axis.total_time)),
+                Coord::from_fn(|axis, _| axes[axis].distance),
+            ),
+        };
+
+        Self {
+            start_t,
+            total_time,
+            distance,
+            q0: out.pos,
+            profile,
+        }
+    }
+
+    /// Whether the stop stays on the line the motion was following.
+    pub fn is_on_path(&self) -> bool {
+        matches!(self.profile, StopProfile::Path { .. })
+    }
+
+    /// Position the stop comes to rest at.
+    pub fn q1(&self) -> Coord<N, T> {
+        self.q0 + self.distance
+    }
+
+    /// Get trajectory parameters at the given time `t`.
+    pub fn tp(&self, t: T) -> Option<Out<N, T>> {
+        if t < self.start_t || t > self.start_t + self.total_time {
+            return None;
+        }
+
+        match &self.profile {
+            StopProfile::Path { direction, profile } => {
+                let out = profile.tp(t)?;
+
+                Some(Out {
+                    pos: self.q0 + *direction * out.pos,
+                    vel: *direction * out.vel,
+                    acc: *direction * out.acc,
+                    jerk: *direction * out.jerk,
+                })
+            }
+            StopProfile::Axes(axes) => {
+                let mut result = Out {
+                    pos: self.q0,
+                    ..Out::default()
+                };
+
+                for (axis, profile) in axes.iter().enumerate() {
+                    // Axes that have already stopped stay where they are
+                    let out = profile.tp(t.min(profile.start_t + profile.total_time))?;
+
+                    result.pos[axis] += out.pos;
+
+                    if t < profile.start_t + profile.total_time {
+                        result.vel[axis] = out.vel;
+                        result.acc[axis] = out.acc;
+                        result.jerk[axis] = out.jerk;
+                    }
+                }
+
+                Some(result)
+            }
+        }
+    }
+}
+
+impl<const N: usize, T: Real> Profile<T> for Segment<N, T> {
+    type Out = Out<N, T>;
+
+    fn start_t(&self) -> T {
+        self.start_t
+    }
+
+    fn duration(&self) -> T {
+        self.total_time
+    }
+
+    fn tp(&self, t: T) -> Option<Out<N, T>> {
+        Segment::tp(self, t)
+    }
+
+    fn phase_times(&self) -> Vec<T> {
+        self.profile.phase_times()
+    }
+}
+
+impl<const N: usize, T: Real> Profile<T> for Stop<N, T> {
+    type Out = Out<N, T>;
+
+    fn start_t(&self) -> T {
+        self.start_t
+    }
+
+    fn duration(&self) -> T {
+        self.total_time
+    }
+
+    fn tp(&self, t: T) -> Option<Out<N, T>> {
+        Stop::tp(self, t)
+    }
+
+    /// Every time any axis changes phase, when the axes stop independently.
+    fn phase_times(&self) -> Vec<T> {
+        match &self.profile {
+            StopProfile::Path { profile, .. } => profile.phase_times(),
+            StopProfile::Axes(axes) => {
+                let end_t = self.start_t + self.total_time;
+
+                let mut times = axes
+                    .iter()
+                    .flat_map(|axis| {
+                        // Axes that stop early finish a phase before the end of the whole stop
+                        let mut times = axis.phase_times();
+                        times.push(axis.start_t + axis.total_time);
+
+                        times
+                    })
+                    .filter(|t| *t > self.start_t && *t < end_t)
+                    .collect::<Vec<_>>();
+
+                times.sort_by(|a, b| a.partial_cmp(b).unwrap_or(core::cmp::Ordering::Equal));
+                times.dedup();
+
+                times
+            }
+        }
+    }
+}
+
+/// Per-axis limits projected onto the unit vector `direction`. The most constrained axis limits the
+/// whole move, and every other axis moves in proportion to it.
+pub(crate) fn path_lim<const N: usize, T: Real>(
+    direction: Coord<N, T>,
+    lim: &Lim<N, T>,
+) -> scurve::Lim<T> {
+    let infinity = T::lit(f64::INFINITY);
+
+    (0..N).filter(|axis| direction[*axis] != T::zero()).fold(
+        scurve::Lim {
+            vel: infinity,
+            acc: infinity,
+            jerk: infinity,
+        },
+        |path_lim, axis| {
+            let scale = direction[axis].abs();
+
+            scurve::Lim {
+                vel: path_lim.vel.min(lim.vel[axis] / scale),
+                acc: path_lim.acc.min(lim.acc[axis] / scale),
+                jerk: path_lim.jerk.min(lim.jerk[axis] / scale),
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::assert_approx_eq;
+
+    use super::*;
+
+    // Single axis: ignore Y and Z
+    #[test]
+    fn book_example_3_9() {
+        let lim = Lim {
+            vel: Coord3::new(5.0, 5.0, 5.0),
+            acc: Coord3::new(10.0, 10.0, 10.0),
+            jerk: Coord3::new(30.0, 30.0, 30.0),
+        };
+
+        let seg = Segment::new(
+            Coord3::zeros(),
+            Coord3::new(10.0, 0.0, 0.0),
+            Coord3::new(1.0, 0.0, 0.0),
+            Coord3::zeros(),
+            0.0,
+            &lim,
+        );
+
+        assert_approx_eq!(f32, seg.total_time, 2.71, epsilon = 1.0e-3);
+
+        let end = seg.tp(seg.total_time).unwrap();
+
+        assert!((end.pos - seg.q1()).norm() < 1.0e-3);
+        assert!(end.vel.norm() < 1.0e-3);
+    }
+
+    #[test]
+    fn synchronised() {
+        let q0 = Coord::<4>::new(1.0, 2.0, 3.0, 4.0);
+        let q1 = Coord::<4>::new(11.0, -13.0, 23.0, 4.0);
+
+        let lim = Lim {
+            vel: Coord::repeat(10.0),
+            acc: Coord::repeat(20.0),
+            // Lowest jerk limit on an axis which doesn't move the furthest
+            jerk: Coord::<4>::new(100.0, 100.0, 100.0, 1.0),
+        };
+
+        let seg = Segment::new(q0, q1, Coord::zeros(), Coord::zeros(), 1.0, &lim);
+
+        assert!(seg.is_feasible());
+        assert!(seg.tp(0.9).is_none());
+
+        let delta = q1 - q0;
+
+        for i in 0..=200 {
+            let t = 1.0 + seg.total_time * i as f32 / 200.0;
+
+            let out = seg.tp(t).unwrap();
+
+            // Every axis is the same proportion of the way through its move
+            let progress = (out.pos - q0).dot(&delta) / delta.norm_squared();
+
+            assert!((out.pos - (q0 + delta * progress)).norm() < 1.0e-4, "{t}");
+
+            let within =
+                |value: Coord<4>, lim: Coord<4>| value.abs() <= lim.map(|lim| lim * 1.001 + 1.0e-4);
+
+            assert!(within(out.vel, lim.vel), "{t}: {}", out.vel);
+            assert!(within(out.acc, lim.acc), "{t}: {}", out.acc);
+            assert!(within(out.jerk, lim.jerk), "{t}: {}", out.jerk);
+        }
+
+        let end = seg.tp(1.0 + seg.total_time).unwrap();
+
+        assert!((end.pos - q1).norm() < 1.0e-3);
+    }
+
+    #[test]
+    fn continuous_acceleration() {
+        let lim = Lim {
+            vel: Coord3::new(5.0, 5.0, 5.0),
+            acc: Coord3::new(10.0, 10.0, 10.0),
+            jerk: Coord3::new(50.0, 50.0, 50.0),
+        };
+
+        let seg = Segment::new(
+            Coord3::zeros(),
+            Coord3::new(3.0, 4.0, 0.0),
+            Coord3::new(0.6, 0.8, 0.0),
+            Coord3::new(0.3, 0.4, 0.0),
+            0.0,
+            &lim,
+        );
+
+        let dt = 0.001;
+
+        let mut prev = seg.tp(0.0).unwrap();
+
+        assert_eq!(prev.acc, Coord3::zeros());
+
+        let mut t = dt;
+
+        while let Some(out) = seg.tp(t) {
+            // A trapezoidal profile would jump by the whole acceleration limit
+            assert!(
+                (out.acc - prev.acc).abs() <= lim.jerk * dt * 1.01,
+                "{t}: {}",
+                out.acc
+            );
+
+            prev = out;
+            t += dt;
+        }
+
+        assert!((prev.vel - seg.v1()).norm() < 0.05);
+    }
+
+    #[test]
+    fn stop_on_path() {
+        let lim = Lim {
+            vel: Coord3::new(5.0, 5.0, 5.0),
+            acc: Coord3::new(10.0, 10.0, 10.0),
+            jerk: Coord3::new(50.0, 50.0, 50.0),
+        };
+
+        let q1 = Coord3::new(10.0, 5.0, 0.0);
+        let seg = Segment::new(
+            Coord3::zeros(),
+            q1,
+            Coord3::zeros(),
+            Coord3::zeros(),
+            0.0,
+            &lim,
+        );
+
+        for t in [0.2, 1.0, seg.total_time - 0.3] {
+            let stop = seg.stop(t).unwrap();
+
+            assert!(stop.is_on_path());
+
+            let start = stop.tp(t).unwrap();
+            let end = stop.tp(t + stop.total_time).unwrap();
+
+            assert_eq!(start.pos, seg.tp(t).unwrap().pos);
+            assert!(end.vel.norm() < 1.0e-4);
+            assert!(end.acc.norm() < 1.0e-4);
+            assert!((end.pos - stop.q1()).norm() < 1.0e-4);
+
+            // Comes to rest on the line, without going past the end of it
+            let progress = stop.q1().dot(&q1) / q1.norm_squared();
+
+            assert!((stop.q1() - q1 * progress).norm() < 1.0e-4);
+            assert!(progress <= 1.0);
+
+            for i in 0..=100 {
+                let out = stop.tp(t + stop.total_time * i as f32 / 100.0).unwrap();
+
+                let within =
+                    |value: Coord3, lim: Coord3| value.abs() <= lim.map(|lim| lim * 1.001 + 1.0e-4);
+
+                assert!(within(out.acc, lim.acc), "{t}: {}", out.acc);
+                assert!(within(out.jerk, lim.jerk), "{t}: {}", out.jerk);
+            }
+        }
+    }
+
+    #[test]
+    fn stop_off_path() {
+        let lim = Lim {
+            vel: Coord3::new(5.0, 5.0, 5.0),
+            acc: Coord3::new(10.0, 10.0, 10.0),
+            jerk: Coord3::new(50.0, 50.0, 50.0),
+        };
+
+        // Going round a curve
+        let out = Out {
+            pos: Coord3::new(1.0, 2.0, 3.0),
+            vel: Coord3::new(4.0, 0.0, 0.0),
+            acc: Coord3::new(0.0, 6.0, 0.0),
+            jerk: Coord3::zeros(),
+        };
+
+        let stop = Stop::new(0.5, &out, &lim);
+
+        assert!(!stop.is_on_path());
+
+        let start = stop.tp(0.5).unwrap();
+
+        assert_eq!(start.pos, out.pos);
+        assert_eq!(start.vel, out.vel);
+        assert_eq!(start.acc, out.acc);
+
+        let end = stop.tp(0.5 + stop.total_time).unwrap();
+
+        assert!(end.vel.norm() < 1.0e-4);
+        assert!(end.acc.norm() < 1.0e-4);
+        assert!((end.pos - stop.q1()).norm() < 1.0e-4);
+
+        // The X axis is still moving after Y has stopped
+        let out = stop.tp(0.9).unwrap();
+
+        assert!(out.vel.x > 0.0);
+        assert_eq!(out.vel.y, 0.0);
+        assert!(stop.distance.x > 0.0 && stop.distance.y > 0.0);
+        assert_eq!(stop.distance.z, 0.0);
+    }
+
+    #[test]
+    fn invalid_inputs() {
+        let lim = Lim {
+            vel: Coord3::new(5.0, 5.0, 5.0),
+            acc: Coord3::new(10.0, 10.0, 10.0),
+            jerk: Coord3::new(50.0, 50.0, 50.0),
+        };
+
+        let q1 = Coord3::new(10.0, 5.0, 0.0);
+
+        assert!(Segment::try_new(
+            Coord3::zeros(),
+            q1,
+            Coord3::zeros(),
+            Coord3::zeros(),
+            0.0,
+            &lim
+        )
+        .is_ok());
+
+        assert_eq!(
+            Segment::try_new(
+                Coord3::zeros(),
+                q1,
+                Coord3::zeros(),
+                Coord3::zeros(),
+                0.0,
+                &Lim {
+                    jerk: Coord3::new(50.0, 0.0, 50.0),
+                    ..lim
+                }
+            )
+            .unwrap_err(),
+            PlanError::InvalidLimit
+        );
+        assert_eq!(
+            Segment::try_new(q1, q1, Coord3::zeros(), Coord3::zeros(), 0.0, &lim).unwrap_err(),
+            PlanError::ZeroDisplacement
+        );
+
+        // Too fast to stop within the segment
+        assert_eq!(
+            Segment::try_new(
+                Coord3::zeros(),
+                Coord3::new(0.1, 0.0, 0.0),
+                Coord3::new(5.0, 0.0, 0.0),
+                Coord3::zeros(),
+                0.0,
+                &lim
+            )
+            .unwrap_err(),
+            PlanError::InfeasibleVelocities
+        );
+
+        let out = Out {
+            pos: Coord3::new(f32::NAN, 0.0, 0.0),
+            ..Out::default()
+        };
+
+        assert_eq!(
+            Stop::try_new(0.0, &out, &lim).unwrap_err(),
+            PlanError::NonFinite
+        );
+    }
+}
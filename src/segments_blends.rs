@@ -1,44 +1,575 @@
-//! Trapezoidal trajectory segments blended with arcs.
+//! Trapezoidal trajectory segments, programmed arcs and splines, blended with arcs, clothoids or
+//! Bezier curves.
+
+use alloc::vec::Vec;
 
 use crate::{
+    arc::{Arc, ArcCenter, Direction, Plane},
     arc_blend::ArcBlend,
-    trapezoidal_non_zero_3d::{Coord3, Lim, Out, Segment},
+    bezier_blend::BezierBlend,
+    blend::Blend,
+    clothoid_blend::ClothoidBlend,
+    error::{self, PlanError},
+    feed_override::FeedOverride,
+    interpolator::Interpolator,
+    kinematics::{Kinematics, LimitError},
+    profile::Profile,
+    real::Real,
+    scurve, scurve_3d,
+    spline::{self, Spline},
+    trapezoidal_non_zero_3d::{Coord, Lim, Out, Segment},
 };
 
+/// Largest angle in radians between the directions of travel either side of the join between an
+/// arc and another item for the join to count as smooth.
+const TANGENT_TOLERANCE: f64 = 1.0e-3;
+
+/// LinuxCNC style path control mode, deciding what happens at the corner between two segments.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathMode<T = f32> {
+    /// `G61.1` exact stop mode: stop at the end of every segment.
+    ExactStop,
+    /// `G61` exact path mode: reach every programmed point, only passing through without stopping
+    /// when two segments are colinear.
+    ExactPath,
+    /// `G64` blending mode. `G64 P<tolerance>` keeps the blend within `tolerance` of the
+    /// programmed point. Plain `G64` (`None`) uses the largest blend the segment lengths allow.
+    Blend(Option<T>),
+}
+
+/// Velocity profile used for the straight line segments of a trajectory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinearProfile<const N: usize = 3, T: Real = f32> {
+    /// Acceleration limited, with steps in acceleration at every phase change.
+    Trapezoidal,
+    /// Jerk limited double S profile, with the given jerk limit for each axis.
+    SCurve { jerk: Coord<N, T> },
+}
+
+/// Shape of the corner blends inserted in [`PathMode::Blend`] mode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendShape {
+    /// Circular arc. Curvature, and so centripetal acceleration, steps at either end.
+    Arc,
+    /// Pair of clothoids with curvature rising linearly to the middle of the corner and back down,
+    /// so acceleration is continuous. Blends are also jerk limited with an
+    /// [`SCurve`](LinearProfile::SCurve) profile.
+    Clothoid,
+    /// Quintic Bezier curve, joining the lines with zero curvature like a clothoid.
+    Bezier,
+}
+
 #[derive(Debug)]
-pub enum Item {
-    Linear(Segment),
-    ArcBlend(ArcBlend),
+pub enum Item<const N: usize = 3, T: Real = f32> {
+    Linear(Segment<N, T>),
+    SCurve(scurve_3d::Segment<N, T>),
+    ArcBlend(ArcBlend<N, T>),
+    ClothoidBlend(ClothoidBlend<N, T>),
+    BezierBlend(BezierBlend<N, T>),
+    /// Programmed arc, helix or spiral. Arcs are always timed with a trapezoidal profile.
+    Arc(Arc<N, T>),
+    /// NURBS curve. Splines are always timed with a trapezoidal profile.
+    Spline(Spline<N, T>),
+}
+
+impl<const N: usize, T: Real> Item<N, T> {
+    /// Plan a straight line segment using the given profile, or an error if it can't be planned
+    /// with the given limits.
+    fn linear(
+        profile: LinearProfile<N, T>,
+        q0: Coord<N, T>,
+        q1: Coord<N, T>,
+        v0: Coord<N, T>,
+        v1: Coord<N, T>,
+        start_t: T,
+        lim: &Lim<N, T>,
+    ) -> Result<Self, PlanError<T>> {
+        match profile {
+            LinearProfile::Trapezoidal => {
+                Segment::try_new(q0, q1, v0, v1, start_t, lim).map(Item::Linear)
+            }
+            LinearProfile::SCurve { jerk } => scurve_3d::Segment::try_new(
+                q0,
+                q1,
+                v0,
+                v1,
+                start_t,
+                &scurve_3d::Lim {
+                    vel: lim.vel,
+                    acc: lim.acc,
+                    jerk,
+                },
+            )
+            .map(Item::SCurve),
+        }
+    }
+
+    /// Corner blend between two lines with the given shape, or `None` if the corner can't be
+    /// blended.
+    fn blend(
+        shape: BlendShape,
+        profile: LinearProfile<N, T>,
+        prev: Coord<N, T>,
+        mid: Coord<N, T>,
+        next: Coord<N, T>,
+        max_deviation: T,
+        lim: Lim<N, T>,
+    ) -> Option<Self> {
+        // A zero tolerance or a reversal can't be blended, so stop instead
+        match shape {
+            BlendShape::Arc => {
+                let blend = ArcBlend::new(prev, mid, next, max_deviation, T::zero(), lim);
+
+                (!blend.is_colinear && blend.arc_radius > T::zero())
+                    .then_some(Item::ArcBlend(blend))
+            }
+            BlendShape::Clothoid => {
+                let jerk = match profile {
+                    LinearProfile::Trapezoidal => None,
+                    LinearProfile::SCurve { jerk } => Some(jerk),
+                };
+
+                let blend =
+                    ClothoidBlend::new(prev, mid, next, max_deviation, T::zero(), lim, jerk);
+
+                (!blend.is_colinear && blend.clothoid_len > T::zero())
+                    .then_some(Item::ClothoidBlend(blend))
+            }
+            BlendShape::Bezier => {
+                let blend = BezierBlend::new(prev, mid, next, max_deviation, lim);
+
+                (!blend.is_colinear && blend.len() > T::zero()).then_some(Item::BezierBlend(blend))
+            }
+        }
+    }
+
+    /// The corner blend, or `None` for straight line segments.
+    pub fn as_blend(&self) -> Option<&dyn Blend<N, T>> {
+        match self {
+            Item::ArcBlend(blend) => Some(blend),
+            Item::ClothoidBlend(blend) => Some(blend),
+            Item::BezierBlend(blend) => Some(blend),
+            Item::Linear(_) | Item::SCurve(_) | Item::Arc(_) | Item::Spline(_) => None,
+        }
+    }
+
+    /// Velocity and acceleration limits of a straight line segment, or `None` for blends.
+    fn linear_lim(&self) -> Option<Lim<N, T>> {
+        match self {
+            Item::Linear(line) => Some(line.lim()),
+            Item::SCurve(line) => Some(Lim {
+                vel: line.lim().vel,
+                acc: line.lim().acc,
+            }),
+            Item::ArcBlend(_)
+            | Item::ClothoidBlend(_)
+            | Item::BezierBlend(_)
+            | Item::Arc(_)
+            | Item::Spline(_) => None,
+        }
+    }
+
+    /// Move the end of a straight line segment to `q1`, keeping its start, profile and limits.
+    /// The segment is left to stop at `q1` until it's replanned with the rest of the trajectory.
+    fn with_end(&self, q1: Coord<N, T>) -> Self {
+        match self {
+            Item::Linear(line) => Item::Linear(Segment::new(
+                line.q0(),
+                q1,
+                line.v0(),
+                Coord::zeros(),
+                line.start_t,
+                &line.lim(),
+            )),
+            Item::SCurve(line) => Item::SCurve(scurve_3d::Segment::new(
+                line.q0(),
+                q1,
+                line.v0(),
+                Coord::zeros(),
+                line.start_t,
+                &line.lim(),
+            )),
+            Item::ArcBlend(_)
+            | Item::ClothoidBlend(_)
+            | Item::BezierBlend(_)
+            | Item::Arc(_)
+            | Item::Spline(_) => panic!("Only straight line segments can be moved"),
+        }
+    }
+
+    /// Position, unit tangent and curvature vector at distance `s` along an arc or spline.
+    fn curve_geometry(&self, s: T) -> (Coord<N, T>, Coord<N, T>, Coord<N, T>) {
+        match self {
+            Item::Arc(arc) => arc.geometry(s),
+            Item::Spline(spline) => spline.geometry(s),
+            _ => panic!("Only arcs and splines are curves"),
+        }
+    }
+
+    /// Cut `len` off the start of an arc or spline, keeping its speeds and limits.
+    fn trimmed_start(&self, len: T) -> Self {
+        match self {
+            Item::Arc(arc) => {
+                let mut arc = *arc;
+
+                arc.trim_start(len);
+
+                Item::Arc(arc)
+            }
+            Item::Spline(spline) => {
+                let mut spline = spline.clone();
+
+                spline.trim_start(len);
+
+                Item::Spline(spline)
+            }
+            _ => panic!("Only arcs and splines can be trimmed"),
+        }
+    }
+
+    /// Cut `len` off the end of an arc or spline, keeping its speeds and limits.
+    fn trimmed_end(&self, len: T) -> Self {
+        match self {
+            Item::Arc(arc) => {
+                let mut arc = *arc;
+
+                arc.trim_end(len);
+
+                Item::Arc(arc)
+            }
+            Item::Spline(spline) => {
+                let mut spline = spline.clone();
+
+                spline.trim_end(len);
+
+                Item::Spline(spline)
+            }
+            _ => panic!("Only arcs and splines can be trimmed"),
+        }
+    }
+
+    /// Replan this item to start at `start_t`, changing speed from `v0` to `v1` along its path.
+    fn replan(&self, start_t: T, v0: T, v1: T) -> Self {
+        let direction = (self.q1() - self.q0())
+            .try_normalize(T::zero())
+            .unwrap_or_else(Coord::zeros);
+
+        match self {
+            Item::Linear(line) => Item::Linear(Segment::new(
+                line.q0(),
+                line.q1(),
+                direction * v0,
+                direction * v1,
+                start_t,
+                &line.lim(),
+            )),
+            Item::SCurve(line) => Item::SCurve(scurve_3d::Segment::new(
+                line.q0(),
+                line.q1(),
+                direction * v0,
+                direction * v1,
+                start_t,
+                &line.lim(),
+            )),
+            Item::ArcBlend(blend) => {
+                let mut blend = *blend;
+
+                blend.start_t = start_t;
+                blend.set_speeds(v0, v1);
+
+                Item::ArcBlend(blend)
+            }
+            Item::ClothoidBlend(blend) => {
+                let mut blend = *blend;
+
+                blend.start_t = start_t;
+                blend.set_speeds(v0, v1);
+
+                Item::ClothoidBlend(blend)
+            }
+            Item::BezierBlend(blend) => {
+                let mut blend = *blend;
+
+                blend.start_t = start_t;
+                blend.set_speeds(v0, v1);
+
+                Item::BezierBlend(blend)
+            }
+            Item::Arc(arc) => {
+                let mut arc = *arc;
+
+                arc.start_t = start_t;
+                arc.set_speeds(v0, v1);
+
+                Item::Arc(arc)
+            }
+            Item::Spline(spline) => {
+                let mut spline = spline.clone();
+
+                spline.start_t = start_t;
+                spline.set_speeds(v0, v1);
+
+                Item::Spline(spline)
+            }
+        }
+    }
+
+    /// Length of the path covered by this item.
+    fn len(&self) -> T {
+        match (self, self.as_blend()) {
+            (Item::Arc(arc), _) => arc.len,
+            (Item::Spline(spline), _) => spline.len,
+            (_, Some(blend)) => blend.path_len(),
+            (_, None) => (self.q1() - self.q0()).norm(),
+        }
+    }
+
+    /// Direction of travel at the start of a straight line segment, arc or spline.
+    fn start_tangent(&self) -> Coord<N, T> {
+        match self {
+            Item::Arc(arc) => arc.geometry(T::zero()).1,
+            Item::Spline(spline) => spline.geometry(T::zero()).1,
+            item => (item.q1() - item.q0())
+                .try_normalize(T::zero())
+                .unwrap_or_else(Coord::zeros),
+        }
+    }
+
+    /// Direction of travel at the end of a straight line segment, arc or spline.
+    fn end_tangent(&self) -> Coord<N, T> {
+        match self {
+            Item::Arc(arc) => arc.geometry(arc.len).1,
+            Item::Spline(spline) => spline.geometry(spline.len).1,
+            item => item.start_tangent(),
+        }
+    }
+
+    /// Speed, acceleration and jerk limits along the path of this item.
+    fn path_lim(&self) -> scurve::Lim<T> {
+        let infinity = T::lit(f64::INFINITY);
+
+        let direction = (self.q1() - self.q0())
+            .try_normalize(T::zero())
+            .unwrap_or_else(Coord::zeros);
+
+        match self {
+            Item::Linear(line) => scurve_3d::path_lim(
+                direction,
+                &scurve_3d::Lim {
+                    vel: line.lim().vel,
+                    acc: line.lim().acc,
+                    jerk: Coord::repeat(infinity),
+                },
+            ),
+            Item::SCurve(line) => scurve_3d::path_lim(direction, &line.lim()),
+            Item::ArcBlend(_) | Item::ClothoidBlend(_) | Item::BezierBlend(_) => scurve::Lim {
+                vel: self.as_blend().map_or(infinity, |blend| blend.max_speed()),
+                acc: infinity,
+                jerk: infinity,
+            },
+            Item::Arc(arc) => scurve::Lim {
+                vel: arc.max_speed(),
+                acc: arc.acceleration_limit,
+                jerk: infinity,
+            },
+            Item::Spline(spline) => scurve::Lim {
+                vel: spline.max_speed(),
+                acc: spline.acceleration_limit,
+                jerk: infinity,
+            },
+        }
+    }
+
+    /// Speed at the end of this item.
+    fn end_speed(&self) -> T {
+        match self {
+            Item::Linear(line) => line.v1().norm(),
+            Item::SCurve(line) => line.v1().norm(),
+            Item::ArcBlend(blend) => blend.v1,
+            Item::ClothoidBlend(blend) => blend.v1,
+            Item::BezierBlend(blend) => blend.v1,
+            Item::Arc(arc) => arc.v1,
+            Item::Spline(spline) => spline.v1,
+        }
+    }
+
+    /// Start position of this item.
+    pub fn q0(&self) -> Coord<N, T> {
+        match self {
+            Item::Linear(line) => line.q0(),
+            Item::SCurve(line) => line.q0(),
+            Item::ArcBlend(blend) => blend.arc_start,
+            Item::ClothoidBlend(blend) => blend.start,
+            Item::BezierBlend(blend) => blend.control_points[0],
+            Item::Arc(arc) => arc.start,
+            Item::Spline(spline) => spline.start,
+        }
+    }
+
+    /// End position of this item.
+    pub fn q1(&self) -> Coord<N, T> {
+        match self {
+            Item::Linear(line) => line.q1(),
+            Item::SCurve(line) => line.q1(),
+            Item::ArcBlend(blend) => blend.arc_end,
+            Item::ClothoidBlend(blend) => blend.end,
+            Item::BezierBlend(blend) => blend.control_points[5],
+            Item::Arc(arc) => arc.end,
+            Item::Spline(spline) => spline.end,
+        }
+    }
+}
+
+impl<const N: usize, T: Real> Profile<T> for Item<N, T> {
+    type Out = Out<N, T>;
+
+    fn start_t(&self) -> T {
+        match self {
+            Item::Linear(line) => line.start_t(),
+            Item::SCurve(line) => line.start_t(),
+            Item::ArcBlend(blend) => blend.start_t(),
+            Item::ClothoidBlend(blend) => blend.start_t(),
+            Item::BezierBlend(blend) => blend.start_t(),
+            Item::Arc(arc) => arc.start_t(),
+            Item::Spline(spline) => spline.start_t(),
+        }
+    }
+
+    fn duration(&self) -> T {
+        match self {
+            Item::Linear(line) => line.duration(),
+            Item::SCurve(line) => line.duration(),
+            Item::ArcBlend(blend) => blend.duration(),
+            Item::ClothoidBlend(blend) => blend.duration(),
+            Item::BezierBlend(blend) => blend.duration(),
+            Item::Arc(arc) => arc.duration(),
+            Item::Spline(spline) => spline.duration(),
+        }
+    }
+
+    fn tp(&self, t: T) -> Option<Out<N, T>> {
+        match self {
+            Item::Linear(line) => Profile::tp(line, t),
+            Item::SCurve(line) => Profile::tp(line, t).map(Into::into),
+            Item::ArcBlend(blend) => Profile::tp(blend, t),
+            Item::ClothoidBlend(blend) => Profile::tp(blend, t),
+            Item::BezierBlend(blend) => Profile::tp(blend, t),
+            Item::Arc(arc) => Profile::tp(arc, t),
+            Item::Spline(spline) => Profile::tp(spline, t),
+        }
+    }
+
+    fn phase_times(&self) -> Vec<T> {
+        match self {
+            Item::Linear(line) => line.phase_times(),
+            Item::SCurve(line) => line.phase_times(),
+            Item::ArcBlend(blend) => blend.phase_times(),
+            Item::ClothoidBlend(blend) => blend.phase_times(),
+            Item::BezierBlend(blend) => blend.phase_times(),
+            Item::Arc(arc) => arc.phase_times(),
+            Item::Spline(spline) => spline.phase_times(),
+        }
+    }
 }
 
 #[derive(Debug)]
-pub struct Trajectory {
-    pub points: Vec<Coord3>,
-    pub blends: Vec<ArcBlend>,
-    pub items: Vec<Item>,
-    pub limits: Lim,
-    pub max_deviation: f32,
-    pub total_time: f32,
+pub struct Trajectory<const N: usize = 3, T: Real = f32> {
+    pub points: Vec<Coord<N, T>>,
+    pub blends: Vec<ArcBlend<N, T>>,
+    pub items: Vec<Item<N, T>>,
+    pub limits: Lim<N, T>,
+    /// Profile of straight line segments pushed from now on.
+    pub profile: LinearProfile<N, T>,
+    /// Shape of blends inserted at corners from now on.
+    pub blend_shape: BlendShape,
+    /// Path mode applied to segments pushed from now on.
+    pub path_mode: PathMode<T>,
+    /// Whether moves pushed from now on are rapids, which follow the rapid override instead of the
+    /// feed override when interpolated with a [`FeedOverride`].
+    pub rapid: bool,
+    /// Path mode of the segment ending at each point in [`points`](Self::points). This decides how
+    /// the corner at that point is handled once the next point is pushed.
+    pub path_modes: Vec<PathMode<T>>,
+    /// Number of items at the end of the trajectory to replan when a point is pushed. Items
+    /// before these are assumed to already be executing so are left alone.
+    pub lookahead: usize,
+    /// Fastest allowed speed at the end of each item in [`items`](Self::items), ignoring the
+    /// distance available to reach it.
+    end_speed_limits: Vec<T>,
+    /// Whether each item in [`items`](Self::items) is part of a rapid move.
+    rapids: Vec<bool>,
+    pub total_time: T,
 }
 
-impl Trajectory {
+impl<const N: usize, T: Real> Default for Trajectory<N, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, T: Real> Trajectory<N, T> {
     pub fn new() -> Self {
         Self {
             // points: vec![Coord3::zeros()],
             points: Vec::new(),
-            max_deviation: 0.5,
+            path_mode: PathMode::Blend(Some(T::lit(0.5))),
+            path_modes: Vec::new(),
             // blends: vec![ArcBlend::default()],
             blends: Vec::new(),
             items: Vec::new(),
             limits: Lim {
-                vel: Coord3::new(5.0, 5.0, 5.0),
-                acc: Coord3::new(10.0, 10.0, 10.0),
+                vel: Coord::repeat(T::lit(5.0)),
+                acc: Coord::repeat(T::lit(10.0)),
             },
-            total_time: 0.0,
+            profile: LinearProfile::Trapezoidal,
+            blend_shape: BlendShape::Arc,
+            lookahead: 64,
+            end_speed_limits: Vec::new(),
+            rapids: Vec::new(),
+            rapid: false,
+            total_time: T::zero(),
         }
     }
 
-    pub fn push_point(&mut self, new_point: Coord3) {
+    /// Push an item onto the end of the trajectory, with the fastest speed allowed at its end. A
+    /// blend is only part of a rapid move if the items either side of it are.
+    fn push_item(&mut self, item: Item<N, T>, end_speed_limit: T) {
+        let rapid = self.rapid && (item.as_blend().is_none() || self.rapids.last() == Some(&true));
+
+        self.items.push(item);
+        self.end_speed_limits.push(end_speed_limit);
+        self.rapids.push(rapid);
+    }
+
+    /// Whether the item at `index` in [`items`](Self::items) is part of a rapid move.
+    pub fn is_rapid(&self, index: usize) -> bool {
+        self.rapids.get(index).copied().unwrap_or(false)
+    }
+
+    /// Push a new point, moving to it in a straight line from the last one.
+    ///
+    /// Returns an error and leaves the trajectory unchanged if the move can't be planned, e.g. if
+    /// the new point is the same as the last one or [`limits`](Self::limits) are invalid.
+    pub fn push_point(&mut self, new_point: Coord<N, T>) -> Result<(), PlanError<T>> {
+        self.push_point_with_limits(new_point, self.limits)
+    }
+
+    /// Push a new point, limiting the move to it by `limits` instead of the trajectory-wide
+    /// [`limits`](Self::limits), e.g. to apply a programmed feed rate.
+    ///
+    /// Returns an error and leaves the trajectory unchanged if the move can't be planned.
+    pub fn push_point_with_limits(
+        &mut self,
+        new_point: Coord<N, T>,
+        limits: Lim<N, T>,
+    ) -> Result<(), PlanError<T>> {
+        // Corner blends are planned with the limits before the new line is, so check them first
+        error::check_finite(new_point.iter().copied())?;
+        error::check_limits(limits.vel.iter().chain(limits.acc.iter()).copied())?;
+
+        // First item affected by the new point
+        let modified = self.items.len().saturating_sub(1);
+
         match self.points.len() {
             0 => {
                 // let b = &mut self.blends[0];
@@ -63,16 +594,17 @@ impl Trajectory {
                 //     self.limits,
                 // );
 
-                let segment = Segment::new(
+                let segment = Item::linear(
+                    self.profile,
                     self.points[0],
                     new_point,
-                    Coord3::zeros(),
-                    Coord3::zeros(),
-                    0.0,
-                    &self.limits,
-                );
+                    Coord::zeros(),
+                    Coord::zeros(),
+                    T::zero(),
+                    &limits,
+                )?;
 
-                self.items.push(Item::Linear(segment));
+                self.push_item(segment, T::zero());
             }
             // // 3 points is a properly computed blend and two segments (one new)
             // 2 => {
@@ -111,9 +643,27 @@ impl Trajectory {
             //     self.items.push(Item::ArcBlend(blend));
             //     self.items.push(Item::Linear(segment));
             // }
-            // 3 or more points and we have a blend between the last and the newly added segment
+            // A line after an arc or spline starts where it actually ends
+            _ if matches!(self.items.last(), Some(Item::Arc(_) | Item::Spline(_))) => {
+                let start = self.items.last().map(Item::q1).unwrap_or(new_point);
+
+                self.push_joined(Item::linear(
+                    self.profile,
+                    start,
+                    new_point,
+                    Coord::zeros(),
+                    Coord::zeros(),
+                    T::zero(),
+                    &limits,
+                )?);
+            }
+            // 3 or more points and we have a corner between the last and the newly added segment
             _ => {
-                let Some(Item::Linear(last_segment)) = self.items.last_mut() else {
+                let Some((last_segment, last_lim)) = self
+                    .items
+                    .last_mut()
+                    .and_then(|item| item.linear_lim().map(|lim| (item, lim)))
+                else {
                     panic!("Last item should be a linear segment");
                 };
 
@@ -121,82 +671,655 @@ impl Trajectory {
                 let mid = last_segment.q1();
                 let next = new_point;
 
-                // TODO: Non-zero initial/final velocities
-                let mut blend =
-                    ArcBlend::new(prev, mid, next, self.max_deviation, 0.0, self.limits);
-
-                // Move last segment's end point to the start of the new blend
-                // TODO: Make this a setter instead of clobbering the previous segment completely.
-                let prev_segment_replace = Segment::new(
-                    last_segment.q0(),
-                    blend.arc_start,
-                    last_segment.v0(),
-                    // Final velocity of previous segment is now the blend start velocity.
-                    // Acceleration will be discontinuous.
-                    blend.tp(blend.start_t).unwrap().vel,
-                    last_segment.start_t,
-                    &self.limits,
-                );
-
-                // Blend starts at end of shortened previous segment
-                blend.start_t = prev_segment_replace.start_t + prev_segment_replace.total_time;
-
-                // Update previous segment's end position to be start of new blend.
-                *last_segment = prev_segment_replace;
-
-                // If segments are not colinear, add a new blend between previous linear segment and
-                // this new one.
-                // TODO: Merge colinear line segments
-                if !blend.is_colinear {
-                    self.items.push(Item::ArcBlend(blend));
+                // Corner at the end of the last segment is handled by that segment's mode
+                let path_mode = self.path_modes.last().copied().unwrap_or(self.path_mode);
+
+                let prev_delta = mid - prev;
+                let next_delta = next - mid;
+
+                // Segments pointing in the same direction. Segments which reverse are also colinear
+                // but must always stop at the midpoint. The first test is the squared norm of the
+                // cross product by Lagrange's identity, so this works for any number of axes. It's
+                // relative to the segment lengths so short corners aren't mistaken for straight
+                // lines, with some headroom for rounding in the subtraction.
+                let norms_squared = prev_delta.norm_squared() * next_delta.norm_squared();
+
+                let is_colinear = norms_squared - prev_delta.dot(&next_delta).powi(2)
+                    <= T::lit(16.0) * T::default_epsilon() * norms_squared
+                    && prev_delta.dot(&next_delta) > T::zero();
+
+                // The blend must respect the limits of both segments either side of it
+                let blend_limits = last_lim.inf(&limits);
+
+                let blend = match path_mode {
+                    PathMode::Blend(tolerance) if !is_colinear => {
+                        // No tolerance means blend as much as the segment lengths allow
+                        let max_deviation = tolerance.unwrap_or_else(|| T::lit(f64::INFINITY));
+
+                        Item::blend(
+                            self.blend_shape,
+                            self.profile,
+                            prev,
+                            mid,
+                            next,
+                            max_deviation,
+                            blend_limits,
+                        )
+                    }
+                    _ => None,
+                };
+
+                // Speeds and start times are set by the lookahead below
+                if let Some(blend) = blend {
+                    let (blend_start, blend_end) = (blend.q0(), blend.q1());
+                    let max_speed = blend.path_lim().vel;
+
+                    let segment = Item::linear(
+                        self.profile,
+                        blend_end,
+                        new_point,
+                        Coord::zeros(),
+                        Coord::zeros(),
+                        T::zero(),
+                        &limits,
+                    )?;
+
+                    // Move last segment's end point to the start of the new blend
+                    *last_segment = last_segment.with_end(blend_start);
+
+                    // Previous segment joins the blend at the blend's speed. Acceleration will be
+                    // discontinuous for arcs.
+                    if let Some(limit) = self.end_speed_limits.last_mut() {
+                        *limit = max_speed;
+                    }
+
+                    self.push_item(blend, max_speed);
+
+                    // Finally push new segment, starting at end of new blend
+                    self.push_item(segment, T::zero());
+                } else {
+                    let segment = Item::linear(
+                        self.profile,
+                        mid,
+                        new_point,
+                        Coord::zeros(),
+                        Coord::zeros(),
+                        T::zero(),
+                        &limits,
+                    )?;
+
+                    *last_segment = last_segment.with_end(mid);
+
+                    // Everything but exact stop mode can pass straight through colinear points.
+                    // Any other corner must come to a stop at the midpoint.
+                    // TODO: Merge colinear line segments
+                    if is_colinear && path_mode != PathMode::ExactStop {
+                        let speed = last_segment.path_lim().vel.min(segment.path_lim().vel);
+
+                        if let Some(limit) = self.end_speed_limits.last_mut() {
+                            *limit = speed;
+                        }
+                    }
+
+                    self.push_item(segment, T::zero());
                 }
+            }
+        }
 
-                // Finally push new segment, starting at end of new blend arc
-                // TODO: Non-zero velocity
-                self.items.push(Item::Linear(Segment::new(
-                    blend.arc_end,
-                    new_point,
-                    // Start velocity of new segment is the same as the end velocity of the blend
-                    // arc
-                    blend.tp(blend.time).unwrap().vel,
-                    Coord3::zeros(),
-                    blend.start_t + blend.time,
-                    &self.limits,
-                )));
+        self.plan(modified);
+
+        self.points.push(new_point);
+        self.path_modes.push(self.path_mode);
+
+        Ok(())
+    }
+
+    /// Push a programmed arc from the last point to `end`, making `turns` extra full turns. Axes
+    /// outside `plane` move linearly with the angle turned to make a helix, and an
+    /// [`ArcCenter::Spiral`] center makes a spiral.
+    ///
+    /// Returns `false` and pushes nothing if there's no point to start from, or `end` isn't on an
+    /// arc with the given center.
+    pub fn push_arc(
+        &mut self,
+        end: Coord<N, T>,
+        center: ArcCenter<N, T>,
+        plane: Plane,
+        direction: Direction,
+        turns: usize,
+    ) -> bool {
+        self.push_arc_with_limits(end, center, plane, direction, turns, self.limits)
+    }
+
+    /// Push a programmed arc like [`push_arc`](Self::push_arc), limited by `limits` instead of the
+    /// trajectory-wide [`limits`](Self::limits).
+    pub fn push_arc_with_limits(
+        &mut self,
+        end: Coord<N, T>,
+        center: ArcCenter<N, T>,
+        plane: Plane,
+        direction: Direction,
+        turns: usize,
+        limits: Lim<N, T>,
+    ) -> bool {
+        // Start where the last item actually ends
+        let Some(start) = self
+            .items
+            .last()
+            .map(Item::q1)
+            .or(self.points.last().copied())
+        else {
+            return false;
+        };
+
+        let Some(arc) = Arc::new(start, end, center, plane, direction, turns, &limits) else {
+            return false;
+        };
+
+        let modified = self.items.len().saturating_sub(1);
+
+        self.push_joined(Item::Arc(arc));
+
+        self.plan(modified);
+
+        self.points.push(arc.end);
+        self.path_modes.push(self.path_mode);
+
+        true
+    }
+
+    /// Push a spline of the given degree from the last point, ending at the last of
+    /// `control_points`. The last point is the first control point of the curve, so `weights` and
+    /// `knots` include an entry for it. No `weights` makes a B-spline, and no `knots` spaces them
+    /// evenly.
+    ///
+    /// Returns `false` and pushes nothing if there's no point to start from, or the control
+    /// points, weights and knots don't make a valid [`Spline`].
+    pub fn push_spline(
+        &mut self,
+        degree: usize,
+        control_points: &[Coord<N, T>],
+        weights: Option<&[T]>,
+        knots: Option<&[T]>,
+    ) -> bool {
+        self.push_spline_with_limits(degree, control_points, weights, knots, self.limits)
+    }
+
+    /// Push a spline like [`push_spline`](Self::push_spline), limited by `limits` instead of the
+    /// trajectory-wide [`limits`](Self::limits).
+    pub fn push_spline_with_limits(
+        &mut self,
+        degree: usize,
+        control_points: &[Coord<N, T>],
+        weights: Option<&[T]>,
+        knots: Option<&[T]>,
+        limits: Lim<N, T>,
+    ) -> bool {
+        // Start where the last item actually ends
+        let Some(start) = self
+            .items
+            .last()
+            .map(Item::q1)
+            .or(self.points.last().copied())
+        else {
+            return false;
+        };
+
+        let points = core::iter::once(start)
+            .chain(control_points.iter().copied())
+            .collect::<Vec<_>>();
+
+        let weights =
+            weights.map_or_else(|| points.iter().map(|_| T::one()).collect(), <[T]>::to_vec);
+        let knots = knots.map_or_else(
+            || spline::clamped_knots(points.len(), degree),
+            <[T]>::to_vec,
+        );
+
+        let Some(spline) = Spline::new(points, weights, knots, degree, &limits) else {
+            return false;
+        };
+
+        let modified = self.items.len().saturating_sub(1);
+        let end = spline.end;
+
+        self.push_joined(Item::Spline(spline));
+
+        self.plan(modified);
+
+        self.points.push(end);
+        self.path_modes.push(self.path_mode);
+
+        true
+    }
+
+    /// Push `item` after the last item, where one of the two is an arc or spline. Smooth joins are
+    /// passed through without stopping, except in exact stop mode. Other joins between a curve and
+    /// a line are blended in blend mode by cutting the curve back from the join, and the rest stop.
+    fn push_joined(&mut self, item: Item<N, T>) {
+        // Join at the end of the last item is handled by that item's mode
+        let path_mode = self.path_modes.last().copied().unwrap_or(self.path_mode);
+
+        let Some(last) = self.items.last_mut() else {
+            self.push_item(item, T::zero());
+
+            return;
+        };
+
+        let is_smooth =
+            (last.end_tangent() - item.start_tangent()).norm() <= T::lit(TANGENT_TOLERANCE);
+
+        let blended = match path_mode {
+            PathMode::Blend(tolerance) if !is_smooth => curve_line_blend(
+                self.blend_shape,
+                self.profile,
+                last,
+                &item,
+                tolerance.unwrap_or_else(|| T::lit(f64::INFINITY)),
+            ),
+            _ => None,
+        };
+
+        if let Some((prev, blend, next)) = blended {
+            let max_speed = blend.path_lim().vel;
+
+            *last = prev;
+
+            if let Some(limit) = self.end_speed_limits.last_mut() {
+                *limit = max_speed;
+            }
+
+            self.push_item(blend, max_speed);
+
+            self.push_item(next, T::zero());
+        } else {
+            if is_smooth && path_mode != PathMode::ExactStop {
+                let speed = last.path_lim().vel.min(item.path_lim().vel);
+
+                if let Some(limit) = self.end_speed_limits.last_mut() {
+                    *limit = speed;
+                }
             }
+
+            self.push_item(item, T::zero());
         }
+    }
 
-        // Very inefficient way of correctly computing total duration
-        self.total_time = self
+    /// Replan speeds over the last [`lookahead`](Self::lookahead) items, or from the item at
+    /// index `modified` if that's earlier, so the machine only stops where it has to. That's at
+    /// exact stops, unblended corners and the end of the trajectory.
+    ///
+    /// A backward pass finds the fastest speed at the end of each item that can still slow down
+    /// for everything after it, then a forward pass limits each speed to what can be reached from
+    /// the one before it.
+    fn plan(&mut self, modified: usize) {
+        let window = self
+            .items
+            .len()
+            .saturating_sub(self.lookahead)
+            .min(modified);
+
+        // Items before the window are left as they are, so continue on from the last of them
+        let (start_speed, mut start_t) = window
+            .checked_sub(1)
+            .map(|prev| (self.items[prev].end_speed(), self.items[prev].end_t()))
+            .unwrap_or((T::zero(), T::zero()));
+
+        let items = &self.items[window..];
+        let mut speeds = self.end_speed_limits[window..].to_vec();
+
+        // Backward pass
+        for i in (0..items.len()).rev() {
+            let mut speed = speeds[i].min(items[i].path_lim().vel);
+
+            if let Some(next) = items.get(i + 1) {
+                speed = speed.min(match next.as_blend() {
+                    Some(blend) => blend.reachable_speed(speeds[i + 1]),
+                    None => reachable_speed(speeds[i + 1], next.len(), &next.path_lim()),
+                });
+            }
+
+            speeds[i] = speed;
+        }
+
+        // Forward pass
+        let mut v0 = start_speed;
+
+        for (item, speed) in items.iter().zip(speeds.iter_mut()) {
+            *speed = speed.min(match item.as_blend() {
+                Some(blend) => blend.reachable_speed(v0),
+                None => reachable_speed(v0, item.len(), &item.path_lim()),
+            });
+
+            v0 = *speed;
+        }
+
+        let mut v0 = start_speed;
+
+        for (item, v1) in self.items[window..].iter_mut().zip(speeds) {
+            *item = item.replan(start_t, v0, v1);
+
+            start_t = item.end_t();
+            v0 = v1;
+        }
+
+        self.total_time = start_t;
+    }
+
+    /// Check every item in the trajectory against the joint limits of the given kinematics,
+    /// sampling every `dt` seconds.
+    pub fn check_joint_limits<K: Kinematics<N, T> + ?Sized>(
+        &self,
+        kinematics: &K,
+        dt: T,
+    ) -> Result<(), LimitError<N, T>> {
+        self.items.iter().try_for_each(|item| match item {
+            Item::Linear(line) => line.check_joint_limits(kinematics, dt),
+            Item::SCurve(line) => line.check_joint_limits(kinematics, dt),
+            Item::ArcBlend(blend) => blend.check_joint_limits(kinematics, dt),
+            Item::ClothoidBlend(blend) => blend.check_joint_limits(kinematics, dt),
+            Item::BezierBlend(blend) => blend.check_joint_limits(kinematics, dt),
+            Item::Arc(arc) => arc.check_joint_limits(kinematics, dt),
+            Item::Spline(spline) => spline.check_joint_limits(kinematics, dt),
+        })
+    }
+
+    /// Stream samples of this trajectory every `period` seconds, e.g. once per servo cycle.
+    pub fn interpolate(&self, period: T) -> Interpolator<'_, N, T> {
+        Interpolator::new(self, period)
+    }
+
+    /// Stream samples of this trajectory every `period` seconds, with feed and rapid overrides
+    /// that can be changed while it runs.
+    pub fn interpolate_with_override(&self, period: T) -> FeedOverride<'_, N, T> {
+        FeedOverride::new(self, period)
+    }
+
+    // Returns true if point belongs to a blend
+    //
+    // This searches every item, so use [`interpolate`](Self::interpolate) to stream samples at a
+    // fixed rate instead.
+    pub fn tp(&self, t: T) -> Option<(Out<N, T>, bool)> {
+        if t > self.total_time || t < T::zero() {
+            return None;
+        }
+
+        self.items
+            .iter()
+            .find_map(|item| item.tp(t).map(|out| (out, item.as_blend().is_some())))
+    }
+}
+
+/// The whole trajectory as one profile, starting at zero. Phases are those of every item, with a
+/// phase change where each item joins the next.
+impl<const N: usize, T: Real> Profile<T> for Trajectory<N, T> {
+    type Out = Out<N, T>;
+
+    fn start_t(&self) -> T {
+        T::zero()
+    }
+
+    fn duration(&self) -> T {
+        self.total_time
+    }
+
+    fn tp(&self, t: T) -> Option<Out<N, T>> {
+        Trajectory::tp(self, t).map(|(out, _is_blend)| out)
+    }
+
+    fn phase_times(&self) -> Vec<T> {
+        let mut times = self
             .items
             .iter()
-            .map(|item| match item {
-                Item::Linear(line) => line.total_time,
-                Item::ArcBlend(blend) => blend.time,
+            .flat_map(|item| {
+                let mut times = item.phase_times();
+                times.push(item.end_t());
+
+                times
             })
-            .sum();
+            .collect::<Vec<_>>();
 
-        self.points.push(new_point);
+        // The end of the last item is the end of the trajectory
+        times.pop();
+
+        times
     }
+}
+
+/// Fastest speed that can be reached from `v0` within `len`, or equivalently the fastest speed
+/// that can still slow down to `v0` within `len`. A jerk limit ramps acceleration up and back down
+/// again, so reaches a lower speed than acceleration alone.
+fn reachable_speed<T: Real>(v0: T, len: T, lim: &scurve::Lim<T>) -> T {
+    let two = T::lit(2.0);
+
+    // Shortest distance to change speed from `v0` to `v1`
+    let distance = |v1: T| {
+        let delta = v1 - v0;
+
+        let time = if delta * lim.jerk >= lim.acc.powi(2) {
+            delta / lim.acc + lim.acc / lim.jerk
+        } else {
+            two * (delta / lim.jerk).sqrt()
+        };
+
+        (v0 + v1) / two * time
+    };
+
+    // Acceleration limit only
+    let mut hi = (v0.powi(2) + two * lim.acc * len).sqrt().min(lim.vel);
+
+    if hi <= v0 || distance(hi) <= len {
+        return hi;
+    }
+
+    let mut lo = v0;
+
+    for _ in 0..32 {
+        let mid = (lo + hi) / two;
 
-    // Returns true if point belongs to an arc blend
-    pub fn tp(&self, t: f32) -> Option<(Out, bool)> {
-        if t > self.total_time || t < 0.0 {
+        if distance(mid) <= len {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+
+    lo
+}
+
+/// Blend the join between an arc or spline and a line, in either order, returning the shortened
+/// curve and line either side of the blend. The curve is cut back from the join by as much as the
+/// blend can round off, up to half of its length. `None` if the two can't be blended, e.g. when the
+/// line leaves the plane an arc turns in.
+fn curve_line_blend<const N: usize, T: Real>(
+    shape: BlendShape,
+    profile: LinearProfile<N, T>,
+    prev: &Item<N, T>,
+    next: &Item<N, T>,
+    max_deviation: T,
+) -> Option<(Item<N, T>, Item<N, T>, Item<N, T>)> {
+    let (curve, line, curve_first) = match (prev, next) {
+        (curve @ (Item::Arc(_) | Item::Spline(_)), line)
+        | (line, curve @ (Item::Arc(_) | Item::Spline(_)))
+            if line.linear_lim().is_some() =>
+        {
+            (curve, line, matches!(prev, Item::Arc(_) | Item::Spline(_)))
+        }
+        _ => return None,
+    };
+
+    let line_lim = line.linear_lim()?;
+
+    let (curve_len, curve_lim) = match curve {
+        Item::Arc(arc) => {
+            // Tangents to a helix never meet a line, so only an arc lying flat in its plane, and a
+            // line in the same plane, can be blended
+            let (a, b, _) = arc.plane.axes();
+
+            if (0..N).any(|axis| {
+                axis != a
+                    && axis != b
+                    && (arc.start[axis] != arc.end[axis] || line.q0()[axis] != line.q1()[axis])
+            }) {
+                return None;
+            }
+
+            (arc.len, arc.lim())
+        }
+        Item::Spline(spline) => (spline.len, spline.lim()),
+        _ => return None,
+    };
+
+    // The blend must respect the limits of both items either side of it
+    let lim = line_lim.inf(&curve_lim);
+
+    let two = T::lit(2.0);
+    let tolerance = T::default_epsilon().sqrt();
+
+    // Point the curve and line join at, and the direction along the line away from it
+    let (join, away) = if curve_first {
+        (line.q0(), line.q1() - line.q0())
+    } else {
+        (line.q1(), line.q0() - line.q1())
+    };
+
+    let line_len = away.norm();
+    let away = away.try_normalize(T::zero())?;
+
+    // Blend with the curve cut back by `cut`. The blend rounds off the corner between the line and
+    // the tangent to the curve at the cut, so it only joins the curve if it reaches all the way back
+    // to it.
+    let blend_at = |cut: T| {
+        let (point, tangent, _) =
+            curve.curve_geometry(if curve_first { curve_len - cut } else { cut });
+
+        // Direction from the corner towards the curve
+        let towards_curve = if curve_first { -tangent } else { tangent };
+
+        // Solve `point - towards_curve * setback = join + away * along` for the corner
+        let offset = point - join;
+        let cos = towards_curve.dot(&away);
+        let det = T::one() - cos.powi(2);
+
+        if det <= T::default_epsilon() {
             return None;
         }
 
-        // TODO: Filter by start time first. Calling `tp` on every segment until we get a `Some` is
-        // hilariously bad.
-        self.items.iter().find_map(|item| match item {
-            Item::Linear(line) => line.tp(t).map(|out| (out.0, false)),
-            Item::ArcBlend(blend) => blend.tp(t).map(|t| (t, true)),
-        })
+        let (curve_dot, line_dot) = (towards_curve.dot(&offset), away.dot(&offset));
+
+        let setback = (curve_dot - cos * line_dot) / det;
+        let along = (line_dot - cos * curve_dot) / det;
+
+        let corner = join + away * along;
+
+        // Tangent must cross the line in front of the curve, and the blend may only use the first
+        // half of the line
+        if setback <= T::zero()
+            || (corner + towards_curve * setback - point).norm() > setback * tolerance
+            || along + setback < T::zero()
+            || along + setback > line_len / two
+        {
+            return None;
+        }
+
+        let curve_side = corner + towards_curve * (setback * two);
+        let line_side = corner + away * (setback * two);
+
+        let blend = if curve_first {
+            Item::blend(
+                shape,
+                profile,
+                curve_side,
+                corner,
+                line_side,
+                max_deviation,
+                lim,
+            )?
+        } else {
+            Item::blend(
+                shape,
+                profile,
+                line_side,
+                corner,
+                curve_side,
+                max_deviation,
+                lim,
+            )?
+        };
+
+        let blend_curve_end = if curve_first { blend.q0() } else { blend.q1() };
+
+        ((blend_curve_end - point).norm() <= setback * tolerance).then_some(blend)
+    };
+
+    // Cut back as far as possible
+    let mut cut = curve_len / two;
+
+    if blend_at(cut).is_none() {
+        let mut lo = T::zero();
+
+        for _ in 0..32 {
+            let mid = (lo + cut) / two;
+
+            if blend_at(mid).is_some() {
+                lo = mid;
+            } else {
+                cut = mid;
+            }
+        }
+
+        // Right at the limit the blend falls short of the curve by the tolerance, so back off a
+        // little to leave the deviation limit out of it
+        cut = lo * T::lit(0.95);
+    }
+
+    let blend = blend_at(cut)?;
+
+    if curve_first {
+        let line = Item::linear(
+            profile,
+            blend.q1(),
+            line.q1(),
+            Coord::zeros(),
+            Coord::zeros(),
+            T::zero(),
+            &line_lim,
+        )
+        .ok()?;
+
+        Some((curve.trimmed_end(cut), blend, line))
+    } else {
+        Some((line.with_end(blend.q0()), blend, curve.trimmed_start(cut)))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::trapezoidal_non_zero_3d::Coord3;
+
+    /// Check every item starts when, where and at the velocity the one before it ends.
+    fn assert_continuous_joins(traj: &Trajectory) {
+        for (i, pair) in traj.items.windows(2).enumerate() {
+            let [prev, next] = pair else { unreachable!() };
+
+            let end_t = prev.start_t() + prev.duration();
+
+            assert!((next.start_t() - end_t).abs() < 1.0e-5, "{i}: {end_t}");
+
+            let end = prev.end();
+            let start = next.tp(next.start_t()).unwrap();
+
+            assert!(
+                (start.pos - end.pos).norm() < 1.0e-4,
+                "{i}: {end:?} {start:?}"
+            );
+            assert!(
+                (start.vel - end.vel).norm() < 1.0e-3,
+                "{i}: {end:?} {start:?}"
+            );
+        }
+    }
 
     #[test]
     fn three_points() {
@@ -206,10 +1329,565 @@ mod tests {
 
         let mut traj = Trajectory::new();
 
-        traj.push_point(p1);
-        traj.push_point(p2);
-        traj.push_point(p3);
+        traj.push_point(p1).unwrap();
+        traj.push_point(p2).unwrap();
+        traj.push_point(p3).unwrap();
 
         dbg!(traj);
     }
+
+    #[test]
+    fn exact_stop() {
+        let mut traj = Trajectory::new();
+
+        traj.path_mode = PathMode::ExactStop;
+
+        traj.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(5.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(10.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(10.0, 5.0, 0.0)).unwrap();
+
+        assert_eq!(traj.items.len(), 3);
+
+        for item in traj.items.iter() {
+            let Item::Linear(line) = item else {
+                panic!("Exact stop mode should not blend");
+            };
+
+            assert_eq!(line.v0(), Coord3::zeros());
+            assert_eq!(line.v1(), Coord3::zeros());
+        }
+    }
+
+    #[test]
+    fn invalid_point() {
+        let mut traj = Trajectory::new();
+
+        traj.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(5.0, 0.0, 0.0)).unwrap();
+
+        let total_time = traj.total_time;
+
+        assert_eq!(
+            traj.push_point(Coord3::new(5.0, 0.0, 0.0)).unwrap_err(),
+            PlanError::ZeroDisplacement
+        );
+        assert_eq!(
+            traj.push_point_with_limits(
+                Coord3::new(5.0, 5.0, 0.0),
+                Lim {
+                    vel: Coord3::new(2.0, 0.0, 2.0),
+                    acc: traj.limits.acc,
+                },
+            )
+            .unwrap_err(),
+            PlanError::InvalidLimit
+        );
+
+        // Nothing changed, so the line still stops at the last point
+        assert_eq!(traj.points.len(), 2);
+        assert_eq!(traj.items.len(), 1);
+        assert_eq!(traj.total_time, total_time);
+        assert_eq!(traj.items[0].q1(), Coord3::new(5.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn exact_path_colinear() {
+        let mut traj = Trajectory::new();
+
+        traj.path_mode = PathMode::ExactPath;
+
+        traj.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(5.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(10.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(10.0, 5.0, 0.0)).unwrap();
+
+        let [Item::Linear(first), Item::Linear(second), Item::Linear(third)] =
+            traj.items.as_slice()
+        else {
+            panic!("Exact path mode should not blend");
+        };
+
+        // Colinear point is passed through at speed
+        assert!(first.v1().x > 0.0);
+        assert_eq!(first.v1(), second.v0());
+
+        // Corner is not
+        assert_eq!(second.v1(), Coord3::zeros());
+        assert_eq!(third.v0(), Coord3::zeros());
+    }
+
+    #[test]
+    fn exact_path_short_corner() {
+        let mut traj = Trajectory::new();
+
+        traj.path_mode = PathMode::ExactPath;
+
+        // Tiny 45 degree corner
+        traj.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(0.01, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(0.02, 0.01, 0.0)).unwrap();
+
+        let [Item::Linear(first), Item::Linear(second)] = traj.items.as_slice() else {
+            panic!("Exact path mode should not blend");
+        };
+
+        assert_eq!(first.v1(), Coord3::zeros());
+        assert_eq!(second.v0(), Coord3::zeros());
+
+        // Long colinear segments which don't fall exactly on the line in floating point
+        let mut traj = Trajectory::new();
+
+        traj.path_mode = PathMode::ExactPath;
+
+        traj.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(100.1, 200.3, 300.7)).unwrap();
+        traj.push_point(Coord3::new(200.2, 400.6, 601.4)).unwrap();
+
+        let [Item::Linear(first), Item::Linear(_)] = traj.items.as_slice() else {
+            panic!("Exact path mode should not blend");
+        };
+
+        assert!(first.v1().norm() > 0.0);
+    }
+
+    #[test]
+    fn blend_tolerance() {
+        let mut traj = Trajectory::new();
+
+        traj.path_mode = PathMode::Blend(Some(0.1));
+
+        traj.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(5.0, 0.0, 0.0)).unwrap();
+
+        // Largest possible blend at this corner
+        traj.path_mode = PathMode::Blend(None);
+
+        traj.push_point(Coord3::new(5.0, 5.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(0.0, 5.0, 0.0)).unwrap();
+
+        let blends = traj
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::ArcBlend(blend) => Some(blend),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(blends.len(), 2);
+
+        // Blend deviation is the distance from the programmed corner to the arc midpoint
+        let deviation = |blend: &ArcBlend| (blend.mid - blend.arc_center).norm() - blend.arc_radius;
+
+        assert!(deviation(blends[0]) <= 0.1 + f32::EPSILON);
+        // Second blend is only limited by segment length
+        assert!(deviation(blends[1]) > 0.5);
+    }
+
+    #[test]
+    fn four_axes() {
+        let mut trajectory = Trajectory::<4>::new();
+
+        trajectory.push_point(Coord::zeros()).unwrap();
+        trajectory
+            .push_point(Coord::from([0.0, 0.0, 10.0, 0.0]))
+            .unwrap();
+        trajectory
+            .push_point(Coord::from([0.0, 0.0, 10.0, 10.0]))
+            .unwrap();
+
+        let Some(Item::ArcBlend(blend)) = trajectory.items.get(1) else {
+            panic!("Corner should be blended");
+        };
+
+        // Corner is in the Z/W plane. The arc must stay in it, at a constant distance from its
+        // center.
+        let mut t = blend.start_t;
+
+        while let Some(out) = blend.tp(t) {
+            assert_eq!(out.pos.xy(), Coord::<2>::zeros());
+            assert!(((out.pos - blend.arc_center).norm() - blend.arc_radius).abs() < 1.0e-4);
+
+            t += 0.01;
+        }
+
+        let Some(Item::Linear(last)) = trajectory.items.last() else {
+            panic!("Trajectory should end with a line");
+        };
+
+        assert_eq!(last.q1(), Coord::from([0.0, 0.0, 10.0, 10.0]));
+    }
+
+    #[test]
+    fn scurve_profile() {
+        let mut traj = Trajectory::new();
+
+        traj.profile = LinearProfile::SCurve {
+            jerk: Coord3::new(100.0, 100.0, 100.0),
+        };
+
+        traj.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(5.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(5.0, 5.0, 0.0)).unwrap();
+
+        // Corner is still blended, joining jerk limited lines
+        let [Item::SCurve(first), Item::ArcBlend(_), Item::SCurve(last)] = traj.items.as_slice()
+        else {
+            panic!("Lines should use S curve profile");
+        };
+
+        assert_eq!(first.q0(), Coord3::zeros());
+        assert_eq!(last.q1(), Coord3::new(5.0, 5.0, 0.0));
+
+        for item in traj.items.iter() {
+            let Item::SCurve(line) = item else {
+                continue;
+            };
+
+            assert!(line.is_feasible());
+
+            // Acceleration ramps up from zero instead of stepping to the limit
+            let start = line.tp(line.start_t).unwrap();
+
+            assert_eq!(start.acc, Coord3::zeros());
+        }
+
+        let end = traj.interpolate(0.001).last().unwrap();
+
+        assert_eq!(end.pos, Coord3::new(5.0, 5.0, 0.0));
+    }
+
+    #[test]
+    fn clothoid_blends() {
+        let mut traj = Trajectory::new();
+
+        let jerk = Coord3::new(100.0, 100.0, 100.0);
+
+        traj.profile = LinearProfile::SCurve { jerk };
+        traj.blend_shape = BlendShape::Clothoid;
+        traj.path_mode = PathMode::Blend(Some(0.2));
+
+        traj.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(5.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(5.0, 5.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(0.0, 5.0, 1.0)).unwrap();
+
+        assert_eq!(
+            traj.items
+                .iter()
+                .filter(|item| matches!(item, Item::ClothoidBlend(_)))
+                .count(),
+            2
+        );
+
+        let period = 0.001;
+
+        let mut samples = traj.interpolate(period);
+        let mut prev = samples.next().unwrap();
+
+        // Acceleration never steps, even going into and out of the corners
+        for out in samples {
+            assert!(
+                (out.acc - prev.acc).abs() <= jerk * period * 1.05,
+                "{} -> {}",
+                prev.acc,
+                out.acc
+            );
+
+            prev = out;
+        }
+
+        assert_eq!(prev.pos, Coord3::new(0.0, 5.0, 1.0));
+    }
+
+    #[test]
+    fn bezier_blends() {
+        let mut traj = Trajectory::new();
+
+        traj.blend_shape = BlendShape::Bezier;
+        traj.path_mode = PathMode::Blend(Some(0.2));
+
+        traj.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(5.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(5.0, 5.0, 0.0)).unwrap();
+
+        let [Item::Linear(first), Item::BezierBlend(blend), Item::Linear(last)] =
+            traj.items.as_slice()
+        else {
+            panic!("Corner should be blended with a Bezier curve");
+        };
+
+        assert_eq!(first.q1(), blend.control_points[0]);
+        assert_eq!(last.q0(), blend.control_points[5]);
+
+        let deviation = blend
+            .tp(blend.start_t + blend.time / 2.0)
+            .map(|out| (out.pos - Coord3::new(5.0, 0.0, 0.0)).norm())
+            .unwrap();
+
+        assert!(deviation <= 0.2 * 1.001, "{deviation}");
+
+        let period = 0.001;
+
+        let mut samples = traj.interpolate(period);
+        let mut prev = samples.next().unwrap();
+
+        // Position and velocity are continuous
+        for out in samples {
+            assert!((out.pos - prev.pos).norm() <= 5.0 * period * 1.01);
+            assert!((out.vel - prev.vel).norm() <= 10.0 * period * 1.5 + 1.0e-3);
+
+            prev = out;
+        }
+
+        assert_eq!(prev.pos, Coord3::new(5.0, 5.0, 0.0));
+    }
+
+    #[test]
+    fn programmed_arcs() {
+        let mut traj = Trajectory::new();
+
+        traj.path_mode = PathMode::Blend(Some(0.1));
+
+        traj.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(2.0, 0.0, 0.0)).unwrap();
+
+        // Half circle below (3, 0), turning a right angle from the line
+        assert!(traj.push_arc(
+            Coord3::new(4.0, 0.0, 0.0),
+            ArcCenter::Point(Coord3::new(3.0, 0.0, 0.0)),
+            Plane::Xy,
+            Direction::Counterclockwise,
+            0,
+        ));
+
+        // Carries straight on from the end of the arc
+        traj.push_point(Coord3::new(4.0, 2.0, 0.0)).unwrap();
+
+        let [Item::Linear(first), Item::ArcBlend(blend), Item::Arc(arc), Item::Linear(last)] =
+            traj.items.as_slice()
+        else {
+            panic!("Line should be blended into the arc");
+        };
+
+        assert_eq!(first.q1(), blend.arc_start);
+        assert!((blend.arc_end - arc.start).norm() < 1.0e-5);
+        assert_eq!(last.q0(), arc.end);
+
+        // No stop at the smooth join between the arc and the last line
+        assert!(arc.v1 > 0.0);
+        assert_eq!(arc.v1, arc.max_speed());
+
+        assert_continuous_joins(&traj);
+
+        let period = 0.001;
+
+        let mut samples = traj.interpolate(period);
+        let mut prev = samples.next().unwrap();
+
+        // Position and velocity are continuous
+        for out in samples {
+            assert!((out.pos - prev.pos).norm() <= 5.0 * period * 1.01);
+            assert!((out.vel - prev.vel).norm() <= 10.0 * period * 1.5 + 1.0e-3);
+
+            assert!(out.vel.abs() <= traj.limits.vel * 1.001, "{}", out.vel);
+            assert!(out.acc.abs() <= traj.limits.acc * 1.001, "{}", out.acc);
+
+            prev = out;
+        }
+
+        assert_eq!(prev.pos, Coord3::new(4.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn splines() {
+        let mut traj = Trajectory::new();
+
+        traj.path_mode = PathMode::Blend(Some(0.1));
+
+        traj.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(2.0, 0.0, 0.0)).unwrap();
+
+        // Cubic leaving at 45 degrees to the line
+        assert!(traj.push_spline(
+            3,
+            &[
+                Coord3::new(3.0, 1.0, 0.0),
+                Coord3::new(4.0, -1.0, 0.0),
+                Coord3::new(5.0, 0.0, 0.0),
+            ],
+            None,
+            None,
+        ));
+
+        // Carries straight on from the end of the spline
+        traj.push_point(Coord3::new(6.0, 1.0, 0.0)).unwrap();
+
+        let [Item::Linear(first), Item::ArcBlend(blend), Item::Spline(spline), Item::Linear(last)] =
+            traj.items.as_slice()
+        else {
+            panic!("Line should be blended into the spline");
+        };
+
+        assert_eq!(first.q1(), blend.arc_start);
+        assert!((blend.arc_end - spline.start).norm() < 1.0e-5);
+        assert_eq!(last.q0(), spline.end);
+        assert!(spline.start_param > 0.0);
+
+        // No stop at the smooth join between the spline and the last line
+        assert!(spline.v1 > 0.0);
+
+        assert_continuous_joins(&traj);
+
+        let period = 0.001;
+
+        let mut samples = traj.interpolate(period);
+        let mut prev = samples.next().unwrap();
+
+        // Position and velocity are continuous
+        for out in samples {
+            assert!((out.pos - prev.pos).norm() <= 5.0 * period * 1.01);
+            assert!((out.vel - prev.vel).norm() <= 10.0 * period * 1.5 + 1.0e-3);
+
+            assert!(out.vel.abs() <= traj.limits.vel * 1.001, "{}", out.vel);
+            assert!(out.acc.abs() <= traj.limits.acc * 1.01, "{}", out.acc);
+
+            prev = out;
+        }
+
+        assert_eq!(prev.pos, Coord3::new(6.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn tangent_curve_joins() {
+        for spline in [false, true] {
+            let mut traj = Trajectory::new();
+
+            traj.path_mode = PathMode::ExactPath;
+
+            traj.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+            traj.push_point(Coord3::new(2.0, 0.0, 0.0)).unwrap();
+
+            // Quarter turn to the left, leaving and joining the lines at a tangent
+            let pushed = if spline {
+                traj.push_spline(
+                    3,
+                    &[
+                        Coord3::new(3.0, 0.0, 0.0),
+                        Coord3::new(4.0, 1.0, 0.0),
+                        Coord3::new(4.0, 2.0, 0.0),
+                    ],
+                    None,
+                    None,
+                )
+            } else {
+                traj.push_arc(
+                    Coord3::new(3.0, 1.0, 0.0),
+                    ArcCenter::Point(Coord3::new(2.0, 1.0, 0.0)),
+                    Plane::Xy,
+                    Direction::Counterclockwise,
+                    0,
+                )
+            };
+
+            assert!(pushed);
+
+            let end = traj.points.last().copied().unwrap() + Coord3::new(0.0, 2.0, 0.0);
+
+            traj.push_point(end).unwrap();
+
+            assert_eq!(traj.items.len(), 3);
+            assert!(traj.items.iter().all(|item| item.as_blend().is_none()));
+
+            // Nothing stops at the joins, so they're passed through at speed
+            assert!(traj.items[0].end().vel.norm() > 0.0, "{spline}");
+            assert!(traj.items[1].end().vel.norm() > 0.0, "{spline}");
+
+            assert_continuous_joins(&traj);
+        }
+    }
+
+    #[test]
+    fn lookahead_short_moves() {
+        let mut traj = Trajectory::new();
+
+        traj.path_mode = PathMode::ExactPath;
+
+        // Each move is far too short to reach feed rate on its own
+        for i in 0..=50 {
+            traj.push_point(Coord3::new(i as f32 * 0.2, 0.0, 0.0))
+                .unwrap();
+        }
+
+        let period = 0.001;
+        let samples = traj.interpolate(period).collect::<Vec<_>>();
+
+        let top_speed = samples.iter().map(|out| out.vel.x).fold(0.0f32, f32::max);
+
+        assert!(top_speed > 4.99, "{top_speed}");
+
+        // Only stops at the end
+        for (i, pair) in samples.windows(2).enumerate().skip(1) {
+            let [prev, out] = pair else { unreachable!() };
+
+            assert!(out.vel.x > 0.0 || i == samples.len() - 2, "{i}: {out:?}");
+            assert!((out.pos - prev.pos).norm() <= 5.0 * period * 1.01, "{i}");
+        }
+
+        assert_eq!(samples.last().unwrap().pos, Coord3::new(10.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn lookahead_blends() {
+        let mut traj = Trajectory::new();
+
+        traj.path_mode = PathMode::Blend(None);
+
+        traj.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(1.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(2.0, 0.5, 0.0)).unwrap();
+        traj.push_point(Coord3::new(3.0, 0.0, 0.0)).unwrap();
+
+        // Exact path mode from here, so the next corner stops
+        traj.path_mode = PathMode::ExactPath;
+
+        traj.push_point(Coord3::new(4.0, 0.5, 0.0)).unwrap();
+        traj.push_point(Coord3::new(4.0, 2.0, 0.0)).unwrap();
+
+        // Speed is continuous from one item to the next
+        for pair in traj.items.windows(2) {
+            let [prev, next] = pair else { unreachable!() };
+
+            let next_speed = match next {
+                Item::Linear(line) => line.v0().norm(),
+                Item::ArcBlend(blend) => blend.v0,
+                Item::ClothoidBlend(blend) => blend.v0,
+                Item::BezierBlend(blend) => blend.v0,
+                Item::SCurve(line) => line.v0().norm(),
+                Item::Arc(arc) => arc.v0,
+                Item::Spline(spline) => spline.v0,
+            };
+
+            assert!((prev.end_speed() - next_speed).abs() < 1.0e-4);
+
+            // Blends are traversed at speed
+            if let Item::ArcBlend(blend) = next {
+                assert!(blend.v0 > 0.0 && blend.v1 > 0.0);
+                assert!(blend.v0.max(blend.v1) <= blend.max_speed());
+            }
+
+            assert_eq!(prev.end_t(), next.start_t());
+        }
+
+        // Stops at the unblended corner
+        let Some(Item::Linear(corner)) = traj.items.iter().rev().nth(1) else {
+            panic!("Exact path corner should not be blended");
+        };
+
+        assert_eq!(corner.q1(), Coord3::new(4.0, 0.5, 0.0));
+        assert_eq!(corner.v1(), Coord3::zeros());
+
+        assert_eq!(traj.total_time, traj.items.last().unwrap().end_t());
+    }
 }
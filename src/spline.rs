@@ -0,0 +1,738 @@
+//! Spline curves, as from `G5`, `G5.1` and `G6.2` moves.
+//!
+//! A spline is a NURBS curve of up to degree [`MAX_DEGREE`] with clamped knots, so it starts at its
+//! first control point and ends at its last. Cubic and quadratic B-splines are the special case of
+//! all weights being one.
+//!
+//! Splines aren't parameterised by arc length, so like a
+//! [`BezierBlend`](crate::bezier_blend::BezierBlend) a table of lengths along the curve is built
+//! once and refined with Newton's method. Speed along the curve follows a trapezoidal profile,
+//! limited like an [`Arc`](crate::arc::Arc) by the direction and curvature of each axis at samples
+//! along the curve.
+
+use alloc::vec::Vec;
+
+use crate::{
+    bezier_blend::GAUSS_LEGENDRE,
+    kinematics::{self, Kinematics, LimitError},
+    profile::Profile,
+    real::Real,
+    trapezoidal_non_zero::{self, Segment},
+    trapezoidal_non_zero_3d::{Coord, Lim, Out},
+};
+
+pub use crate::trapezoidal_non_zero_3d::Coord3;
+
+/// Highest supported degree.
+pub const MAX_DEGREE: usize = 5;
+
+/// Number of intervals in the arc length table for each knot span.
+const TABLE_PER_SPAN: usize = 16;
+
+/// Number of samples per arc length table interval when finding the limits of a spline.
+const LIMIT_SAMPLES: usize = 4;
+
+#[derive(Debug, Clone)]
+pub struct Spline<const N: usize = 3, T: Real = f32> {
+    pub control_points: Vec<Coord<N, T>>,
+    /// Weight of each control point. All ones for a B-spline.
+    pub weights: Vec<T>,
+    /// Knot vector, with `degree + 1` more knots than control points.
+    pub knots: Vec<T>,
+    pub degree: usize,
+    /// Curve parameter at the start of the path. Trimming the spline moves this and `end_param`
+    /// instead of changing the control points.
+    pub start_param: T,
+    /// Curve parameter at the end of the path.
+    pub end_param: T,
+    pub start: Coord<N, T>,
+    pub end: Coord<N, T>,
+    /// Length of the path.
+    pub len: T,
+    /// Fastest speed along the path.
+    pub velocity_limit: T,
+    /// Fastest acceleration or deceleration along the path.
+    pub acceleration_limit: T,
+    lim: Lim<N, T>,
+    /// Curve parameter and distance along the path at each entry of the arc length table.
+    lengths: Vec<(T, T)>,
+    /// Distance along the path over time.
+    profile: Segment<T>,
+    /// Speed along the path at its start.
+    pub v0: T,
+    /// Speed along the path at its end.
+    pub v1: T,
+    pub time: T,
+    pub start_t: T,
+}
+
+impl<const N: usize, T: Real> Spline<N, T> {
+    /// NURBS curve through `control_points`, with a `weight` for each. The spline starts and
+    /// finishes at rest at time zero until replanned with [`set_speeds`](Self::set_speeds).
+    ///
+    /// Returns `None` if the degree isn't between 1 and [`MAX_DEGREE`], the number of weights or
+    /// knots is wrong, a weight isn't positive, the knots decrease or aren't clamped, or the curve
+    /// stops moving anywhere along it.
+    pub fn new(
+        control_points: Vec<Coord<N, T>>,
+        weights: Vec<T>,
+        knots: Vec<T>,
+        degree: usize,
+        lim: &Lim<N, T>,
+    ) -> Option<Self> {
+        let count = control_points.len();
+
+        if !(1..=MAX_DEGREE).contains(&degree)
+            || count <= degree
+            || weights.len() != count
+            || knots.len() != count + degree + 1
+            || weights.iter().any(|weight| *weight <= T::zero())
+            || knots.windows(2).any(|pair| pair[1] < pair[0])
+        {
+            return None;
+        }
+
+        let (start_param, end_param) = (knots[0], knots[count + degree]);
+
+        // Clamped knots make the curve start and end at the first and last control points
+        if start_param >= end_param
+            || knots[..=degree].iter().any(|knot| *knot != start_param)
+            || knots[count..].iter().any(|knot| *knot != end_param)
+        {
+            return None;
+        }
+
+        let mut spline = Self {
+            start: control_points[0],
+            end: control_points[count - 1],
+            control_points,
+            weights,
+            knots,
+            degree,
+            start_param,
+            end_param,
+            len: T::zero(),
+            velocity_limit: T::zero(),
+            acceleration_limit: T::zero(),
+            lim: *lim,
+            lengths: Vec::new(),
+            profile: Segment::default(),
+            v0: T::zero(),
+            v1: T::zero(),
+            time: T::zero(),
+            start_t: T::zero(),
+        };
+
+        spline.measure();
+
+        if !spline.find_limits() {
+            return None;
+        }
+
+        spline.set_speeds(T::zero(), T::zero());
+
+        Some(spline)
+    }
+
+    /// B-spline through `control_points` with unit weights and evenly spaced clamped knots. A
+    /// cubic B-spline with four control points is a cubic Bezier curve.
+    pub fn b_spline(
+        control_points: Vec<Coord<N, T>>,
+        degree: usize,
+        lim: &Lim<N, T>,
+    ) -> Option<Self> {
+        let weights = control_points.iter().map(|_| T::one()).collect();
+        let knots = clamped_knots(control_points.len(), degree);
+
+        Self::new(control_points, weights, knots, degree, lim)
+    }
+
+    /// Index of the knot span containing parameter `u`, so `knots[span] <= u < knots[span + 1]`
+    /// except at the very end of the curve.
+    fn span(&self, u: T) -> usize {
+        self.knots
+            .partition_point(|knot| *knot <= u)
+            .saturating_sub(1)
+            .clamp(self.degree, self.control_points.len() - 1)
+    }
+
+    /// Values and first and second derivatives of the `degree + 1` basis functions that are
+    /// non-zero in `span` at parameter `u`, from "The NURBS Book" algorithm A2.3.
+    fn basis(&self, span: usize, u: T) -> [[T; MAX_DEGREE + 1]; 3] {
+        let p = self.degree;
+        let knots = &self.knots;
+
+        let mut ndu = [[T::zero(); MAX_DEGREE + 1]; MAX_DEGREE + 1];
+        let mut left = [T::zero(); MAX_DEGREE + 1];
+        let mut right = [T::zero(); MAX_DEGREE + 1];
+
+        ndu[0][0] = T::one();
+
+        for j in 1..=p {
+            left[j] = u - knots[span + 1 - j];
+            right[j] = knots[span + j] - u;
+
+            let mut saved = T::zero();
+
+            for r in 0..j {
+                // Knot differences in the lower triangle, basis functions in the upper
+                ndu[j][r] = right[r + 1] + left[j - r];
+
+                let temp = ndu[r][j - 1] / ndu[j][r];
+
+                ndu[r][j] = saved + right[r + 1] * temp;
+                saved = left[j - r] * temp;
+            }
+
+            ndu[j][j] = saved;
+        }
+
+        let mut ders = [[T::zero(); MAX_DEGREE + 1]; 3];
+
+        for j in 0..=p {
+            ders[0][j] = ndu[j][p];
+        }
+
+        let max_order = p.min(2);
+
+        for r in 0..=p {
+            // Two rows of coefficients, alternating between the current and previous order
+            let mut a = [[T::zero(); MAX_DEGREE + 1]; 2];
+            let (mut s1, mut s2) = (0, 1);
+
+            a[0][0] = T::one();
+
+            for k in 1..=max_order {
+                let mut d = T::zero();
+                let pk = p - k;
+
+                if r >= k {
+                    let rk = r - k;
+
+                    a[s2][0] = a[s1][0] / ndu[pk + 1][rk];
+                    d = a[s2][0] * ndu[rk][pk];
+                }
+
+                let j1 = if r + 1 >= k { 1 } else { k - r };
+                let j2 = if r < pk + 2 { k - 1 } else { p - r };
+
+                for j in j1..=j2 {
+                    let rkj = r + j - k;
+
+                    a[s2][j] = (a[s1][j] - a[s1][j - 1]) / ndu[pk + 1][rkj];
+                    d += a[s2][j] * ndu[rkj][pk];
+                }
+
+                if r <= pk {
+                    a[s2][k] = -a[s1][k - 1] / ndu[pk + 1][r];
+                    d += a[s2][k] * ndu[r][pk];
+                }
+
+                ders[k][r] = d;
+
+                core::mem::swap(&mut s1, &mut s2);
+            }
+        }
+
+        // Derivative of order `k` is scaled by `p! / (p - k)!`
+        let mut factor = T::lit(p as f64);
+
+        for (k, row) in ders.iter_mut().enumerate().skip(1).take(max_order) {
+            for value in row.iter_mut().take(p + 1) {
+                *value *= factor;
+            }
+
+            factor *= T::lit((p - k) as f64);
+        }
+
+        ders
+    }
+
+    /// Position and its first and second derivatives with respect to the curve parameter `u`.
+    fn derivatives(&self, u: T) -> (Coord<N, T>, Coord<N, T>, Coord<N, T>) {
+        let p = self.degree;
+        let span = self.span(u);
+        let ders = self.basis(span, u);
+
+        // Weighted sum of the control points, and the sum of the weights, and their derivatives
+        let mut points = [Coord::<N, T>::zeros(); 3];
+        let mut weights = [T::zero(); 3];
+
+        for (k, basis) in ders.iter().enumerate() {
+            for (j, basis) in basis.iter().take(p + 1).enumerate() {
+                let i = span - p + j;
+                let weighted = *basis * self.weights[i];
+
+                points[k] += self.control_points[i] * weighted;
+                weights[k] += weighted;
+            }
+        }
+
+        // Quotient rule for the rational curve
+        let pos = points[0] / weights[0];
+        let d1 = (points[1] - pos * weights[1]) / weights[0];
+        let d2 = (points[2] - d1 * (T::lit(2.0) * weights[1]) - pos * weights[2]) / weights[0];
+
+        (pos, d1, d2)
+    }
+
+    /// Position, unit tangent and curvature vector at curve parameter `u`.
+    fn frame(&self, u: T) -> (Coord<N, T>, Coord<N, T>, Coord<N, T>) {
+        let (pos, d1, d2) = self.derivatives(u);
+
+        let speed_squared = d1.norm_squared();
+
+        // Remove the component along the tangent, which only changes the parameterisation
+        let curvature = (d2 - d1 * (d1.dot(&d2) / speed_squared)) / speed_squared;
+
+        (pos, d1 / speed_squared.sqrt(), curvature)
+    }
+
+    /// Length of the curve between parameters `u0` and `u1`.
+    fn integrate_speed(&self, u0: T, u1: T) -> T {
+        let half = (u1 - u0) / T::lit(2.0);
+        let center = (u0 + u1) / T::lit(2.0);
+
+        GAUSS_LEGENDRE
+            .iter()
+            .fold(T::zero(), |sum, (node, weight)| {
+                sum + T::lit(*weight) * self.derivatives(center + half * T::lit(*node)).1.norm()
+            })
+            * half
+    }
+
+    /// Rebuild the arc length table between the start and end parameters, with the same number of
+    /// intervals in each knot span so the table follows the curve's own spacing.
+    fn measure(&mut self) {
+        let mut breaks = Vec::new();
+
+        breaks.push(self.start_param);
+
+        breaks.extend(
+            self.knots
+                .iter()
+                .copied()
+                .filter(|knot| *knot > self.start_param && *knot < self.end_param),
+        );
+
+        breaks.push(self.end_param);
+        breaks.dedup();
+
+        let intervals = T::lit(TABLE_PER_SPAN as f64);
+
+        self.lengths.clear();
+        self.lengths.push((self.start_param, T::zero()));
+
+        let mut len = T::zero();
+
+        for pair in breaks.windows(2) {
+            let step = (pair[1] - pair[0]) / intervals;
+
+            for i in 0..TABLE_PER_SPAN {
+                let u0 = pair[0] + step * T::lit(i as f64);
+                let u1 = if i + 1 == TABLE_PER_SPAN {
+                    pair[1]
+                } else {
+                    u0 + step
+                };
+
+                len += self.integrate_speed(u0, u1);
+
+                self.lengths.push((u1, len));
+            }
+        }
+
+        self.len = len;
+        self.start = self.derivatives(self.start_param).0;
+        self.end = self.derivatives(self.end_param).0;
+    }
+
+    /// Curve parameter at distance `s` along the path. The table gives a first guess which is
+    /// refined with Newton's method, staying within the table interval so it can't diverge.
+    pub fn param(&self, s: T) -> T {
+        let s = s.max(T::zero()).min(self.len);
+
+        let i = self
+            .lengths
+            .partition_point(|(_, len)| *len <= s)
+            .clamp(1, self.lengths.len() - 1)
+            - 1;
+
+        let ((u0, len0), (u1, len1)) = (self.lengths[i], self.lengths[i + 1]);
+
+        let mut u = if len1 > len0 {
+            u0 + (u1 - u0) * (s - len0) / (len1 - len0)
+        } else {
+            u0
+        };
+
+        let tolerance = T::default_epsilon() * T::lit(16.0) * (len1 - len0);
+
+        for _ in 0..8 {
+            let error = len0 + self.integrate_speed(u0, u) - s;
+
+            let speed = self.derivatives(u).1.norm();
+
+            if error.abs() <= tolerance || speed <= T::zero() {
+                break;
+            }
+
+            u = (u - error / speed).max(u0).min(u1);
+        }
+
+        u
+    }
+
+    /// Find the fastest speed and acceleration along the path that keep every axis within its
+    /// limits, leaving half the acceleration of each curving axis for going round the curve.
+    /// Returns `false` if the curve stops moving at any of the samples.
+    fn find_limits(&mut self) -> bool {
+        let two = T::lit(2.0);
+
+        let mut max_tangent = Coord::<N, T>::zeros();
+        let mut max_curvature = Coord::<N, T>::zeros();
+
+        let samples = T::lit(LIMIT_SAMPLES as f64);
+
+        for pair in self.lengths.windows(2) {
+            let (u0, u1) = (pair[0].0, pair[1].0);
+
+            for i in 0..=LIMIT_SAMPLES {
+                let u = u0 + (u1 - u0) * T::lit(i as f64) / samples;
+
+                if self.derivatives(u).1.norm_squared() <= T::zero() {
+                    return false;
+                }
+
+                let (_, tangent, curvature) = self.frame(u);
+
+                max_tangent = max_tangent.sup(&tangent.abs());
+                max_curvature = max_curvature.sup(&curvature.abs());
+            }
+        }
+
+        self.velocity_limit = T::lit(f64::INFINITY);
+        self.acceleration_limit = T::lit(f64::INFINITY);
+
+        for axis in 0..N {
+            let turning = max_curvature[axis] > T::zero();
+
+            let share = if turning { two } else { T::one() };
+
+            if max_tangent[axis] > T::zero() {
+                self.velocity_limit = self
+                    .velocity_limit
+                    .min(self.lim.vel[axis] / max_tangent[axis]);
+                self.acceleration_limit = self
+                    .acceleration_limit
+                    .min(self.lim.acc[axis] / (share * max_tangent[axis]));
+            }
+
+            // Centripetal acceleration `v^2 * curvature` gets the other half
+            if turning {
+                self.velocity_limit = self
+                    .velocity_limit
+                    .min((self.lim.acc[axis] / (two * max_curvature[axis])).sqrt());
+            }
+        }
+
+        true
+    }
+
+    pub fn lim(&self) -> Lim<N, T> {
+        self.lim
+    }
+
+    /// Fastest speed anywhere along the spline.
+    pub fn max_speed(&self) -> T {
+        self.velocity_limit
+    }
+
+    /// Distances along the path of the knots inside it, where the curvature of splines below
+    /// degree 3 can change suddenly.
+    pub fn knot_distances(&self) -> Vec<T> {
+        self.lengths
+            .iter()
+            .skip(TABLE_PER_SPAN)
+            .step_by(TABLE_PER_SPAN)
+            .map(|(_, len)| *len)
+            .take_while(|len| *len < self.len)
+            .collect()
+    }
+
+    /// Change speed from `v0` at the start of the spline to `v1` at its end, as fast as the limits
+    /// allow in between, updating its duration. The two speeds must be reachable from each other
+    /// within the length of the spline.
+    pub fn set_speeds(&mut self, v0: T, v1: T) {
+        self.v0 = v0;
+        self.v1 = v1;
+
+        self.profile = Segment::new(
+            T::zero(),
+            self.len,
+            v0,
+            v1,
+            &trapezoidal_non_zero::Lim {
+                vel: self.velocity_limit,
+                acc: self.acceleration_limit,
+            },
+        );
+        self.time = self.profile.t;
+    }
+
+    /// Position, unit tangent and curvature vector at distance `s` along the path.
+    pub fn geometry(&self, s: T) -> (Coord<N, T>, Coord<N, T>, Coord<N, T>) {
+        self.frame(self.param(s))
+    }
+
+    /// Cut `len` off the start of the path, keeping its speeds and limits.
+    pub fn trim_start(&mut self, len: T) {
+        self.start_param = self.param(len);
+
+        self.measure();
+        self.set_speeds(self.v0, self.v1);
+    }
+
+    /// Cut `len` off the end of the path, keeping its speeds and limits.
+    pub fn trim_end(&mut self, len: T) {
+        self.end_param = self.param(self.len - len);
+
+        self.measure();
+        self.set_speeds(self.v0, self.v1);
+    }
+
+    /// Get trajectory parameters at the given time `t`.
+    pub fn tp(&self, t: T) -> Option<Out<N, T>> {
+        let t = t - self.start_t;
+
+        if t >= self.time || t < T::zero() {
+            return None;
+        }
+
+        let path = self.profile.tp(t)?;
+
+        let (pos, tangent, curvature) = self.geometry(path.pos);
+
+        Some(Out {
+            pos,
+            vel: tangent * path.vel,
+            acc: tangent * path.acc + curvature * path.vel.powi(2),
+        })
+    }
+
+    /// Check this spline against the joint limits of the given kinematics, sampling every `dt`
+    /// seconds.
+    pub fn check_joint_limits<K: Kinematics<N, T> + ?Sized>(
+        &self,
+        kinematics: &K,
+        dt: T,
+    ) -> Result<(), LimitError<N, T>> {
+        kinematics::check_limits(
+            kinematics,
+            self.start_t,
+            self.start_t + self.time,
+            dt,
+            |t| self.tp(t),
+        )
+    }
+}
+
+/// Knot vector for `count` control points of a spline of the given degree, clamped at both ends
+/// and evenly spaced between zero and one in the middle.
+pub fn clamped_knots<T: Real>(count: usize, degree: usize) -> Vec<T> {
+    let spans = count.saturating_sub(degree).max(1);
+
+    (0..count + degree + 1)
+        .map(|i| {
+            let knot = i.saturating_sub(degree).min(spans);
+
+            T::lit(knot as f64) / T::lit(spans as f64)
+        })
+        .collect()
+}
+
+impl<const N: usize, T: Real> Profile<T> for Spline<N, T> {
+    type Out = Out<N, T>;
+
+    fn start_t(&self) -> T {
+        self.start_t
+    }
+
+    fn duration(&self) -> T {
+        self.time
+    }
+
+    fn tp(&self, t: T) -> Option<Out<N, T>> {
+        Spline::tp(self, t)
+    }
+
+    fn phase_times(&self) -> Vec<T> {
+        // The speed profile starts at zero
+        self.profile
+            .phase_times()
+            .into_iter()
+            .map(|t| self.start_t + t)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_cmp::assert_approx_eq;
+
+    fn lim() -> Lim {
+        Lim {
+            vel: Coord3::new(2.0, 2.0, 2.0),
+            acc: Coord3::new(5.0, 5.0, 5.0),
+        }
+    }
+
+    #[test]
+    fn knots() {
+        assert_eq!(
+            clamped_knots::<f32>(4, 3),
+            vec![0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0]
+        );
+        assert_eq!(
+            clamped_knots::<f32>(5, 2),
+            vec![0.0, 0.0, 0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0, 1.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn rational_quarter_circle() {
+        let weight = core::f64::consts::FRAC_1_SQRT_2;
+
+        // Exact quarter circle of radius 2 from a rational quadratic
+        let spline = Spline::new(
+            vec![
+                Coord::<3, f64>::new(2.0, 0.0, 0.0),
+                Coord::<3, f64>::new(2.0, 2.0, 0.0),
+                Coord::<3, f64>::new(0.0, 2.0, 0.0),
+            ],
+            vec![1.0, weight, 1.0],
+            vec![0.0, 0.0, 0.0, 1.0, 1.0, 1.0],
+            2,
+            &Lim {
+                vel: Coord::<3, f64>::repeat(2.0),
+                acc: Coord::<3, f64>::repeat(5.0),
+            },
+        )
+        .unwrap();
+
+        assert_approx_eq!(f64, spline.len, core::f64::consts::PI, epsilon = 1.0e-9);
+
+        for i in 0..=10 {
+            let (pos, tangent, curvature) = spline.geometry(spline.len * i as f64 / 10.0);
+
+            assert_approx_eq!(f64, pos.norm(), 2.0, epsilon = 1.0e-9);
+            assert_approx_eq!(f64, tangent.dot(&pos), 0.0, epsilon = 1.0e-9);
+            assert_approx_eq!(f64, curvature.norm(), 0.5, epsilon = 1.0e-9);
+        }
+
+        // Centripetal acceleration of either axis can use half of its limit
+        assert_approx_eq!(
+            f64,
+            spline.velocity_limit,
+            (2.5f64 * 2.0).sqrt().min(2.0),
+            epsilon = 1.0e-9
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let points = vec![
+            Coord3::new(0.0, 0.0, 0.0),
+            Coord3::new(1.0, 1.0, 0.0),
+            Coord3::new(2.0, 0.0, 0.0),
+        ];
+
+        // Degree too high for the number of points
+        assert!(Spline::b_spline(points.clone(), 3, &lim()).is_none());
+
+        // Unclamped knots
+        assert!(Spline::new(
+            points.clone(),
+            vec![1.0; 3],
+            vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0],
+            2,
+            &lim()
+        )
+        .is_none());
+
+        // Zero weight
+        assert!(Spline::new(
+            points,
+            vec![1.0, 0.0, 1.0],
+            vec![0.0, 0.0, 0.0, 1.0, 1.0, 1.0],
+            2,
+            &lim()
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn follow() {
+        let mut spline = Spline::b_spline(
+            vec![
+                Coord3::new(0.0, 0.0, 0.0),
+                Coord3::new(1.0, 2.0, 0.0),
+                Coord3::new(3.0, -2.0, 0.5),
+                Coord3::new(4.0, 1.0, 1.0),
+                Coord3::new(6.0, 0.0, 1.0),
+            ],
+            3,
+            &lim(),
+        )
+        .unwrap();
+
+        spline.start_t = 1.0;
+        spline.set_speeds(0.0, 0.0);
+
+        let dt = 0.001;
+
+        let mut prev = spline.tp(1.0).unwrap();
+        let mut t = 1.0 + dt;
+
+        assert_eq!(prev.pos, Coord3::zeros());
+
+        while let Some(out) = spline.tp(t) {
+            // Derivatives match the motion
+            assert!(((out.pos - prev.pos) / dt - (out.vel + prev.vel) / 2.0).norm() < 1.0e-2);
+
+            assert!(out.vel.abs() <= lim().vel * 1.001, "{t}: {}", out.vel);
+            assert!(out.acc.abs() <= lim().acc * 1.01, "{t}: {}", out.acc);
+
+            prev = out;
+            t += dt;
+        }
+
+        assert!((prev.pos - Coord3::new(6.0, 0.0, 1.0)).norm() < 1.0e-3);
+        assert!(prev.vel.norm() < 1.0e-2);
+    }
+
+    #[test]
+    fn trim() {
+        let mut spline = Spline::b_spline(
+            vec![
+                Coord3::new(0.0, 0.0, 0.0),
+                Coord3::new(1.0, 2.0, 0.0),
+                Coord3::new(3.0, 2.0, 0.0),
+                Coord3::new(4.0, 0.0, 0.0),
+            ],
+            3,
+            &lim(),
+        )
+        .unwrap();
+
+        let len = spline.len;
+        let middle = spline.geometry(len / 2.0).0;
+
+        spline.trim_start(1.0);
+        spline.trim_end(0.5);
+
+        assert_approx_eq!(f32, spline.len, len - 1.5, epsilon = 1.0e-4);
+        assert!((spline.geometry(len / 2.0 - 1.0).0 - middle).norm() < 1.0e-4);
+    }
+}
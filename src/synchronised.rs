@@ -1,23 +1,49 @@
 //! A single segment with synchronised axes.
 
-use nalgebra::Vector3;
+use crate::{
+    error::PlanError,
+    profile::{self, Profile},
+    real::Real,
+    trapezoidal_non_zero_3d,
+};
+use alloc::vec::Vec;
+
+pub use crate::trapezoidal_non_zero_3d::{Coord, Coord3};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lim<const N: usize = 3, T: Real = f32> {
+    pub vel: Coord<N, T>,
+    pub acc: Coord<N, T>,
+}
 
-pub type Coord3 = Vector3<f32>;
+// `Default` can't be derived for any `N` as nalgebra requires `[T; N]: Default`.
+impl<const N: usize, T: Real> Default for Lim<N, T> {
+    fn default() -> Self {
+        Self {
+            vel: Coord::zeros(),
+            acc: Coord::zeros(),
+        }
+    }
+}
 
-#[derive(Default, Debug, Clone, Copy, PartialEq)]
-pub struct Lim {
-    pub vel: Coord3,
-    pub acc: Coord3,
+#[derive(Debug, Clone, Copy)]
+pub struct Out<const N: usize = 3, T: Real = f32> {
+    pub pos: Coord<N, T>,
+    pub vel: Coord<N, T>,
+    pub acc: Coord<N, T>,
 }
 
-#[derive(Debug, Default, Clone, Copy)]
-pub struct Out {
-    pub pos: Coord3,
-    pub vel: Coord3,
-    pub acc: Coord3,
+impl<const N: usize, T: Real> Default for Out<N, T> {
+    fn default() -> Self {
+        Self {
+            pos: Coord::zeros(),
+            vel: Coord::zeros(),
+            acc: Coord::zeros(),
+        }
+    }
 }
 
-impl core::ops::Add for Out {
+impl<const N: usize, T: Real> core::ops::Add for Out<N, T> {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self::Output {
@@ -39,39 +65,78 @@ impl core::ops::Add for Out {
 //     pub total_time: f32,
 // }
 
-#[derive(Debug, Default)]
-pub struct Segment {
+#[derive(Debug)]
+pub struct Segment<const N: usize = 3, T: Real = f32> {
     /// Start time of this segment.
-    pub start_t: f32,
+    pub start_t: T,
     /// Initial position.
-    q0: Coord3,
+    q0: Coord<N, T>,
     /// Final position.
-    q1: Coord3,
+    q1: Coord<N, T>,
     /// Initial velocity.
-    v0: Coord3,
+    v0: Coord<N, T>,
     /// Final velocity.
-    v1: Coord3,
+    v1: Coord<N, T>,
 
     /// Total time.
-    pub total_time: f32,
+    pub total_time: T,
 
     /// Acceleration time.
-    t_a: f32,
+    t_a: T,
 
     /// Deceleration time.
-    t_d: f32,
+    t_d: T,
 
     /// Maximum velocity for each axis.
-    vlim: Coord3,
+    vlim: Coord<N, T>,
 
     /// Sign of displacement.
-    sign: Coord3,
+    sign: Coord<N, T>,
 }
 
-impl Segment {
-    pub fn new(q0: Coord3, q1: Coord3, v0: Coord3, v1: Coord3, start_t: f32, lim: &Lim) -> Self {
+impl<const N: usize, T: Real> Default for Segment<N, T> {
+    fn default() -> Self {
+        Self {
+            start_t: T::zero(),
+            q0: Coord::zeros(),
+            q1: Coord::zeros(),
+            v0: Coord::zeros(),
+            v1: Coord::zeros(),
+            total_time: T::zero(),
+            t_a: T::zero(),
+            t_d: T::zero(),
+            vlim: Coord::zeros(),
+            sign: Coord::zeros(),
+        }
+    }
+}
+
+impl<const N: usize, T: Real> Segment<N, T> {
+    /// Like [`new`](Self::new), but returns an error for inputs that can't be planned instead of
+    /// panicking or producing a profile full of NaNs.
+    pub fn try_new(
+        q0: Coord<N, T>,
+        q1: Coord<N, T>,
+        v0: Coord<N, T>,
+        v1: Coord<N, T>,
+        start_t: T,
+        lim: &Lim<N, T>,
+    ) -> Result<Self, PlanError<T>> {
+        trapezoidal_non_zero_3d::validate(q0, q1, v0, v1, start_t, &lim.vel, &lim.acc)?;
+
+        Ok(Self::new(q0, q1, v0, v1, start_t, lim))
+    }
+
+    pub fn new(
+        q0: Coord<N, T>,
+        q1: Coord<N, T>,
+        v0: Coord<N, T>,
+        v1: Coord<N, T>,
+        start_t: T,
+        lim: &Lim<N, T>,
+    ) -> Self {
         assert!(
-            lim.acc > Coord3::zeros() && lim.vel > Coord3::zeros(),
+            lim.acc > Coord::zeros() && lim.vel > Coord::zeros(),
             "Limits must all be positive values, got {:?}",
             lim
         );
@@ -91,12 +156,12 @@ impl Segment {
         let v_delta = v1 - v0;
 
         // Largest axis, i.e. the one everything else will be adjusted against
-        let largest_axis = dbg!(h.component_div(&v_delta)).abs().imax();
+        let largest_axis = h.component_div(&v_delta).abs().imax();
 
-        dbg!(largest_axis);
+        let two = T::lit(2.0);
 
         // "Trajectory with preassigned acceleration and velocity", page 73
-        let preassigned_acc_vel = |axis: usize, limits: &Lim| {
+        let preassigned_acc_vel = |axis: usize, limits: &Lim<N, T>| {
             let h = h[axis];
             let a_max = limits.acc[axis];
             let v_max = limits.vel[axis];
@@ -107,7 +172,7 @@ impl Segment {
             let v_lim_reached = {
                 let lhs = h * a_max;
 
-                let rhs = v_max.powi(2) - (v0.powi(2) + v1.powi(2)) / 2.0;
+                let rhs = v_max.powi(2) - (v0.powi(2) + v1.powi(2)) / two;
 
                 lhs > rhs
             };
@@ -118,7 +183,7 @@ impl Segment {
                 v_max
             } else {
                 // Didn't reach max velocity, so reduce it by how much acceleration we can get away with
-                f32::sqrt(h * a_max + (v0.powi(2) + v1.powi(2)) / 2.0)
+                (h * a_max + (v0.powi(2) + v1.powi(2)) / two).sqrt()
             };
 
             // dbg!(v_lim_reached, h, a_max);
@@ -129,8 +194,8 @@ impl Segment {
             // Total duration of this segment
             let total_time = if v_lim_reached {
                 h / v_max
-                    + v_max / (2.0 * a_max) * (1.0 - v0 / v_max).powi(2)
-                    + v_max / (2.0 * a_max) * (1.0 - v1 / v_max).powi(2)
+                    + v_max / (two * a_max) * (T::one() - v0 / v_max).powi(2)
+                    + v_max / (two * a_max) * (T::one() - v1 / v_max).powi(2)
             } else {
                 // No cruise, so just sum accel + decel
                 t_a + t_d
@@ -146,19 +211,14 @@ impl Segment {
             largest_axis_accel_time,
             largest_axis_decel_time,
             largest_axis_total_time,
-            largest_axis_v_max,
-        ) = preassigned_acc_vel(largest_axis, &lim);
+            _largest_axis_v_max,
+        ) = preassigned_acc_vel(largest_axis, lim);
 
-        // Compute new limits based on largest axis. This synchronises all other axes.
-        let vlim = h / (largest_axis_total_time - largest_axis_accel_time);
-
-        dbg!(
-            vlim,
-            largest_axis_accel_time,
-            largest_axis_decel_time,
-            largest_axis_total_time,
-            largest_axis_v_max
-        );
+        // Compute new limits based on largest axis. This synchronises all other axes. The
+        // displacement is the area under the velocity profile (eq. 3.13), which also holds for
+        // non-zero initial and final velocities.
+        let vlim = (h - (v0 * largest_axis_accel_time + v1 * largest_axis_decel_time) / two)
+            / (largest_axis_total_time - (largest_axis_accel_time + largest_axis_decel_time) / two);
 
         Self {
             start_t,
@@ -175,7 +235,7 @@ impl Segment {
     }
 
     /// Get trajectory parameters at the given time `t`.
-    pub fn tp(&self, t: f32) -> Option<(Out, Phase)> {
+    pub fn tp(&self, t: T) -> Option<(Out<N, T>, Phase)> {
         let Self {
             q0,
             q1,
@@ -189,6 +249,8 @@ impl Segment {
             ..
         } = *self;
 
+        let two = T::lit(2.0);
+
         let t0 = start_t;
         let t1 = t0 + total_time;
         let t_delta = t - t0;
@@ -200,7 +262,7 @@ impl Segment {
             phase = Phase::Accel;
 
             Some(Out {
-                pos: q0 + v0 * (t - t0) + (vlim - v0) / (2.0 * t_a) * (t - t0).powi(2),
+                pos: q0 + v0 * (t - t0) + (vlim - v0) / (two * t_a) * (t - t0).powi(2),
                 vel: v0 + (vlim - v0) / t_a * (t - t0),
                 acc: (vlim - v0) / t_a,
             })
@@ -210,9 +272,9 @@ impl Segment {
             phase = Phase::Cruise;
 
             Some(Out {
-                pos: q0 + v0 * t_a / 2.0 + vlim * (t - t0 - t_a / 2.0),
+                pos: q0 + v0 * t_a / two + vlim * (t - t0 - t_a / two),
                 vel: vlim,
-                acc: Coord3::zeros(),
+                acc: Coord::zeros(),
             })
         }
         // Decel (3.13c) (non-inclusive)
@@ -220,7 +282,7 @@ impl Segment {
             phase = Phase::Decel;
 
             Some(Out {
-                pos: q1 - v1 * (t1 - t) - (vlim - v1) / (2.0 * t_d) * (t1 - t).powi(2),
+                pos: q1 - v1 * (t1 - t) - (vlim - v1) / (two * t_d) * (t1 - t).powi(2),
                 vel: v1 + (vlim - v1) / t_d * (t1 - t),
                 acc: -(vlim - v1) / t_d,
             })
@@ -242,23 +304,50 @@ impl Segment {
         })
     }
 
-    pub fn q0(&self) -> Coord3 {
+    pub fn q0(&self) -> Coord<N, T> {
         self.q0.component_mul(&self.sign)
     }
 
-    pub fn q1(&self) -> Coord3 {
+    pub fn q1(&self) -> Coord<N, T> {
         self.q1.component_mul(&self.sign)
     }
 
-    pub fn v0(&self) -> Coord3 {
+    pub fn v0(&self) -> Coord<N, T> {
         self.v0.component_mul(&self.sign)
     }
 
-    pub fn v1(&self) -> Coord3 {
+    pub fn v1(&self) -> Coord<N, T> {
         self.v1.component_mul(&self.sign)
     }
 }
 
+impl<const N: usize, T: Real> Profile<T> for Segment<N, T> {
+    type Out = Out<N, T>;
+
+    fn start_t(&self) -> T {
+        self.start_t
+    }
+
+    fn duration(&self) -> T {
+        self.total_time
+    }
+
+    fn tp(&self, t: T) -> Option<Out<N, T>> {
+        if t < self.start_t {
+            return None;
+        }
+
+        Segment::tp(self, t).map(|(out, _phase)| out)
+    }
+
+    fn phase_times(&self) -> Vec<T> {
+        profile::phase_times(
+            self.start_t,
+            &[self.t_a, self.total_time - self.t_a - self.t_d, self.t_d],
+        )
+    }
+}
+
 pub enum Phase {
     Accel,
     Cruise,
@@ -289,11 +378,11 @@ mod tests {
         dbg!(seg);
     }
 
-    // Single axis: ignore Y and Z
+    // Single axis: Y and Z don't move
     #[test]
     fn book_example_3_7_a() {
         let q0 = Coord3::new(0.0, 0.0, 0.0);
-        let q1 = Coord3::new(30.0, 0.0, 20.0);
+        let q1 = Coord3::new(30.0, 0.0, 0.0);
 
         let v0 = Coord3::new(5.0, 0.0, 0.0);
         let v1 = Coord3::new(2.0, 0.0, 0.0);
@@ -305,20 +394,18 @@ mod tests {
 
         let seg = Segment::new(q0, q1, v0, v1, 0.0, &lim);
 
-        dbg!(&seg);
-
-        // FIXME: The constants are out of the book but are missing a few DP
+        // The book rounds these to 2DP
         assert_approx_eq!(f32, seg.vlim[0], lim.vel[0]);
-        assert_approx_eq!(f32, seg.t_a, 0.5);
-        assert_approx_eq!(f32, seg.t_d, 0.8);
-        assert_approx_eq!(f32, seg.total_time, 3.44);
+        assert_approx_eq!(f32, seg.t_a, 0.5, epsilon = 1.0e-5);
+        assert_approx_eq!(f32, seg.t_d, 0.8, epsilon = 1.0e-5);
+        assert_approx_eq!(f32, seg.total_time, 3.445, epsilon = 1.0e-5);
     }
 
-    // Single axis: ignore Y and Z
+    // Single axis: Y and Z don't move
     #[test]
     fn book_example_3_7_b() {
         let q0 = Coord3::new(0.0, 0.0, 0.0);
-        let q1 = Coord3::new(30.0, 0.0, 20.0);
+        let q1 = Coord3::new(30.0, 0.0, 0.0);
 
         let v0 = Coord3::new(5.0, 0.0, 0.0);
         let v1 = Coord3::new(2.0, 0.0, 0.0);
@@ -330,12 +417,17 @@ mod tests {
 
         let seg = Segment::new(q0, q1, v0, v1, 0.0, &lim);
 
-        dbg!(&seg);
-
-        // FIXME: The constants are out of the book but are missing a few DP
-        assert_approx_eq!(f32, seg.vlim[0], 17.7);
-        assert_approx_eq!(f32, seg.t_a, 1.27);
-        assert_approx_eq!(f32, seg.t_d, 1.57);
-        assert_approx_eq!(f32, seg.total_time, 2.84);
+        // The book rounds these to 2DP. Max velocity isn't reached (eq. 3.10).
+        let vlim = 314.5f32.sqrt();
+
+        assert_approx_eq!(f32, seg.vlim[0], vlim, epsilon = 1.0e-4);
+        assert_approx_eq!(f32, seg.t_a, (vlim - 5.0) / 10.0, epsilon = 1.0e-5);
+        assert_approx_eq!(f32, seg.t_d, (vlim - 2.0) / 10.0, epsilon = 1.0e-5);
+        assert_approx_eq!(
+            f32,
+            seg.total_time,
+            (2.0 * vlim - 7.0) / 10.0,
+            epsilon = 1.0e-5
+        );
     }
 }
@@ -0,0 +1,1254 @@
+//! Time optimal path parameterisation, from the paper "Time-Optimal Trajectory Generation for Path
+//! Following with Bounded Acceleration and Velocity", Kunz and Stilman.
+//!
+//! Instead of timing each segment and blend on its own, the whole path of lines and blends is
+//! parameterised by its arc length `s`. The fastest path velocity `ṡ` at every point is then found
+//! by integrating forwards at maximum acceleration and backwards at maximum deceleration in the
+//! `s`/`ṡ` phase plane, joining the two at the switching points described in the paper.
+//!
+//! Corners which aren't blended must be passed through at zero velocity, so the path is split
+//! there and each piece is integrated from and to rest.
+
+use alloc::vec::Vec;
+
+use crate::{
+    arc,
+    bezier_blend::BezierBlend,
+    clothoid_blend::ClothoidBlend,
+    real::Real,
+    segments_blends::{Item, Trajectory},
+    spline::Spline,
+    trapezoidal_non_zero_3d::{Coord, Lim, Out},
+};
+
+/// A piece of a [`Path`], parameterised by arc length.
+#[derive(Debug, Clone)]
+pub enum PathSegment<const N: usize = 3, T: Real = f32> {
+    Line {
+        start: Coord<N, T>,
+        end: Coord<N, T>,
+    },
+    Arc {
+        center: Coord<N, T>,
+        radius: T,
+        /// Unit vector pointing from the center to the start of the arc.
+        x: Coord<N, T>,
+        /// Unit vector perpendicular to `x` in the plane of the arc, pointing towards its end.
+        y: Coord<N, T>,
+        /// Angle swept by the arc in radians.
+        angle: T,
+    },
+    Clothoid(ClothoidBlend<N, T>),
+    Bezier(BezierBlend<N, T>),
+    /// Programmed arc, which may also be a helix.
+    Helix(arc::Arc<N, T>),
+    Spline(Spline<N, T>),
+}
+
+impl<const N: usize, T: Real> PathSegment<N, T> {
+    /// Circular arc from `start` to `end` around `center`, taking the shorter way round.
+    pub fn arc(center: Coord<N, T>, start: Coord<N, T>, end: Coord<N, T>) -> Self {
+        let x = (start - center).normalize();
+        let end_dir = (end - center).normalize();
+        let y = (end_dir - x * end_dir.dot(&x)).normalize();
+
+        Self::Arc {
+            center,
+            radius: (start - center).norm(),
+            x,
+            y,
+            angle: x.angle(&end_dir),
+        }
+    }
+
+    /// Length of this segment.
+    pub fn len(&self) -> T {
+        match *self {
+            Self::Line { start, end } => (end - start).norm(),
+            Self::Arc { radius, angle, .. } => radius * angle,
+            Self::Clothoid(blend) => blend.len(),
+            Self::Bezier(blend) => blend.len(),
+            Self::Helix(arc) => arc.len,
+            Self::Spline(ref spline) => spline.len,
+        }
+    }
+
+    /// Position at distance `s` along this segment.
+    pub fn pos(&self, s: T) -> Coord<N, T> {
+        match *self {
+            Self::Line { start, end } => start + (end - start) * (s / self.len()),
+            Self::Arc {
+                center,
+                radius,
+                x,
+                y,
+                ..
+            } => {
+                let (sin, cos) = (s / radius).sin_cos();
+
+                center + (x * cos + y * sin) * radius
+            }
+            Self::Clothoid(blend) => blend.geometry(s).0,
+            Self::Bezier(blend) => blend.geometry(s).0,
+            Self::Helix(arc) => arc.geometry(s).0,
+            Self::Spline(ref spline) => spline.geometry(s).0,
+        }
+    }
+
+    /// First derivative of position with respect to `s`, which is the unit tangent.
+    pub fn tangent(&self, s: T) -> Coord<N, T> {
+        match *self {
+            Self::Line { start, end } => (end - start) / self.len(),
+            Self::Arc { radius, x, y, .. } => {
+                let (sin, cos) = (s / radius).sin_cos();
+
+                y * cos - x * sin
+            }
+            Self::Clothoid(blend) => blend.geometry(s).1,
+            Self::Bezier(blend) => blend.geometry(s).1,
+            Self::Helix(arc) => arc.geometry(s).1,
+            Self::Spline(ref spline) => spline.geometry(s).1,
+        }
+    }
+
+    /// Second derivative of position with respect to `s`.
+    pub fn curvature(&self, s: T) -> Coord<N, T> {
+        match *self {
+            Self::Line { .. } => Coord::zeros(),
+            Self::Arc { radius, x, y, .. } => {
+                let (sin, cos) = (s / radius).sin_cos();
+
+                -(x * cos + y * sin) / radius
+            }
+            Self::Clothoid(blend) => blend.geometry(s).2,
+            Self::Bezier(blend) => blend.geometry(s).2,
+            Self::Helix(arc) => arc.geometry(s).2,
+            Self::Spline(ref spline) => spline.geometry(s).2,
+        }
+    }
+
+    /// Distances along this segment where the acceleration limit curve isn't differentiable. That's
+    /// where the tangent of any axis crosses zero, where the curvature of a clothoid peaks, or at
+    /// the knots of a spline.
+    fn switching_points(&self) -> Vec<T> {
+        let mut points = Vec::new();
+
+        match *self {
+            Self::Line { .. } => {}
+            Self::Arc {
+                radius,
+                x,
+                y,
+                angle,
+                ..
+            } => {
+                let pi = T::pi();
+
+                for axis in 0..N {
+                    if x[axis] == T::zero() && y[axis] == T::zero() {
+                        continue;
+                    }
+
+                    // Angle where `y * cos - x * sin` is zero for this axis
+                    let mut switching_angle = y[axis].atan2(x[axis]);
+
+                    while switching_angle < T::zero() {
+                        switching_angle += pi;
+                    }
+
+                    while switching_angle < angle {
+                        points.push(switching_angle * radius);
+
+                        switching_angle += pi;
+                    }
+                }
+            }
+            Self::Clothoid(blend) => {
+                // Curvature peaks where the two clothoids meet
+                points.push(blend.clothoid_len);
+
+                points.extend(self.tangent_zero_crossings(Self::SAMPLES));
+            }
+            Self::Bezier(_) => points.extend(self.tangent_zero_crossings(Self::SAMPLES)),
+            Self::Helix(arc) if arc.is_spiral() => {
+                // The crossings drift away from the quarter turns as the radius changes, so sample
+                // each half turn as finely as a whole curve
+                let half_turns = (arc.sweep.abs() / T::pi())
+                    .ceil()
+                    .to_subset()
+                    .unwrap_or(1.0);
+
+                points.extend(self.tangent_zero_crossings(Self::SAMPLES * half_turns as usize));
+            }
+            Self::Helix(arc) => {
+                let pi = T::pi();
+
+                let turned = arc.sweep.abs();
+                let sign = arc.sweep.signum();
+
+                // Tangent of the first axis of the plane is zero where the angle is a multiple of
+                // π, and of the second axis a quarter turn later. Each repeats every half turn.
+                for angle in [T::zero(), pi / T::lit(2.0)] {
+                    // Angle turned to the first crossing
+                    let first = sign * (angle - arc.start_angle);
+                    let mut crossing = first - (first / pi).floor() * pi;
+
+                    while crossing < turned {
+                        points.push(crossing * arc.len / turned);
+
+                        crossing += pi;
+                    }
+                }
+            }
+            Self::Spline(ref spline) => {
+                // Curvature can change suddenly at the knots
+                let knots = spline.knot_distances();
+                let spans = knots.len() + 1;
+
+                points.extend(knots);
+                points.extend(self.tangent_zero_crossings(Self::SAMPLES * spans));
+            }
+        }
+
+        points
+    }
+
+    /// Number of steps to sample curves along when there's no closed form for their switching
+    /// points.
+    const SAMPLES: usize = 64;
+
+    /// Distances along a curve where the tangent of any axis changes sign. There's no closed form,
+    /// so take `samples` steps along looking for a change of sign then bisect.
+    fn tangent_zero_crossings(&self, samples: usize) -> Vec<T> {
+        let step = self.len() / T::lit(samples as f64);
+
+        let mut points = Vec::new();
+
+        for axis in 0..N {
+            let tangent = |s: T| self.tangent(s)[axis];
+
+            for i in 0..samples {
+                let mut before = step * T::lit(i as f64);
+                let mut after = before + step;
+
+                if tangent(before) * tangent(after) >= T::zero() {
+                    continue;
+                }
+
+                for _ in 0..32 {
+                    let mid = (before + after) / T::lit(2.0);
+
+                    if tangent(before) * tangent(mid) <= T::zero() {
+                        after = mid;
+                    } else {
+                        before = mid;
+                    }
+                }
+
+                points.push(after);
+            }
+        }
+
+        points
+    }
+}
+
+/// A path of lines and blends, parameterised by arc length.
+#[derive(Debug, Clone)]
+pub struct Path<const N: usize = 3, T: Real = f32> {
+    segments: Vec<PathSegment<N, T>>,
+    /// Distance along the path that each segment starts at.
+    starts: Vec<T>,
+    len: T,
+    /// Distances along the path where the limit curves aren't differentiable, and whether the
+    /// curvature is discontinuous there.
+    switching_points: Vec<(T, bool)>,
+}
+
+impl<const N: usize, T: Real> Path<N, T> {
+    /// Segments of zero length are skipped.
+    pub fn new(segments: impl IntoIterator<Item = PathSegment<N, T>>) -> Self {
+        let segments = segments
+            .into_iter()
+            .filter(|segment| segment.len() > T::zero())
+            .collect::<Vec<_>>();
+
+        let mut starts = Vec::with_capacity(segments.len());
+        let mut switching_points = Vec::new();
+        let mut len = T::zero();
+
+        for segment in segments.iter() {
+            // Curvature changes at every join
+            if len > T::zero() {
+                switching_points.push((len, true));
+            }
+
+            starts.push(len);
+
+            switching_points.extend(
+                segment
+                    .switching_points()
+                    .into_iter()
+                    .map(|point| (len + point, false)),
+            );
+
+            len += segment.len();
+        }
+
+        switching_points
+            .sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(core::cmp::Ordering::Equal));
+
+        Self {
+            segments,
+            starts,
+            len,
+            switching_points,
+        }
+    }
+
+    /// The lines, arcs, splines and blends of a trajectory. Other items are followed by a straight line
+    /// from their start to their end.
+    pub fn from_trajectory(trajectory: &Trajectory<N, T>) -> Self {
+        Self::new(trajectory.items.iter().map(|item| match item {
+            Item::ArcBlend(blend) => {
+                PathSegment::arc(blend.arc_center, blend.arc_start, blend.arc_end)
+            }
+            Item::ClothoidBlend(blend) => PathSegment::Clothoid(*blend),
+            Item::BezierBlend(blend) => PathSegment::Bezier(*blend),
+            Item::Arc(arc) => PathSegment::Helix(*arc),
+            Item::Spline(spline) => PathSegment::Spline(spline.clone()),
+            item => PathSegment::Line {
+                start: item.q0(),
+                end: item.q1(),
+            },
+        }))
+    }
+
+    /// Total length of the path.
+    pub fn len(&self) -> T {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Segment at distance `s` along the path, and the distance along that segment.
+    fn segment(&self, s: T) -> (&PathSegment<N, T>, T) {
+        let index = self
+            .starts
+            .partition_point(|start| *start <= s)
+            .saturating_sub(1);
+
+        let local = (s - self.starts[index]).max(T::zero());
+
+        (&self.segments[index], local.min(self.segments[index].len()))
+    }
+
+    /// Position at distance `s` along the path.
+    pub fn pos(&self, s: T) -> Coord<N, T> {
+        let (segment, s) = self.segment(s);
+
+        segment.pos(s)
+    }
+
+    /// Unit tangent at distance `s` along the path.
+    pub fn tangent(&self, s: T) -> Coord<N, T> {
+        let (segment, s) = self.segment(s);
+
+        segment.tangent(s)
+    }
+
+    /// Second derivative of position at distance `s` along the path.
+    pub fn curvature(&self, s: T) -> Coord<N, T> {
+        let (segment, s) = self.segment(s);
+
+        segment.curvature(s)
+    }
+
+    /// Distances along the path where the direction changes abruptly, so the path must be
+    /// traversed at zero velocity.
+    fn corners(&self) -> Vec<T> {
+        let tolerance = T::default_epsilon().sqrt();
+
+        self.segments
+            .windows(2)
+            .zip(self.starts.iter().skip(1))
+            .filter(|(pair, _)| {
+                let (prev, next) = (&pair[0], &pair[1]);
+
+                prev.tangent(prev.len()).dot(&next.tangent(T::zero())) < T::one() - tolerance
+            })
+            .map(|(_, start)| *start)
+            .collect()
+    }
+}
+
+/// A point in the phase plane.
+#[derive(Debug, Clone, Copy)]
+struct Sample<T> {
+    /// Distance along the path.
+    s: T,
+    /// Path velocity `ṡ`.
+    vel: T,
+    /// Time the sample is reached at.
+    t: T,
+}
+
+impl<T: Real> Sample<T> {
+    fn new(s: T, vel: T) -> Self {
+        Self {
+            s,
+            vel,
+            t: T::zero(),
+        }
+    }
+}
+
+/// Time optimal trajectory along a [`Path`] under per-axis velocity and acceleration limits.
+#[derive(Debug, Clone)]
+pub struct TimeOptimal<const N: usize = 3, T: Real = f32> {
+    path: Path<N, T>,
+    samples: Vec<Sample<T>>,
+}
+
+impl<const N: usize, T: Real> TimeOptimal<N, T> {
+    /// Integrate the phase plane every `time_step` seconds. Smaller steps give a result closer to
+    /// the true optimum.
+    ///
+    /// Returns `None` if the integration fails, e.g. when a limit is zero.
+    pub fn new(path: Path<N, T>, lim: &Lim<N, T>, time_step: T) -> Option<Self> {
+        assert!(
+            time_step > T::zero(),
+            "Time step must be a positive value, got {:?}",
+            time_step
+        );
+
+        let mut bounds = Vec::with_capacity(2);
+
+        bounds.push(T::zero());
+        bounds.extend(path.corners());
+        bounds.push(path.len());
+
+        let mut samples: Vec<Sample<T>> = Vec::new();
+
+        for section in bounds.windows(2) {
+            let integrator = Integrator {
+                path: &path,
+                lim,
+                time_step,
+                start: section[0],
+                end: section[1],
+                eps: T::default_epsilon().sqrt() * path.len().max(T::one()),
+                tolerance: T::default_epsilon() * T::lit(16.0) * path.len().max(T::one()),
+            };
+
+            let section_samples = integrator.integrate()?;
+
+            // Sections join at rest, so drop the duplicated point
+            let skip = usize::from(!samples.is_empty());
+
+            samples.extend(section_samples.into_iter().skip(skip));
+        }
+
+        // Time taken between two samples, assuming constant acceleration
+        for i in 1..samples.len() {
+            let (prev, next) = (samples[i - 1], samples[i]);
+
+            let distance = next.s - prev.s;
+            let mean_vel = (prev.vel + next.vel) / T::lit(2.0);
+
+            let dt = if distance > T::zero() && mean_vel > T::zero() {
+                distance / mean_vel
+            } else {
+                T::zero()
+            };
+
+            samples[i].t = prev.t + dt;
+        }
+
+        Some(Self { path, samples })
+    }
+
+    /// Plan a time optimal trajectory along the lines and blends of `trajectory` under its
+    /// trajectory-wide [`limits`](Trajectory::limits).
+    pub fn from_trajectory(trajectory: &Trajectory<N, T>, time_step: T) -> Option<Self> {
+        Self::new(
+            Path::from_trajectory(trajectory),
+            &trajectory.limits,
+            time_step,
+        )
+    }
+
+    pub fn path(&self) -> &Path<N, T> {
+        &self.path
+    }
+
+    /// Total duration of the trajectory.
+    pub fn duration(&self) -> T {
+        self.samples
+            .last()
+            .map(|sample| sample.t)
+            .unwrap_or_else(T::zero)
+    }
+
+    /// Get trajectory parameters at the given time `t`.
+    pub fn tp(&self, t: T) -> Option<Out<N, T>> {
+        if t < T::zero() || t > self.duration() || self.path.is_empty() {
+            return None;
+        }
+
+        // Nothing to interpolate between, so stay where the only sample is
+        if self.samples.len() < 2 {
+            return self.samples.first().map(|sample| Out {
+                pos: self.path.pos(sample.s),
+                vel: Coord::zeros(),
+                acc: Coord::zeros(),
+            });
+        }
+
+        // First sample after `t`, skipping any which take no time
+        let index = self
+            .samples
+            .partition_point(|sample| sample.t <= t)
+            .clamp(1, self.samples.len() - 1);
+
+        let (prev, next) = (self.samples[index - 1], self.samples[index]);
+
+        let step = next.t - prev.t;
+
+        // Constant acceleration between samples
+        let path_acc = if step > T::zero() {
+            (next.vel - prev.vel) / step
+        } else {
+            T::zero()
+        };
+
+        let dt = t - prev.t;
+
+        let s = (prev.s + prev.vel * dt + path_acc * dt.powi(2) / T::lit(2.0)).min(self.path.len());
+        let vel = prev.vel + path_acc * dt;
+
+        let tangent = self.path.tangent(s);
+
+        Some(Out {
+            pos: self.path.pos(s),
+            vel: tangent * vel,
+            acc: tangent * path_acc + self.path.curvature(s) * vel.powi(2),
+        })
+    }
+}
+
+/// Phase plane integration over the part of a path between two corners.
+struct Integrator<'a, const N: usize, T: Real> {
+    path: &'a Path<N, T>,
+    lim: &'a Lim<N, T>,
+    time_step: T,
+    start: T,
+    end: T,
+    /// Distance used for numerical derivatives and to step either side of switching points.
+    eps: T,
+    /// Distance below which two points on the path are considered the same.
+    tolerance: T,
+}
+
+impl<'a, const N: usize, T: Real> Integrator<'a, N, T> {
+    fn integrate(&self) -> Option<Vec<Sample<T>>> {
+        let mut trajectory = Vec::new();
+
+        trajectory.push(Sample::new(self.start, T::zero()));
+
+        let mut acc = self.path_acc(self.start, T::zero(), true);
+
+        while !self.integrate_forward(&mut trajectory, acc)? {
+            // Nothing left to switch at, so the deceleration to the end will cut the trajectory
+            // short instead
+            let Some((switching_point, before_acc, after_acc)) =
+                self.next_switching_point(trajectory.last()?.s)
+            else {
+                break;
+            };
+
+            if !self.integrate_backward(&mut trajectory, switching_point, before_acc) {
+                return None;
+            }
+
+            acc = after_acc;
+        }
+
+        // Finally come to a stop at the end
+        self.integrate_backward(&mut trajectory, Sample::new(self.end, T::zero()), T::zero())
+            .then_some(trajectory)
+    }
+
+    /// Fastest (`max`) or slowest path acceleration at `s` with path velocity `vel`.
+    fn path_acc(&self, s: T, vel: T, max: bool) -> T {
+        let tangent = self.path.tangent(s);
+        let curvature = self.path.curvature(s);
+
+        let factor = if max { T::one() } else { -T::one() };
+
+        let path_acc = (0..N).filter(|axis| tangent[*axis] != T::zero()).fold(
+            T::lit(f64::INFINITY),
+            |path_acc, axis| {
+                let centripetal = if curvature[axis] != T::zero() {
+                    factor * curvature[axis] * vel.powi(2) / tangent[axis]
+                } else {
+                    T::zero()
+                };
+
+                path_acc.min(self.lim.acc[axis] / tangent[axis].abs() - centripetal)
+            },
+        );
+
+        factor * path_acc
+    }
+
+    /// Slope of the fastest or slowest trajectory through the given point in the phase plane.
+    fn phase_slope(&self, s: T, vel: T, max: bool) -> T {
+        self.path_acc(s, vel, max) / vel
+    }
+
+    /// Fastest path velocity at `s` allowed by the acceleration limits.
+    fn acc_max_vel(&self, s: T) -> T {
+        let tangent = self.path.tangent(s);
+        let curvature = self.path.curvature(s);
+        let acc = self.lim.acc;
+
+        let mut max_vel = T::lit(f64::INFINITY);
+
+        for i in 0..N {
+            if tangent[i] != T::zero() {
+                for j in (i + 1)..N {
+                    if tangent[j] != T::zero() {
+                        let a_ij = curvature[i] / tangent[i] - curvature[j] / tangent[j];
+
+                        if a_ij != T::zero() {
+                            max_vel = max_vel.min(
+                                ((acc[i] / tangent[i].abs() + acc[j] / tangent[j].abs())
+                                    / a_ij.abs())
+                                .sqrt(),
+                            );
+                        }
+                    }
+                }
+            } else if curvature[i] != T::zero() {
+                max_vel = max_vel.min((acc[i] / curvature[i].abs()).sqrt());
+            }
+        }
+
+        max_vel
+    }
+
+    /// Fastest path velocity at `s` allowed by the velocity limits.
+    fn vel_max_vel(&self, s: T) -> T {
+        let tangent = self.path.tangent(s);
+
+        (0..N)
+            .filter(|axis| tangent[*axis] != T::zero())
+            .fold(T::lit(f64::INFINITY), |max_vel, axis| {
+                max_vel.min(self.lim.vel[axis] / tangent[axis].abs())
+            })
+    }
+
+    fn acc_max_vel_deriv(&self, s: T) -> T {
+        (self.acc_max_vel(s + self.eps) - self.acc_max_vel(s - self.eps)) / (T::lit(2.0) * self.eps)
+    }
+
+    fn vel_max_vel_deriv(&self, s: T) -> T {
+        let tangent = self.path.tangent(s);
+        let curvature = self.path.curvature(s);
+
+        // Derivative of the limit of whichever axis is most constrained
+        let Some(axis) = (0..N)
+            .filter(|axis| tangent[*axis] != T::zero())
+            .min_by(|a, b| {
+                (self.lim.vel[*a] / tangent[*a].abs())
+                    .partial_cmp(&(self.lim.vel[*b] / tangent[*b].abs()))
+                    .unwrap_or(core::cmp::Ordering::Equal)
+            })
+        else {
+            return T::zero();
+        };
+
+        -(self.lim.vel[axis] * curvature[axis]) / (tangent[axis] * tangent[axis].abs())
+    }
+
+    /// Next point in this section where curvature is discontinuous, or the end of the section.
+    fn next_discontinuity(&self, s: T) -> T {
+        self.path
+            .switching_points
+            .iter()
+            .find(|(point, discontinuity)| *discontinuity && *point > s)
+            .map(|(point, _)| *point)
+            .unwrap_or(self.end)
+            .min(self.end)
+    }
+
+    /// Next switching point of the path after `s`, or the end of this section.
+    fn next_path_switching_point(&self, s: T) -> (T, bool) {
+        self.path
+            .switching_points
+            .iter()
+            .find(|(point, _)| *point > s)
+            .copied()
+            .filter(|(point, _)| *point < self.end)
+            .unwrap_or((self.end, true))
+    }
+
+    /// Integrate forwards at maximum acceleration until the end of the section or a limit curve
+    /// that can't be followed is hit. Returns `Some(true)` at the end of the section and `None` if
+    /// the integration fails.
+    fn integrate_forward(&self, trajectory: &mut Vec<Sample<T>>, mut acc: T) -> Option<bool> {
+        let &Sample { mut s, mut vel, .. } = trajectory.last()?;
+
+        loop {
+            let next_discontinuity = self.next_discontinuity(s);
+
+            let (old_s, old_vel) = (s, vel);
+
+            vel += self.time_step * acc;
+            s += self.time_step * (old_vel + vel) / T::lit(2.0);
+
+            // Stop exactly on the discontinuity instead of stepping over it
+            if s > next_discontinuity && next_discontinuity < self.end {
+                if s - next_discontinuity < self.tolerance {
+                    continue;
+                }
+
+                vel = old_vel + (next_discontinuity - old_s) * (vel - old_vel) / (s - old_s);
+                s = next_discontinuity;
+            }
+
+            if s > self.end {
+                trajectory.push(Sample::new(s, vel));
+
+                return Some(true);
+            } else if vel < T::zero() {
+                return None;
+            }
+
+            // Follow the velocity limit curve if possible
+            if vel > self.vel_max_vel(s)
+                && self.phase_slope(old_s, self.vel_max_vel(old_s), false)
+                    <= self.vel_max_vel_deriv(old_s)
+            {
+                vel = self.vel_max_vel(s);
+            }
+
+            trajectory.push(Sample::new(s, vel));
+
+            acc = self.path_acc(s, vel, true);
+
+            if vel > self.acc_max_vel(s) || vel > self.vel_max_vel(s) {
+                // Find the intersection with the limit curve more accurately by bisection
+                let overshoot = trajectory.pop()?;
+                let last = *trajectory.last()?;
+
+                let (mut before, mut before_vel) = (last.s, last.vel);
+                let (mut after, mut after_vel) = (overshoot.s, overshoot.vel);
+
+                while after - before > self.tolerance {
+                    let mid = (before + after) / T::lit(2.0);
+                    let mut mid_vel = (before_vel + after_vel) / T::lit(2.0);
+
+                    if mid_vel > self.vel_max_vel(mid)
+                        && self.phase_slope(before, self.vel_max_vel(before), false)
+                            <= self.vel_max_vel_deriv(before)
+                    {
+                        mid_vel = self.vel_max_vel(mid);
+                    }
+
+                    if mid_vel > self.acc_max_vel(mid) || mid_vel > self.vel_max_vel(mid) {
+                        (after, after_vel) = (mid, mid_vel);
+                    } else {
+                        (before, before_vel) = (mid, mid_vel);
+                    }
+                }
+
+                trajectory.push(Sample::new(before, before_vel));
+
+                if self.acc_max_vel(after) < self.vel_max_vel(after) {
+                    if after > next_discontinuity
+                        || self.phase_slope(before, before_vel, true)
+                            > self.acc_max_vel_deriv(before)
+                    {
+                        return Some(false);
+                    }
+
+                    // The fastest trajectory can follow the acceleration limit curve here, so it
+                    // only crossed it by rounding. Stepping from the intersection would cross it
+                    // again straight away, so follow the curve instead.
+                    s = overshoot.s;
+                    vel = self.acc_max_vel(s);
+
+                    trajectory.push(Sample::new(s, vel));
+
+                    acc = self.path_acc(s, vel, true);
+                } else if self.phase_slope(before, before_vel, false)
+                    > self.vel_max_vel_deriv(before)
+                {
+                    return Some(false);
+                } else {
+                    s = before;
+                    vel = before_vel;
+                }
+            }
+        }
+    }
+
+    /// Integrate backwards at maximum deceleration from `point` until the trajectory is met, then
+    /// replace the rest of the trajectory with the backwards one. Returns `false` if the two never
+    /// meet.
+    fn integrate_backward(
+        &self,
+        trajectory: &mut Vec<Sample<T>>,
+        point: Sample<T>,
+        mut acc: T,
+    ) -> bool {
+        if trajectory.len() < 2 {
+            return false;
+        }
+
+        let Sample { mut s, mut vel, .. } = point;
+
+        // Indices of the start trajectory segment being tested for an intersection
+        let mut start2 = trajectory.len() - 1;
+        let mut start1 = start2 - 1;
+
+        // Backwards trajectory, in reverse order
+        let mut backward: Vec<Sample<T>> = Vec::new();
+        let mut slope = T::zero();
+
+        while start1 > 0 || s >= self.start {
+            if trajectory[start1].s <= s {
+                backward.push(Sample::new(s, vel));
+
+                vel -= self.time_step * acc;
+                s -= self.time_step * (vel + backward.last().unwrap().vel) / T::lit(2.0);
+
+                acc = self.path_acc(s, vel, false);
+
+                let front = backward.last().unwrap();
+
+                slope = (front.vel - vel) / (front.s - s);
+
+                if vel < T::zero() {
+                    return false;
+                }
+            } else if start1 > 0 {
+                start1 -= 1;
+                start2 -= 1;
+            } else {
+                return false;
+            }
+
+            let (a, b) = (trajectory[start1], trajectory[start2]);
+            let front_s = backward.last().map(|front| front.s).unwrap_or(s);
+
+            // Check for an intersection between the current segment of the start trajectory and
+            // the backwards trajectory
+            let start_slope = (b.vel - a.vel) / (b.s - a.s);
+            let intersection =
+                (a.vel - vel + slope * s - start_slope * a.s) / (slope - start_slope);
+
+            if a.s.max(s) - self.tolerance <= intersection
+                && intersection <= self.tolerance + b.s.min(front_s)
+            {
+                let intersection_vel = a.vel + start_slope * (intersection - a.s);
+
+                trajectory.truncate(start2);
+                trajectory.push(Sample::new(intersection, intersection_vel));
+                trajectory.extend(backward.into_iter().rev());
+
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Next switching point after `s` on either limit curve, and the accelerations before and
+    /// after it.
+    fn next_switching_point(&self, s: T) -> Option<(Sample<T>, T, T)> {
+        let mut acc_point = self.next_acc_switching_point(s);
+
+        while let Some((point, _, _)) = acc_point {
+            if point.vel <= self.vel_max_vel(point.s) {
+                break;
+            }
+
+            acc_point = self.next_acc_switching_point(point.s);
+        }
+
+        let acc_s = acc_point.map(|(point, _, _)| point.s);
+
+        let mut vel_point = self.next_vel_switching_point(s);
+
+        while let Some((point, _, _)) = vel_point {
+            let below_acc_limit = point.vel <= self.acc_max_vel(point.s - self.eps)
+                && point.vel <= self.acc_max_vel(point.s + self.eps);
+
+            if acc_s.is_none_or(|acc_s| point.s > acc_s) || below_acc_limit {
+                break;
+            }
+
+            vel_point = self.next_vel_switching_point(point.s);
+        }
+
+        match (acc_point, vel_point) {
+            (Some(acc), Some(vel)) if vel.0.s < acc.0.s => Some(vel),
+            (Some(acc), _) => Some(acc),
+            (None, vel) => vel,
+        }
+    }
+
+    fn next_acc_switching_point(&self, s: T) -> Option<(Sample<T>, T, T)> {
+        match (
+            self.next_path_acc_switching_point(s),
+            self.next_smooth_acc_switching_point(s),
+        ) {
+            (Some(path), Some(smooth)) if smooth.0.s < path.0.s => Some(smooth),
+            (path, smooth) => path.or(smooth),
+        }
+    }
+
+    /// Next switching point on the acceleration limit curve where the path isn't smooth.
+    fn next_path_acc_switching_point(&self, s: T) -> Option<(Sample<T>, T, T)> {
+        let eps = self.eps;
+        let mut switching_s = s;
+
+        loop {
+            let discontinuity;
+
+            (switching_s, discontinuity) = self.next_path_switching_point(switching_s);
+
+            if switching_s > self.end - eps {
+                return None;
+            }
+
+            if discontinuity {
+                let before_vel = self.acc_max_vel(switching_s - eps);
+                let after_vel = self.acc_max_vel(switching_s + eps);
+
+                let vel = before_vel.min(after_vel);
+
+                let before_acc = self.path_acc(switching_s - eps, vel, false);
+                let after_acc = self.path_acc(switching_s + eps, vel, true);
+
+                if (before_vel > after_vel
+                    || self.phase_slope(switching_s - eps, vel, false)
+                        > self.acc_max_vel_deriv(switching_s - eps * T::lit(2.0)))
+                    && (before_vel < after_vel
+                        || self.phase_slope(switching_s + eps, vel, true)
+                            < self.acc_max_vel_deriv(switching_s + eps * T::lit(2.0)))
+                {
+                    return Some((Sample::new(switching_s, vel), before_acc, after_acc));
+                }
+            } else if self.acc_max_vel_deriv(switching_s - eps) < T::zero()
+                && self.acc_max_vel_deriv(switching_s + eps) > T::zero()
+            {
+                return Some((
+                    Sample::new(switching_s, self.acc_max_vel(switching_s)),
+                    T::zero(),
+                    T::zero(),
+                ));
+            }
+        }
+    }
+
+    /// Next point where the acceleration limit curve is smooth but the slowest trajectory touching
+    /// it would leave it, as happens before the curvature peak of a Bezier blend. Found by
+    /// stepping along like velocity switching points.
+    fn next_smooth_acc_switching_point(&self, s: T) -> Option<(Sample<T>, T, T)> {
+        let step = T::lit(0.001).min((self.end - self.start) / T::lit(100.0));
+
+        // Only where acceleration, not velocity, is the tighter limit
+        let on_curve = |s: T| {
+            let vel = self.acc_max_vel(s);
+
+            vel.is_finite() && vel < self.vel_max_vel(s)
+        };
+
+        let above_curve =
+            |s: T| self.phase_slope(s, self.acc_max_vel(s), false) > self.acc_max_vel_deriv(s);
+
+        // Step along until the slowest trajectory can follow the curve again, having left it first
+        let mut s = s;
+        let mut started = false;
+
+        loop {
+            s += step;
+
+            if s >= self.end {
+                return None;
+            }
+
+            if !on_curve(s) {
+                started = false;
+            } else if above_curve(s) {
+                started = true;
+            } else if started {
+                break;
+            }
+        }
+
+        let (mut before, mut after) = (s - step, s);
+
+        while after - before > self.tolerance {
+            let mid = (before + after) / T::lit(2.0);
+
+            if above_curve(mid) {
+                before = mid;
+            } else {
+                after = mid;
+            }
+        }
+
+        let vel = self.acc_max_vel(after);
+
+        Some((
+            Sample::new(after, vel),
+            self.path_acc(after, vel, false),
+            self.path_acc(after, vel, true),
+        ))
+    }
+
+    fn next_vel_switching_point(&self, s: T) -> Option<(Sample<T>, T, T)> {
+        let step = T::lit(0.001).min((self.end - self.start) / T::lit(100.0));
+
+        let above_curve =
+            |s: T| self.phase_slope(s, self.vel_max_vel(s), false) > self.vel_max_vel_deriv(s);
+
+        // Step along until the slowest trajectory leaves the velocity limit curve, having been
+        // able to follow it first
+        let mut s = s;
+        let mut started = false;
+
+        loop {
+            s += step;
+
+            if s >= self.end {
+                return None;
+            }
+
+            if !above_curve(s) {
+                started = true;
+            } else if started {
+                break;
+            }
+        }
+
+        let (mut before, mut after) = (s - step, s);
+
+        while after - before > self.tolerance {
+            let mid = (before + after) / T::lit(2.0);
+
+            if above_curve(mid) {
+                after = mid;
+            } else {
+                before = mid;
+            }
+        }
+
+        let before_acc = self.path_acc(before, self.vel_max_vel(before), true);
+        let after_acc = self.path_acc(after, self.vel_max_vel(after), false);
+
+        Some((
+            Sample::new(after, self.vel_max_vel(after)),
+            before_acc,
+            after_acc,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        arc::{ArcCenter, Direction, Plane},
+        segments_blends::{BlendShape, PathMode},
+        trapezoidal_non_zero_3d::Coord3,
+    };
+
+    fn lim() -> Lim {
+        Lim {
+            vel: Coord3::new(5.0, 5.0, 5.0),
+            acc: Coord3::new(10.0, 10.0, 10.0),
+        }
+    }
+
+    #[test]
+    fn straight_line() {
+        let path = Path::new([PathSegment::Line {
+            start: Coord3::zeros(),
+            end: Coord3::new(10.0, 0.0, 0.0),
+        }]);
+
+        let topp = TimeOptimal::new(path, &lim(), 0.001).unwrap();
+
+        // Same as a trapezoidal profile: accelerate for 0.5s, cruise for 1.5s, decelerate for 0.5s
+        assert!((topp.duration() - 2.5).abs() < 0.01, "{}", topp.duration());
+
+        let end = topp.tp(topp.duration()).unwrap();
+
+        assert!((end.pos - Coord3::new(10.0, 0.0, 0.0)).norm() < 1.0e-3);
+    }
+
+    #[test]
+    fn single_sample() {
+        let path = Path::new([PathSegment::Line {
+            start: Coord3::new(1.0, 0.0, 0.0),
+            end: Coord3::new(10.0, 0.0, 0.0),
+        }]);
+
+        // Nothing to interpolate between, so it stays at the start
+        let topp = TimeOptimal {
+            path,
+            samples: Vec::from([Sample::new(0.0, 0.0)]),
+        };
+
+        let out = topp.tp(0.0).unwrap();
+
+        assert_eq!(out.pos, Coord3::new(1.0, 0.0, 0.0));
+        assert_eq!(out.vel, Coord3::zeros());
+        assert!(topp.tp(0.1).is_none());
+    }
+
+    #[test]
+    fn blended_path() {
+        let mut traj = Trajectory::new();
+
+        traj.limits = lim();
+        traj.path_mode = PathMode::Blend(Some(0.2));
+
+        traj.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(5.0, 0.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(5.0, 5.0, 0.0)).unwrap();
+        traj.push_point(Coord3::new(0.0, 5.0, 2.0)).unwrap();
+
+        let topp = TimeOptimal::from_trajectory(&traj, 0.001).unwrap();
+
+        // No slower than timing each item on its own
+        assert!(topp.duration() <= traj.total_time, "{}", topp.duration());
+
+        let dt = 0.001;
+        let mut t = 0.0;
+
+        while let Some(out) = topp.tp(t) {
+            assert!(out.vel.abs() <= lim().vel * 1.01, "{t}: {}", out.vel);
+            assert!(out.acc.abs() <= lim().acc * 1.05, "{t}: {}", out.acc);
+
+            t += dt;
+        }
+
+        let end = topp.tp(topp.duration()).unwrap();
+
+        assert!((end.pos - Coord3::new(0.0, 5.0, 2.0)).norm() < 1.0e-3);
+        assert!(end.vel.norm() < 1.0e-3);
+    }
+
+    #[test]
+    fn helix() {
+        let lim = Lim {
+            vel: Coord::<3, f64>::new(5.0, 5.0, 5.0),
+            acc: Coord::<3, f64>::new(10.0, 10.0, 10.0),
+        };
+
+        let mut traj = Trajectory::<3, f64>::new();
+
+        traj.limits = lim;
+
+        traj.push_point(Coord::<3, f64>::new(0.0, 0.0, 0.0))
+            .unwrap();
+        traj.push_point(Coord::<3, f64>::new(2.0, 0.0, 0.0))
+            .unwrap();
+
+        // Two turns, joining the line smoothly
+        assert!(traj.push_arc(
+            Coord::<3, f64>::new(2.0, 0.0, -1.0),
+            ArcCenter::Point(Coord::<3, f64>::new(2.0, 1.0, 0.0)),
+            Plane::Xy,
+            Direction::Counterclockwise,
+            1,
+        ));
+
+        let topp = TimeOptimal::from_trajectory(&traj, 0.001).unwrap();
+
+        assert!(topp.duration() <= traj.total_time, "{}", topp.duration());
+
+        let dt = 0.001;
+        let mut t = 0.0;
+
+        while let Some(out) = topp.tp(t) {
+            assert!(out.vel.abs() <= lim.vel * 1.01, "{t}: {}", out.vel);
+            assert!(out.acc.abs() <= lim.acc * 1.05, "{t}: {}", out.acc);
+
+            t += dt;
+        }
+
+        let end = topp.tp(topp.duration()).unwrap();
+
+        assert!((end.pos - Coord::<3, f64>::new(2.0, 0.0, -1.0)).norm() < 1.0e-3);
+    }
+
+    #[test]
+    fn curved_blends() {
+        for shape in [BlendShape::Clothoid, BlendShape::Bezier] {
+            let mut traj = Trajectory::new();
+
+            traj.limits = lim();
+            traj.blend_shape = shape;
+            traj.path_mode = PathMode::Blend(Some(0.2));
+
+            traj.push_point(Coord3::new(0.0, 0.0, 0.0)).unwrap();
+            traj.push_point(Coord3::new(5.0, 0.0, 0.0)).unwrap();
+            traj.push_point(Coord3::new(5.0, 5.0, 0.0)).unwrap();
+
+            let topp = TimeOptimal::from_trajectory(&traj, 0.001).unwrap();
+
+            assert!(topp.duration() <= traj.total_time, "{shape:?}");
+
+            let mut t = 0.0;
+
+            while let Some(out) = topp.tp(t) {
+                assert!(
+                    out.vel.abs() <= lim().vel * 1.01,
+                    "{shape:?} {t}: {}",
+                    out.vel
+                );
+                assert!(
+                    out.acc.abs() <= lim().acc * 1.05,
+                    "{shape:?} {t}: {}",
+                    out.acc
+                );
+
+                t += 0.001;
+            }
+        }
+    }
+
+    #[test]
+    fn stops_at_corner() {
+        let path = Path::new([
+            PathSegment::Line {
+                start: Coord3::zeros(),
+                end: Coord3::new(5.0, 0.0, 0.0),
+            },
+            PathSegment::Line {
+                start: Coord3::new(5.0, 0.0, 0.0),
+                end: Coord3::new(5.0, 5.0, 0.0),
+            },
+        ]);
+
+        let topp = TimeOptimal::new(path, &lim(), 0.001).unwrap();
+
+        // Two identical moves from rest to rest
+        let half = topp.duration() / 2.0;
+
+        let corner = topp.tp(half).unwrap();
+
+        assert!((corner.pos - Coord3::new(5.0, 0.0, 0.0)).norm() < 1.0e-2);
+        assert!(corner.vel.norm() < 0.1, "{}", corner.vel);
+    }
+}
@@ -1,21 +1,28 @@
-/// Trapezoidal single trajectory segment.
+//! Trapezoidal single trajectory segment.
+
+use crate::{
+    error::{self, PlanError},
+    profile::{self, Profile},
+    real::Real,
+};
+use alloc::{vec, vec::Vec};
 
 #[derive(Default, Debug, Clone, Copy)]
-pub struct Lim {
-    pub vel: f32,
-    pub acc: f32,
-    pub jerk: f32,
+pub struct Lim<T = f32> {
+    pub vel: T,
+    pub acc: T,
+    pub jerk: T,
 }
 
 #[derive(Debug, Default, Clone, Copy)]
-pub struct Out {
-    pub pos: f32,
-    pub vel: f32,
-    pub acc: f32,
-    pub jerk: f32,
+pub struct Out<T = f32> {
+    pub pos: T,
+    pub vel: T,
+    pub acc: T,
+    pub jerk: T,
 }
 
-impl core::ops::Add for Out {
+impl<T: Real> core::ops::Add for Out<T> {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self::Output {
@@ -29,46 +36,58 @@ impl core::ops::Add for Out {
 }
 
 #[derive(Debug, Default, Clone, Copy)]
-pub struct Times {
-    pub t_j1: f32,
-    pub t_j2: f32,
-    pub t_d: f32,
-    pub t_a: f32,
-    pub t_v: f32,
-    pub total_time: f32,
+pub struct Times<T = f32> {
+    pub t_j1: T,
+    pub t_j2: T,
+    pub t_d: T,
+    pub t_a: T,
+    pub t_v: T,
+    pub total_time: T,
 }
 
 // TODO: Un-pub
 #[derive(Debug, Default)]
-pub struct Segment {
+pub struct Segment<T = f32> {
     /// Start time of this segment.
-    start_t: f32,
+    start_t: T,
     /// Duration of this segment.
-    t: f32,
+    t: T,
     /// Initial position.
-    q0: f32,
+    q0: T,
     /// Final position.
-    q1: f32,
-    /// Initial velocity.
-    v0: f32,
-    /// Final velocity.
-    v1: f32,
+    q1: T,
 
     /// Total time.
-    total_time: f32,
+    total_time: T,
 
     /// Acceleration time.
-    t_a: f32,
+    t_a: T,
 
     /// Highest velocity reached in this segment.
-    vlim: f32,
-
-    /// Limits provided by the user.
-    lim: Lim,
+    vlim: T,
 }
 
-impl Segment {
-    fn new(q0: f32, q1: f32, v0: f32, v1: f32, lim: &Lim) -> Self {
+impl<T: Real> Segment<T> {
+    /// Plan a segment from rest to rest, or return an error for inputs that can't be planned.
+    ///
+    /// This profile always starts and ends at rest, so any non-zero `v0` or `v1` is rejected. Use
+    /// [`trapezoidal_non_zero`](crate::trapezoidal_non_zero) for moves that don't.
+    pub fn try_new(q0: T, q1: T, v0: T, v1: T, lim: &Lim<T>) -> Result<Self, PlanError<T>> {
+        error::check_finite([q0, q1, v0, v1])?;
+        error::check_limits([lim.vel, lim.acc])?;
+
+        if q0 == q1 {
+            return Err(PlanError::ZeroDisplacement);
+        }
+
+        if v0 != T::zero() || v1 != T::zero() {
+            return Err(PlanError::InfeasibleVelocities);
+        }
+
+        Ok(Self::new(q0, q1, lim))
+    }
+
+    fn new(q0: T, q1: T, lim: &Lim<T>) -> Self {
         // Correct signs for trajectories with negative positions at start and/or end
         let lim = {
             let sign = (q1 - q0).signum();
@@ -96,69 +115,67 @@ impl Segment {
 
         // Max velocity cannot be reached (eq. 3.10)
         if h < v_max.powi(2) / a_max {
-            t_a = f32::sqrt(h / a_max);
-            total_time = 2.0 * t_a;
+            t_a = (h / a_max).sqrt();
+            total_time = T::lit(2.0) * t_a;
             v_max = a_max * t_a;
         }
 
         Self {
-            start_t: 0.0,
+            start_t: T::zero(),
             t: total_time,
             q0,
             q1,
-            v0,
-            v1,
             t_a,
             vlim: v_max,
-            lim,
             total_time,
         }
     }
 
-    pub fn final_pos(&self) -> f32 {
+    pub fn final_pos(&self) -> T {
         self.q1
     }
 
     /// Get trajectory parameters at the given time `t`.
-    fn tp(&self, t: f32) -> Option<Out> {
+    fn tp(&self, t: T) -> Option<Out<T>> {
         let t = t - self.start_t;
+        let two = T::lit(2.0);
 
         // Accel
         if t < self.t_a {
             let a0 = self.q0;
-            let a1 = 0.0;
-            let a2 = self.vlim / (2.0 * self.t_a);
+            let a1 = T::zero();
+            let a2 = self.vlim / (two * self.t_a);
 
             Some(Out {
                 pos: a0 + a1 * t + a2 * t.powi(2),
-                vel: a1 + 2.0 * a2 * t,
-                acc: 2.0 * a2,
-                jerk: 0.0,
+                vel: a1 + two * a2 * t,
+                acc: two * a2,
+                jerk: T::zero(),
             })
         }
         // Coast
         else if t < (self.total_time - self.t_a) {
-            let b0 = self.q0 - (self.vlim * self.t_a) / 2.0;
+            let b0 = self.q0 - (self.vlim * self.t_a) / two;
             let b1 = self.vlim;
 
             Some(Out {
                 pos: b0 + b1 * t,
                 vel: b1,
-                acc: 0.0,
-                jerk: 0.0,
+                acc: T::zero(),
+                jerk: T::zero(),
             })
         }
         // Decel
         else if t <= self.total_time {
-            let c0 = self.q1 - (self.vlim * self.total_time.powi(2)) / (2.0 * self.t_a);
+            let c0 = self.q1 - (self.vlim * self.total_time.powi(2)) / (two * self.t_a);
             let c1 = (self.vlim * self.total_time) / self.t_a;
-            let c2 = -(self.vlim / (2.0 * self.t_a));
+            let c2 = -(self.vlim / (two * self.t_a));
 
             Some(Out {
                 pos: c0 + c1 * t + c2 * t.powi(2),
-                vel: c1 + 2.0 * c2 * t,
-                acc: 2.0 * c2,
-                jerk: 0.0,
+                vel: c1 + two * c2 * t,
+                acc: two * c2,
+                jerk: T::zero(),
             })
         }
         // Out of range
@@ -168,17 +185,57 @@ impl Segment {
     }
 }
 
-pub fn tp(t: f32, q0: f32, q1: f32, v0: f32, v1: f32, lim: &Lim, times: &mut Times) -> (f32, Out) {
-    let segment = Segment::new(q0, q1, v0, v1, &lim);
+impl<T: Real> Profile<T> for Segment<T> {
+    type Out = Out<T>;
+
+    fn start_t(&self) -> T {
+        self.start_t
+    }
+
+    fn duration(&self) -> T {
+        self.total_time
+    }
+
+    fn tp(&self, t: T) -> Option<Out<T>> {
+        if t < self.start_t {
+            return None;
+        }
+
+        Segment::tp(self, t)
+    }
+
+    fn phase_times(&self) -> Vec<T> {
+        profile::phase_times(
+            self.start_t,
+            &[self.t_a, self.total_time - self.t_a * T::lit(2.0), self.t_a],
+        )
+    }
+}
+
+pub fn tp<T: Real>(
+    t: T,
+    q0: T,
+    q1: T,
+    v0: T,
+    v1: T,
+    lim: &Lim<T>,
+    times: &mut Times<T>,
+) -> (T, Out<T>) {
+    // Nothing to plot for inputs that can't be planned
+    let Ok(segment) = Segment::try_new(q0, q1, v0, v1, lim) else {
+        *times = Times::default();
+
+        return (T::zero(), Out::default());
+    };
 
     let total_time = segment.t;
 
     *times = Times {
-        t_j1: 0.0,
-        t_j2: 0.0,
-        t_d: 0.0,
+        t_j1: T::zero(),
+        t_j2: T::zero(),
+        t_d: T::zero(),
         t_a: segment.t_a,
-        t_v: 0.0,
+        t_v: T::zero(),
         total_time,
     };
 
@@ -186,14 +243,12 @@ pub fn tp(t: f32, q0: f32, q1: f32, v0: f32, v1: f32, lim: &Lim, times: &mut Tim
 }
 
 /// Returns a tuple of total trajectory time + segment properties at `t`.
-pub fn tp_seg(t: f32, segments: &[Segment]) -> (f32, Out) {
+pub fn tp_seg<T: Real>(t: T, segments: &[Segment<T>]) -> (T, Out<T>) {
     let mut segs = segments.iter().filter(|segment| {
         // Any segment where start time is less than or equal to `t` AND the segment's end
         // time (s.start_t + s.total_time) is than or equal to `t`
 
-        let in_range = segment.start_t <= t && (segment.start_t + segment.total_time) > t;
-
-        in_range
+        segment.start_t <= t && (segment.start_t + segment.total_time) > t
     });
 
     let num_segs = segs.clone().count();
@@ -232,40 +287,40 @@ pub fn tp_seg(t: f32, segments: &[Segment]) -> (f32, Out) {
     let total_time = segments
         .last()
         .map(|seg| seg.start_t + seg.total_time)
-        .unwrap_or(0.0);
+        .unwrap_or_else(T::zero);
 
     (total_time, outs)
 }
 
 /// Generate test data for multiple segments
-pub fn make_segments(lim: &Lim, enable_overlap: bool) -> Vec<Segment> {
-    let q0 = 0.0;
-    let q1 = 1.0;
-    let q2 = -3.0;
-    let q3 = 5.0;
+pub fn make_segments<T: Real>(lim: &Lim<T>, enable_overlap: bool) -> Vec<Segment<T>> {
+    let q0 = T::zero();
+    let q1 = T::one();
+    let q2 = T::lit(-3.0);
+    let q3 = T::lit(5.0);
 
     // NOTE: Set overlap times to 0 if "come to full stop" option is desired
 
-    let s1 = Segment::new(q0, q1, 0.0, 0.0, &lim);
+    let s1 = Segment::new(q0, q1, lim);
 
-    let mut s2 = Segment::new(q1, q2, 0.0, 0.0, &lim);
+    let mut s2 = Segment::new(q1, q2, lim);
 
     // Disable overlap if desired
     let overlap_time = if !enable_overlap {
-        0.0
+        T::zero()
     } else {
-        f32::min(s1.t_a, s2.t_a)
+        s1.t_a.min(s2.t_a)
     };
 
     s2.start_t = s1.start_t + s1.total_time - overlap_time;
 
-    let mut s3 = Segment::new(q2, q3, 0.0, 0.0, &lim);
+    let mut s3 = Segment::new(q2, q3, lim);
 
     // Disable overlap if desired
     let overlap_time = if !enable_overlap {
-        0.0
+        T::zero()
     } else {
-        f32::min(s2.t_a, s3.t_a)
+        s2.t_a.min(s3.t_a)
     };
 
     s3.start_t = s2.start_t + s2.total_time - overlap_time;
@@ -275,10 +330,43 @@ pub fn make_segments(lim: &Lim, enable_overlap: bool) -> Vec<Segment> {
 
 #[cfg(test)]
 mod tests {
-    // use super::*;
+    use super::*;
 
     #[test]
     fn multi() {
         //
     }
+
+    #[test]
+    fn zero_displacement() {
+        let lim = Lim {
+            vel: 2.0,
+            acc: 5.0,
+            jerk: 0.0,
+        };
+
+        assert!(Segment::try_new(0.0, 1.0, 0.0, 0.0, &lim).is_ok());
+        assert_eq!(
+            Segment::try_new(1.0, 1.0, 0.0, 0.0, &lim).unwrap_err(),
+            PlanError::ZeroDisplacement
+        );
+    }
+
+    #[test]
+    fn non_zero_velocities() {
+        let lim = Lim {
+            vel: 2.0,
+            acc: 5.0,
+            jerk: 0.0,
+        };
+
+        assert_eq!(
+            Segment::try_new(0.0, 1.0, 0.5, 0.0, &lim).unwrap_err(),
+            PlanError::InfeasibleVelocities
+        );
+        assert_eq!(
+            Segment::try_new(0.0, 1.0, 0.0, -0.5, &lim).unwrap_err(),
+            PlanError::InfeasibleVelocities
+        );
+    }
 }
@@ -1,19 +1,26 @@
 //! Trapezoidal trajectory with non-zero initial velocity.
 
+use crate::{
+    error::{self, PlanError},
+    profile::{self, Profile},
+    real::Real,
+};
+use alloc::vec::Vec;
+
 #[derive(Default, Debug, Clone, Copy, PartialEq)]
-pub struct Lim {
-    pub vel: f32,
-    pub acc: f32,
+pub struct Lim<T = f32> {
+    pub vel: T,
+    pub acc: T,
 }
 
 #[derive(Debug, Default, Clone, Copy)]
-pub struct Out {
-    pub pos: f32,
-    pub vel: f32,
-    pub acc: f32,
+pub struct Out<T = f32> {
+    pub pos: T,
+    pub vel: T,
+    pub acc: T,
 }
 
-impl core::ops::Add for Out {
+impl<T: Real> core::ops::Add for Out<T> {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self::Output {
@@ -26,64 +33,78 @@ impl core::ops::Add for Out {
 }
 
 #[derive(Debug, Default, Clone, Copy)]
-pub struct Times {
-    pub t_j1: f32,
-    pub t_j2: f32,
-    pub t_d: f32,
-    pub t_a: f32,
-    pub t_v: f32,
-    pub total_time: f32,
+pub struct Times<T = f32> {
+    pub t_j1: T,
+    pub t_j2: T,
+    pub t_d: T,
+    pub t_a: T,
+    pub t_v: T,
+    pub total_time: T,
 }
 
 // TODO: Un-pub
-#[derive(Debug, Default)]
-pub struct Segment {
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Segment<T = f32> {
     /// Start time of this segment.
-    start_t: f32,
+    start_t: T,
     /// Duration of this segment.
-    pub t: f32,
+    pub t: T,
     /// Initial position.
-    q0: f32,
+    q0: T,
     /// Final position.
-    q1: f32,
+    q1: T,
     /// Initial velocity.
-    v0: f32,
+    v0: T,
     /// Final velocity.
-    v1: f32,
+    v1: T,
 
     /// Total time.
-    total_time: f32,
+    total_time: T,
 
     /// Acceleration time.
-    pub t_a: f32,
+    pub t_a: T,
 
     /// Deceleration time.
-    pub t_d: f32,
+    pub t_d: T,
 
     /// Highest velocity reached in this segment.
-    pub vlim: f32,
+    pub vlim: T,
 
     /// Sign of displacement.
-    sign: f32,
+    sign: T,
 }
 
-impl Segment {
+impl<T: Real> Segment<T> {
     // Compute a trajectory that goes from `q0` to `q1` in the fastest time possible whilst
     // respecting the given limits.
-    pub fn new(q0: f32, q1: f32, v0: f32, v1: f32, lim: &Lim) -> Self {
+    pub fn new(q0: T, q1: T, v0: T, v1: T, lim: &Lim<T>) -> Self {
         let lim = Lim {
             vel: lim.vel.abs(),
             acc: lim.acc.abs(),
         };
 
-        println!("---");
-
         // assert!(
         //     lim.acc > 0.0 && lim.vel > 0.0,
         //     "Limits must all be positive values, got {:?}",
         //     lim
         // );
 
+        let min = Lim {
+            vel: -lim.vel,
+            acc: -lim.acc,
+        };
+
+        Self::new_asymmetric(q0, q1, v0, v1, &lim, &min)
+    }
+
+    /// Like [`new`](Self::new), but with different limits in each direction, e.g. for a Z axis that
+    /// can decelerate harder than it can accelerate against gravity.
+    ///
+    /// `max` holds the upper bound of the velocity and acceleration and `min` the lower bound, so
+    /// both of its values are negative. When moving in the positive direction, `max.acc` limits
+    /// the acceleration phase and `min.acc` the deceleration phase, and the other way around when
+    /// moving in the negative direction.
+    pub fn new_asymmetric(q0: T, q1: T, v0: T, v1: T, max: &Lim<T>, min: &Lim<T>) -> Self {
         let sign = (q1 - q0).signum();
 
         let q0 = sign * q0;
@@ -97,54 +118,91 @@ impl Segment {
         //     lim.vel = v1.min(lim.vel);
         // }
 
-        let Lim {
-            vel: v_max,
-            acc: a_max,
-            ..
-        } = lim;
+        // Limits in the direction of travel (3.31)
+        let (v_max, a_acc, a_dec) = if sign < T::zero() {
+            (-min.vel, -min.acc, max.acc)
+        } else {
+            (max.vel, max.acc, -min.acc)
+        };
 
         // Displacement
         let h = q1 - q0;
 
-        // dbg!(sign, q0, q1, a_max, v_max);
+        let (t_a, t_d, total_time, vlim) = fastest(h, v0, v1, v_max, a_acc, a_dec);
 
-        // Was the given max velocity reached? If so, this segment will contain a cruise phase.
-        let v_lim_reached = {
-            let lhs = h * a_max;
+        Self {
+            start_t: T::zero(),
+            t: total_time,
+            q0,
+            q1,
+            v0,
+            v1,
+            t_a,
+            t_d,
+            vlim,
+            sign,
+            total_time,
+        }
+    }
 
-            let rhs = v_max.powi(2) - (v0.powi(2) + v1.powi(2)) / 2.0;
+    /// Like [`new`](Self::new), but returns an error for inputs that can't be planned instead of
+    /// producing a profile full of NaNs.
+    pub fn try_new(q0: T, q1: T, v0: T, v1: T, lim: &Lim<T>) -> Result<Self, PlanError<T>> {
+        Self::validate(q0, q1, v0, v1, lim)?;
 
-            lhs > rhs
-        };
+        Ok(Self::new(q0, q1, v0, v1, lim))
+    }
 
-        let vlim = if v_lim_reached {
-            // We reached max allowed velocity
-            v_max
-        } else {
-            // Didn't reach max velocity, so reduce it by how much acceleration we can get away with
-            f32::sqrt(h * a_max + (v0.powi(2) + v1.powi(2)) / 2.0)
-        };
+    fn validate(q0: T, q1: T, v0: T, v1: T, lim: &Lim<T>) -> Result<(), PlanError<T>> {
+        error::check_finite([q0, q1, v0, v1])?;
+        error::check_limits([lim.vel, lim.acc])?;
 
-        // dbg!(v_lim_reached, h, a_max);
+        if q0 == q1 {
+            return Err(PlanError::ZeroDisplacement);
+        }
 
-        let t_a = (vlim - v0) / a_max;
-        let t_d = (vlim - v1) / a_max;
+        let sign = (q1 - q0).signum();
 
-        // Total duration of this segment
-        let total_time = if v_lim_reached {
-            h / v_max
-                + v_max / (2.0 * a_max) * (1.0 - v0 / v_max).powi(2)
-                + v_max / (2.0 * a_max) * (1.0 - v1 / v_max).powi(2)
-        } else {
-            // No cruise, so just sum accel + decel
-            t_a + t_d
-        };
+        check_reachable((q1 - q0).abs(), v0 * sign, v1 * sign, lim)
+    }
 
-        dbg!(v0, v1, a_max, t_a, t_d, total_time, vlim, h);
+    /// Compute a trajectory that goes from `q0` to `q1` in exactly `duration` seconds, e.g. to
+    /// meet a conveyor, using as little acceleration as possible.
+    ///
+    /// Returns an error if `duration` is shorter than the fastest possible time from
+    /// [`new`](Self::new), or for any input [`try_new`](Self::try_new) would reject.
+    pub fn with_duration(
+        q0: T,
+        q1: T,
+        v0: T,
+        v1: T,
+        duration: T,
+        lim: &Lim<T>,
+    ) -> Result<Self, PlanError<T>> {
+        Self::validate(q0, q1, v0, v1, lim)?;
+        error::check_finite([duration])?;
+
+        let fastest = Self::new(q0, q1, v0, v1, lim);
+
+        if duration < fastest.total_time {
+            return Err(PlanError::DurationTooShort {
+                duration,
+                min_duration: fastest.total_time,
+            });
+        }
 
-        Self {
-            start_t: 0.0,
-            t: total_time,
+        let sign = if q1 < q0 { -T::one() } else { T::one() };
+
+        let q0 = sign * q0;
+        let q1 = sign * q1;
+        let v0 = sign * v0;
+        let v1 = sign * v1;
+
+        let (t_a, t_d, vlim) = fixed_duration(q1 - q0, v0, v1, duration, lim);
+
+        Ok(Self {
+            start_t: T::zero(),
+            t: duration,
             q0,
             q1,
             v0,
@@ -153,16 +211,16 @@ impl Segment {
             t_d,
             vlim,
             sign,
-            total_time,
-        }
+            total_time: duration,
+        })
     }
 
-    pub fn final_pos(&self) -> f32 {
+    pub fn final_pos(&self) -> T {
         self.q1
     }
 
     /// Get trajectory parameters at the given time `t`.
-    pub fn tp(&self, t: f32) -> Option<Out> {
+    pub fn tp(&self, t: T) -> Option<Out<T>> {
         let Self {
             q0,
             q1,
@@ -174,16 +232,18 @@ impl Segment {
             total_time,
             start_t,
             ..
-        } = self;
+        } = *self;
+
+        let two = T::lit(2.0);
 
         let t0 = start_t;
         let t1 = t0 + total_time;
         let t_delta = t - t0;
 
         // Accel (3.13a)
-        let out = if t_delta < *t_a {
+        let out = if t_delta < t_a {
             Some(Out {
-                pos: q0 + v0 * (t - t0) + (vlim - v0) / (2.0 * t_a) * (t - t0).powi(2),
+                pos: q0 + v0 * (t - t0) + (vlim - v0) / (two * t_a) * (t - t0).powi(2),
                 vel: v0 + (vlim - v0) / t_a * (t - t0),
                 acc: (vlim - v0) / t_a,
             })
@@ -191,15 +251,15 @@ impl Segment {
         // Coast (3.13b)
         else if t_delta < (total_time - t_d) {
             Some(Out {
-                pos: q0 + v0 * t_a / 2.0 + vlim * (t - t0 - t_a / 2.0),
-                vel: *vlim,
-                acc: 0.0,
+                pos: q0 + v0 * t_a / two + vlim * (t - t0 - t_a / two),
+                vel: vlim,
+                acc: T::zero(),
             })
         }
         // Decel (3.13c) (non-inclusive)
-        else if t_delta <= *total_time {
+        else if t_delta <= total_time {
             Some(Out {
-                pos: q1 - v1 * (t1 - t) - (vlim - v1) / (2.0 * t_d) * (t1 - t).powi(2),
+                pos: q1 - v1 * (t1 - t) - (vlim - v1) / (two * t_d) * (t1 - t).powi(2),
                 vel: v1 + (vlim - v1) / t_d * (t1 - t),
                 acc: -(vlim - v1) / t_d,
             })
@@ -216,14 +276,346 @@ impl Segment {
         })
     }
 
-    pub fn times(&self) -> Times {
+    pub fn times(&self) -> Times<T> {
         Times {
-            t_j1: 0.0,
-            t_j2: 0.0,
+            t_j1: T::zero(),
+            t_j2: T::zero(),
             t_d: self.t_d,
             t_a: self.t_a,
-            t_v: 0.0,
+            t_v: T::zero(),
             total_time: self.total_time,
         }
     }
 }
+
+impl<T: Real> Profile<T> for Segment<T> {
+    type Out = Out<T>;
+
+    fn start_t(&self) -> T {
+        self.start_t
+    }
+
+    fn duration(&self) -> T {
+        self.total_time
+    }
+
+    fn tp(&self, t: T) -> Option<Out<T>> {
+        if t < self.start_t {
+            return None;
+        }
+
+        Segment::tp(self, t)
+    }
+
+    fn phase_times(&self) -> Vec<T> {
+        profile::phase_times(
+            self.start_t,
+            &[self.t_a, self.total_time - self.t_a - self.t_d, self.t_d],
+        )
+    }
+}
+
+/// Acceleration time, deceleration time, total time and peak velocity of the fastest profile that
+/// covers the displacement `h`, accelerating at `a_acc` and decelerating at `a_dec` ("Trajectory
+/// with preassigned acceleration and velocity", page 73).
+pub(crate) fn fastest<T: Real>(h: T, v0: T, v1: T, v_max: T, a_acc: T, a_dec: T) -> (T, T, T, T) {
+    let two = T::lit(2.0);
+
+    // Distance covered getting to and from the velocity `v`
+    let ramps =
+        |v: T| (v.powi(2) - v0.powi(2)) / (two * a_acc) + (v.powi(2) - v1.powi(2)) / (two * a_dec);
+
+    // Was the given max velocity reached? If so, this segment will contain a cruise phase.
+    let v_lim_reached = h > ramps(v_max);
+
+    let vlim = if v_lim_reached {
+        // We reached max allowed velocity
+        v_max
+    } else {
+        // Didn't reach max velocity, so reduce it by how much acceleration we can get away with
+        ((two * h * a_acc * a_dec + v0.powi(2) * a_dec + v1.powi(2) * a_acc) / (a_acc + a_dec))
+            .sqrt()
+    };
+
+    let t_a = (vlim - v0) / a_acc;
+    let t_d = (vlim - v1) / a_dec;
+
+    // Total duration of this segment
+    let total_time = if v_lim_reached {
+        t_a + t_d + (h - ramps(v_max)) / v_max
+    } else {
+        // No cruise, so just sum accel + decel
+        t_a + t_d
+    };
+
+    (t_a, t_d, total_time, vlim)
+}
+
+/// Check the boundary velocities are within the limit, and that `v1` can be reached from `v0`
+/// within the displacement `h` ("Trajectory with preassigned acceleration and velocity", page 73).
+pub(crate) fn check_reachable<T: Real>(
+    h: T,
+    v0: T,
+    v1: T,
+    lim: &Lim<T>,
+) -> Result<(), PlanError<T>> {
+    let within_limit = v0.abs() <= lim.vel && v1.abs() <= lim.vel;
+    let reachable = lim.acc * h >= (v0.powi(2) - v1.powi(2)).abs() / T::lit(2.0);
+
+    (within_limit && reachable)
+        .then_some(())
+        .ok_or(PlanError::InfeasibleVelocities)
+}
+
+/// Acceleration time, deceleration time and peak velocity of the profile that covers the
+/// displacement `h` in exactly `duration`, with the lowest acceleration possible. `duration` must
+/// be at least as long as the fastest profile.
+///
+/// The lowest acceleration is found with no cruise phase, accelerating for part of the duration
+/// then decelerating for the rest, or the other way around when the boundary velocities would
+/// overshoot `h`. If that would exceed the
+/// velocity limit, the profile cruises at the limit instead.
+pub(crate) fn fixed_duration<T: Real>(h: T, v0: T, v1: T, duration: T, lim: &Lim<T>) -> (T, T, T) {
+    let two = T::lit(2.0);
+
+    let excess = two * h - duration * (v0 + v1);
+
+    let acc = (excess.abs() + (excess.powi(2) + (duration * (v0 - v1)).powi(2)).sqrt())
+        / duration.powi(2);
+
+    // Constant velocity already covers `h`
+    if acc <= T::zero() {
+        return (duration / two, duration / two, v0);
+    }
+
+    let vlim = if excess >= T::zero() {
+        (v0 + v1 + acc * duration) / two
+    } else {
+        (v0 + v1 - acc * duration) / two
+    };
+
+    if vlim > lim.vel {
+        let vlim = lim.vel;
+        let acc = ((vlim - v0).powi(2) + (vlim - v1).powi(2)) / (two * (vlim * duration - h));
+
+        return ((vlim - v0) / acc, (vlim - v1) / acc, vlim);
+    }
+
+    ((vlim - v0).abs() / acc, (vlim - v1).abs() / acc, vlim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(segment: &Segment<f64>, q1: f64, v0: f64, v1: f64, duration: f64, lim: &Lim<f64>) {
+        assert!((segment.total_time - duration).abs() < 1.0e-9);
+
+        let start = segment.tp(0.0).unwrap();
+        let end = segment.tp(duration).unwrap();
+
+        assert!((start.vel - v0).abs() < 1.0e-9);
+        assert!((end.pos - q1).abs() < 1.0e-9, "{end:?}");
+        assert!((end.vel - v1).abs() < 1.0e-9, "{end:?}");
+
+        for i in 0..=100 {
+            let out = segment.tp(duration * i as f64 / 100.0).unwrap();
+
+            assert!(out.vel.abs() <= lim.vel + 1.0e-9, "{out:?}");
+            assert!(out.acc.abs() <= lim.acc + 1.0e-9, "{out:?}");
+        }
+    }
+
+    #[test]
+    fn fixed_duration_from_rest() {
+        let lim = Lim {
+            vel: 10.0,
+            acc: 10.0,
+        };
+
+        let segment = Segment::with_duration(0.0, 10.0, 0.0, 0.0, 4.0, &lim).unwrap();
+
+        check(&segment, 10.0, 0.0, 0.0, 4.0, &lim);
+
+        // Triangular, with the lowest possible acceleration
+        assert!((segment.tp(0.0).unwrap().acc - 2.5).abs() < 1.0e-9);
+        assert!((segment.vlim - 5.0).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn fixed_duration_velocity_limit() {
+        let lim = Lim {
+            vel: 4.0,
+            acc: 10.0,
+        };
+
+        let segment = Segment::with_duration(0.0, 10.0, 0.0, 0.0, 3.0, &lim).unwrap();
+
+        check(&segment, 10.0, 0.0, 0.0, 3.0, &lim);
+
+        assert_eq!(segment.vlim, 4.0);
+    }
+
+    #[test]
+    fn fixed_duration_slow_down() {
+        let lim = Lim {
+            vel: 10.0,
+            acc: 10.0,
+        };
+
+        // Entering and leaving too fast to take this long, so slow down in the middle
+        let segment = Segment::with_duration(0.0, 5.0, 4.0, 3.0, 2.0, &lim).unwrap();
+
+        check(&segment, 5.0, 4.0, 3.0, 2.0, &lim);
+
+        assert!(segment.vlim < 3.0);
+
+        // Backwards
+        let segment = Segment::with_duration(0.0, -5.0, -4.0, -3.0, 2.0, &lim).unwrap();
+
+        check(&segment, -5.0, -4.0, -3.0, 2.0, &lim);
+    }
+
+    #[test]
+    fn fixed_duration_too_short() {
+        let lim = Lim {
+            vel: 10.0,
+            acc: 10.0,
+        };
+
+        let fastest = Segment::new(0.0, 10.0, 0.0, 0.0, &lim);
+
+        assert_eq!(
+            Segment::with_duration(0.0, 10.0, 0.0, 0.0, 1.0, &lim).unwrap_err(),
+            PlanError::DurationTooShort {
+                duration: 1.0,
+                min_duration: fastest.total_time
+            }
+        );
+
+        // Exactly the fastest time gives the fastest profile
+        let segment =
+            Segment::with_duration(0.0, 10.0, 0.0, 0.0, fastest.total_time, &lim).unwrap();
+
+        check(&segment, 10.0, 0.0, 0.0, fastest.total_time, &lim);
+    }
+
+    #[test]
+    fn asymmetric_limits() {
+        let max = Lim { vel: 4.0, acc: 2.0 };
+        let min = Lim {
+            vel: -6.0,
+            acc: -8.0,
+        };
+
+        let within = |segment: &Segment<f64>| {
+            for i in 0..=100 {
+                let out = segment.tp(segment.total_time * i as f64 / 100.0).unwrap();
+
+                assert!(out.vel <= max.vel + 1.0e-9 && out.vel >= min.vel - 1.0e-9);
+                assert!(out.acc <= max.acc + 1.0e-9 && out.acc >= min.acc - 1.0e-9);
+            }
+        };
+
+        // Up: accelerate gently and stop hard
+        let segment = Segment::new_asymmetric(0.0, 20.0, 0.0, 0.0, &max, &min);
+
+        within(&segment);
+
+        assert_eq!(segment.t_a, 2.0);
+        assert_eq!(segment.t_d, 0.5);
+        assert!((segment.total_time - 6.25).abs() < 1.0e-9);
+        assert!((segment.tp(segment.total_time).unwrap().pos - 20.0).abs() < 1.0e-9);
+
+        // Down: accelerate hard, cruise faster and stop gently
+        let segment = Segment::new_asymmetric(20.0, 0.0, 0.0, 0.0, &max, &min);
+
+        within(&segment);
+
+        assert_eq!(segment.vlim, 6.0);
+        assert_eq!(segment.t_a, 0.75);
+        assert_eq!(segment.t_d, 3.0);
+        assert!((segment.total_time - (3.75 + 8.75 / 6.0)).abs() < 1.0e-9);
+        assert!(segment.tp(segment.total_time).unwrap().pos.abs() < 1.0e-9);
+
+        // Too short to cruise, with non-zero boundary velocities
+        let segment = Segment::new_asymmetric(0.0, 2.0, 1.0, 0.5, &max, &min);
+
+        within(&segment);
+
+        let end = segment.tp(segment.total_time).unwrap();
+
+        assert!(segment.vlim < max.vel);
+        assert!((end.pos - 2.0).abs() < 1.0e-9);
+        assert!((end.vel - 0.5).abs() < 1.0e-9);
+
+        // Same as `new` when symmetrical
+        let lim = Lim::<f64> { vel: 4.0, acc: 2.0 };
+        let symmetrical = Segment::new_asymmetric(
+            0.0,
+            3.0,
+            1.0,
+            0.5,
+            &lim,
+            &Lim {
+                vel: -4.0,
+                acc: -2.0,
+            },
+        );
+        let segment = Segment::new(0.0, 3.0, 1.0, 0.5, &lim);
+
+        assert!((symmetrical.total_time - segment.total_time).abs() < 1.0e-12);
+        assert!((symmetrical.vlim - segment.vlim).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn invalid_inputs() {
+        let lim = Lim {
+            vel: 10.0,
+            acc: 10.0,
+        };
+
+        assert!(Segment::try_new(0.0, 10.0, 0.0, 0.0, &lim).is_ok());
+        assert!(Segment::try_new(0.0, -10.0, -2.0, 0.0, &lim).is_ok());
+
+        assert_eq!(
+            Segment::try_new(0.0, f32::NAN, 0.0, 0.0, &lim).unwrap_err(),
+            PlanError::NonFinite
+        );
+        assert_eq!(
+            Segment::try_new(
+                0.0,
+                10.0,
+                0.0,
+                0.0,
+                &Lim {
+                    vel: 10.0,
+                    acc: 0.0
+                }
+            )
+            .unwrap_err(),
+            PlanError::InvalidLimit
+        );
+        assert_eq!(
+            Segment::try_new(1.0, 1.0, 0.0, 0.0, &lim).unwrap_err(),
+            PlanError::ZeroDisplacement
+        );
+
+        // Over the velocity limit
+        assert_eq!(
+            Segment::try_new(0.0, 10.0, 11.0, 0.0, &lim).unwrap_err(),
+            PlanError::InfeasibleVelocities
+        );
+
+        // Can't stop in time
+        assert_eq!(
+            Segment::try_new(0.0, 1.0, 10.0, 0.0, &lim).unwrap_err(),
+            PlanError::InfeasibleVelocities
+        );
+
+        assert_eq!(
+            Segment::with_duration(0.0, 10.0, 0.0, 0.0, f32::INFINITY, &lim).unwrap_err(),
+            PlanError::NonFinite
+        );
+    }
+}
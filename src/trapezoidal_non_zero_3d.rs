@@ -1,23 +1,72 @@
 //! Trapezoidal trajectory with non-zero initial velocity.
 
-use nalgebra::Vector3;
+use crate::{
+    error::{self, PlanError},
+    kinematics::{self, Kinematics, LimitError},
+    profile::{self, Profile},
+    real::Real,
+    trapezoidal_non_zero,
+};
+use alloc::vec::Vec;
+use nalgebra::SVector;
+
+/// Position, velocity, etc for `N` axes.
+pub type Coord<const N: usize, T = f32> = SVector<T, N>;
+
+pub type Coord3 = Coord<3>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lim<const N: usize = 3, T: Real = f32> {
+    pub vel: Coord<N, T>,
+    pub acc: Coord<N, T>,
+}
 
-pub type Coord3 = Vector3<f32>;
+// `Default` can't be derived for any `N` as nalgebra requires `[T; N]: Default`.
+impl<const N: usize, T: Real> Default for Lim<N, T> {
+    fn default() -> Self {
+        Self {
+            vel: Coord::zeros(),
+            acc: Coord::zeros(),
+        }
+    }
+}
+
+impl<const N: usize, T: Real> Lim<N, T> {
+    /// Component-wise minimum of two sets of limits.
+    pub fn inf(&self, other: &Self) -> Self {
+        Self {
+            vel: self.vel.inf(&other.vel),
+            acc: self.acc.inf(&other.acc),
+        }
+    }
 
-#[derive(Default, Debug, Clone, Copy, PartialEq)]
-pub struct Lim {
-    pub vel: Coord3,
-    pub acc: Coord3,
+    /// The same limits in the opposite direction.
+    fn mirrored(&self) -> Self {
+        Self {
+            vel: -self.vel,
+            acc: -self.acc,
+        }
+    }
 }
 
-#[derive(Debug, Default, Clone, Copy)]
-pub struct Out {
-    pub pos: Coord3,
-    pub vel: Coord3,
-    pub acc: Coord3,
+#[derive(Debug, Clone, Copy)]
+pub struct Out<const N: usize = 3, T: Real = f32> {
+    pub pos: Coord<N, T>,
+    pub vel: Coord<N, T>,
+    pub acc: Coord<N, T>,
 }
 
-impl core::ops::Add for Out {
+impl<const N: usize, T: Real> Default for Out<N, T> {
+    fn default() -> Self {
+        Self {
+            pos: Coord::zeros(),
+            vel: Coord::zeros(),
+            acc: Coord::zeros(),
+        }
+    }
+}
+
+impl<const N: usize, T: Real> core::ops::Add for Out<N, T> {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self::Output {
@@ -39,42 +88,102 @@ impl core::ops::Add for Out {
 //     pub total_time: f32,
 // }
 
-#[derive(Debug, Default)]
-pub struct Segment {
+#[derive(Debug)]
+pub struct Segment<const N: usize = 3, T: Real = f32> {
     /// Start time of this segment.
-    pub start_t: f32,
+    pub start_t: T,
     /// Initial position.
-    q0: Coord3,
+    q0: Coord<N, T>,
     /// Final position.
-    q1: Coord3,
+    q1: Coord<N, T>,
     /// Initial velocity.
-    v0: Coord3,
+    v0: Coord<N, T>,
     /// Final velocity.
-    v1: Coord3,
+    v1: Coord<N, T>,
 
     /// Total time.
-    pub total_time: f32,
+    pub total_time: T,
 
     /// Acceleration time.
-    t_a: f32,
+    t_a: T,
 
     /// Deceleration time.
-    t_d: f32,
+    t_d: T,
 
     /// Highest velocity reached in this segment.
-    vlim: Coord3,
+    vlim: Coord<N, T>,
 
     /// Sign of displacement.
-    sign: Coord3,
+    sign: Coord<N, T>,
+
+    /// Tightest symmetrical limits that satisfy the limits provided by the user.
+    lim: Lim<N, T>,
 }
 
-impl Segment {
-    pub fn new(q0: Coord3, q1: Coord3, v0: Coord3, v1: Coord3, start_t: f32, lim: &Lim) -> Self {
-        assert!(
-            lim.acc > Coord3::zeros() && lim.vel > Coord3::zeros(),
-            "Limits must all be positive values, got {:?}",
-            lim
-        );
+impl<const N: usize, T: Real> Default for Segment<N, T> {
+    fn default() -> Self {
+        Self {
+            start_t: T::zero(),
+            q0: Coord::zeros(),
+            q1: Coord::zeros(),
+            v0: Coord::zeros(),
+            v1: Coord::zeros(),
+            total_time: T::zero(),
+            t_a: T::zero(),
+            t_d: T::zero(),
+            vlim: Coord::zeros(),
+            sign: Coord::zeros(),
+            lim: Lim::default(),
+        }
+    }
+}
+
+impl<const N: usize, T: Real> Segment<N, T> {
+    /// Panics if any limit isn't a finite positive value. Use [`try_new`](Self::try_new) to check
+    /// the rest of the inputs too.
+    pub fn new(
+        q0: Coord<N, T>,
+        q1: Coord<N, T>,
+        v0: Coord<N, T>,
+        v1: Coord<N, T>,
+        start_t: T,
+        lim: &Lim<N, T>,
+    ) -> Self {
+        Self::new_asymmetric(q0, q1, v0, v1, start_t, lim, &lim.mirrored())
+    }
+
+    /// Like [`new`](Self::new), but with different limits in each direction on every axis, e.g.
+    /// for a Z axis that can decelerate harder than it can accelerate against gravity.
+    ///
+    /// `max` holds the upper bounds of each axis and `min` the lower bounds, so all of its values
+    /// are negative. See [`trapezoidal_non_zero::Segment::new_asymmetric`] for how they apply to
+    /// the acceleration and deceleration phases.
+    pub fn new_asymmetric(
+        q0: Coord<N, T>,
+        q1: Coord<N, T>,
+        v0: Coord<N, T>,
+        v1: Coord<N, T>,
+        start_t: T,
+        max: &Lim<N, T>,
+        min: &Lim<N, T>,
+    ) -> Self {
+        check_asymmetric_limits(max, min)
+            .expect("Upper limits must all be positive and lower limits negative");
+
+        Self::plan(q0, q1, v0, v1, start_t, max, min)
+    }
+
+    /// Fastest profile within the given limits, which must already have been checked.
+    fn plan(
+        q0: Coord<N, T>,
+        q1: Coord<N, T>,
+        v0: Coord<N, T>,
+        v1: Coord<N, T>,
+        start_t: T,
+        max: &Lim<N, T>,
+        min: &Lim<N, T>,
+    ) -> Self {
+        let user_lim = max.inf(&min.mirrored());
 
         let sign = (q1 - q0).map(|axis| axis.signum());
         // let sign = Coord3::new(1.0, 1.0, 1.0);
@@ -87,73 +196,56 @@ impl Segment {
         // Displacement
         let h = q1 - q0;
 
-        let largest_axis = h.abs().imax();
-
-        // "Trajectory with preassigned acceleration and velocity", page 73
-        let process_axis = |axis: usize, limits: &Lim| {
-            let h = h[axis];
-            let a_max = limits.acc[axis];
-            let v_max = limits.vel[axis];
-            let v0 = v0[axis];
-            let v1 = v1[axis];
-
-            // Was the given max velocity reached? If so, this segment will contain a cruise phase.
-            let v_lim_reached = {
-                let lhs = h * a_max;
-
-                let rhs = v_max.powi(2) - (v0.powi(2) + v1.powi(2)) / 2.0;
-
-                lhs > rhs
-            };
-
-            let vlim = if v_lim_reached {
-                // We reached max allowed velocity
-                v_max
-            } else {
-                // Didn't reach max velocity, so reduce it by how much acceleration we can get away with
-                f32::sqrt(h * a_max + (v0.powi(2) + v1.powi(2)) / 2.0)
-            };
-
-            // dbg!(v_lim_reached, h, a_max);
+        let two = T::lit(2.0);
 
-            let t_a = (vlim - v0) / a_max;
-            let t_d = (vlim - v1) / a_max;
+        let largest_axis = h.abs().imax();
 
-            // Total duration of this segment
-            let total_time = if v_lim_reached {
-                h / v_max
-                    + v_max / (2.0 * a_max) * (1.0 - v0 / v_max).powi(2)
-                    + v_max / (2.0 * a_max) * (1.0 - v1 / v_max).powi(2)
+        // Every axis moves in proportion to the largest one, so its limits in its direction of
+        // travel (3.31) scale by its share of the displacement. The tightest of them all sets the
+        // limits of the largest axis, so a minor axis with tight limits in one direction is the
+        // one that sets the timing if it would take longer than the rest.
+        let axis_lim = |axis: usize| {
+            if sign[axis] < T::zero() {
+                (-min.vel[axis], -min.acc[axis], max.acc[axis])
             } else {
-                // No cruise, so just sum accel + decel
-                t_a + t_d
-            };
-
-            // dbg!(v0, v1, a_max, t_a, t_d, total_time, vlim, h);
-            (t_a, t_d, total_time, vlim)
-        };
-
-        let (largest_axis_accel_time, largest_axis_decel_time, largest_axis_total_time, _) =
-            process_axis(largest_axis, &lim);
-
-        // Compute new limits based on largest axis. This synchronises all other axes.
-        let lim = {
-            Lim {
-                vel: h.map(|axis| axis / (largest_axis_total_time - largest_axis_accel_time)),
-                acc: h.map(|axis| {
-                    axis / (largest_axis_accel_time
-                        * (largest_axis_total_time - largest_axis_accel_time))
-                }),
+                (max.vel[axis], max.acc[axis], -min.acc[axis])
             }
         };
 
-        let mut vlim = Coord3::zeros();
+        let (v_max, a_acc, a_dec) = (0..N).filter(|axis| h[*axis] > T::zero()).fold(
+            axis_lim(largest_axis),
+            |(v_max, a_acc, a_dec), axis| {
+                let (vel, acc, dec) = axis_lim(axis);
+                let scale = h[largest_axis] / h[axis];
+
+                (
+                    v_max.min(vel * scale),
+                    a_acc.min(acc * scale),
+                    a_dec.min(dec * scale),
+                )
+            },
+        );
 
-        for i in 0..q0.len() {
-            let (_, _, _, limit) = process_axis(i, &lim);
+        let (t_a, t_d, total_time, _) = trapezoidal_non_zero::fastest(
+            h[largest_axis],
+            v0[largest_axis],
+            v1[largest_axis],
+            v_max,
+            a_acc,
+            a_dec,
+        );
 
-            vlim[i] = limit;
-        }
+        // Every other axis shares the accel, cruise and decel times of the largest axis. The
+        // displacement is the area under the velocity profile (eq. 3.13), so solve that for the
+        // cruise velocity of each axis. This also holds for non-zero initial and final velocities.
+        let denominator = total_time - (t_a + t_d) / two;
+
+        // Zero length segments don't move at all
+        let vlim = if denominator > T::zero() {
+            (h - (v0 * t_a + v1 * t_d) / two) / denominator
+        } else {
+            Coord::zeros()
+        };
 
         Self {
             start_t,
@@ -161,16 +253,110 @@ impl Segment {
             q1,
             v0,
             v1,
-            total_time: largest_axis_total_time,
-            t_a: largest_axis_accel_time,
-            t_d: largest_axis_decel_time,
+            total_time,
+            t_a,
+            t_d,
             vlim,
             sign,
+            lim: user_lim,
+        }
+    }
+
+    /// Like [`new`](Self::new), but returns an error for inputs that can't be planned instead of
+    /// panicking or producing a profile full of NaNs.
+    pub fn try_new(
+        q0: Coord<N, T>,
+        q1: Coord<N, T>,
+        v0: Coord<N, T>,
+        v1: Coord<N, T>,
+        start_t: T,
+        lim: &Lim<N, T>,
+    ) -> Result<Self, PlanError<T>> {
+        Self::try_new_asymmetric(q0, q1, v0, v1, start_t, lim, &lim.mirrored())
+    }
+
+    /// Like [`new_asymmetric`](Self::new_asymmetric), but returns an error for inputs that can't
+    /// be planned instead of panicking or producing a profile full of NaNs.
+    ///
+    /// Boundary velocities are checked against the tightest of the limits in either direction.
+    pub fn try_new_asymmetric(
+        q0: Coord<N, T>,
+        q1: Coord<N, T>,
+        v0: Coord<N, T>,
+        v1: Coord<N, T>,
+        start_t: T,
+        max: &Lim<N, T>,
+        min: &Lim<N, T>,
+    ) -> Result<Self, PlanError<T>> {
+        check_asymmetric_limits(max, min)?;
+
+        let lim = max.inf(&min.mirrored());
+
+        validate(q0, q1, v0, v1, start_t, &lim.vel, &lim.acc)?;
+
+        Ok(Self::plan(q0, q1, v0, v1, start_t, max, min))
+    }
+
+    /// Plan a segment that goes from `q0` to `q1` in exactly `duration` seconds, e.g. to meet a
+    /// conveyor or another machine, using as little acceleration as possible.
+    ///
+    /// The axis that moves furthest gets the lowest acceleration profile that takes `duration`, and
+    /// every other axis is synchronised to it like in [`new`](Self::new). Returns an error if
+    /// `duration` is shorter than the fastest possible time, or for any input
+    /// [`try_new`](Self::try_new) would reject.
+    pub fn with_duration(
+        q0: Coord<N, T>,
+        q1: Coord<N, T>,
+        v0: Coord<N, T>,
+        v1: Coord<N, T>,
+        start_t: T,
+        duration: T,
+        lim: &Lim<N, T>,
+    ) -> Result<Self, PlanError<T>> {
+        validate(q0, q1, v0, v1, start_t, &lim.vel, &lim.acc)?;
+        error::check_finite([duration])?;
+
+        let fastest = Self::new(q0, q1, v0, v1, start_t, lim);
+
+        if duration < fastest.total_time {
+            return Err(PlanError::DurationTooShort {
+                duration,
+                min_duration: fastest.total_time,
+            });
         }
+
+        let Self { q0, q1, v0, v1, .. } = fastest;
+
+        let two = T::lit(2.0);
+
+        let h = q1 - q0;
+        let largest_axis = h.abs().imax();
+
+        let (t_a, t_d, _) = trapezoidal_non_zero::fixed_duration(
+            h[largest_axis],
+            v0[largest_axis],
+            v1[largest_axis],
+            duration,
+            &trapezoidal_non_zero::Lim {
+                vel: lim.vel[largest_axis],
+                acc: lim.acc[largest_axis],
+            },
+        );
+
+        // Same as `new`, every axis shares the times of the largest one
+        let vlim = (h - (v0 * t_a + v1 * t_d) / two) / (duration - (t_a + t_d) / two);
+
+        Ok(Self {
+            total_time: duration,
+            t_a,
+            t_d,
+            vlim,
+            ..fastest
+        })
     }
 
     /// Get trajectory parameters at the given time `t`.
-    pub fn tp(&self, t: f32) -> Option<(Out, Phase)> {
+    pub fn tp(&self, t: T) -> Option<(Out<N, T>, Phase)> {
         let Self {
             q0,
             q1,
@@ -184,18 +370,30 @@ impl Segment {
             ..
         } = *self;
 
+        let two = T::lit(2.0);
+
         let t0 = start_t;
         let t1 = t0 + total_time;
         let t_delta = t - t0;
 
         let mut phase = Phase::Accel;
 
+        // No accel phase, e.g. a line that starts at full speed, so this is before the start
+        let out = if t_delta < t_a && t_a <= T::zero() {
+            phase = Phase::Accel;
+
+            Some(Out {
+                pos: q0,
+                vel: v0,
+                acc: Coord::zeros(),
+            })
+        }
         // Accel (3.13a)
-        let out = if t_delta < t_a {
+        else if t_delta < t_a {
             phase = Phase::Accel;
 
             Some(Out {
-                pos: q0 + v0 * (t - t0) + (vlim - v0) / (2.0 * t_a) * (t - t0).powi(2),
+                pos: q0 + v0 * (t - t0) + (vlim - v0) / (two * t_a) * (t - t0).powi(2),
                 vel: v0 + (vlim - v0) / t_a * (t - t0),
                 acc: (vlim - v0) / t_a,
             })
@@ -205,17 +403,27 @@ impl Segment {
             phase = Phase::Cruise;
 
             Some(Out {
-                pos: q0 + v0 * t_a / 2.0 + vlim * (t - t0 - t_a / 2.0),
+                pos: q0 + v0 * t_a / two + vlim * (t - t0 - t_a / two),
                 vel: vlim,
-                acc: Coord3::zeros(),
+                acc: Coord::zeros(),
+            })
+        }
+        // No decel phase, e.g. a line that ends at full speed, so this is the very end
+        else if t <= t1 && t_d <= T::zero() {
+            phase = Phase::Decel;
+
+            Some(Out {
+                pos: q1,
+                vel: v1,
+                acc: Coord::zeros(),
             })
         }
         // Decel (3.13c) (non-inclusive)
-        else if t_delta <= total_time {
+        else if t <= t1 {
             phase = Phase::Decel;
 
             Some(Out {
-                pos: q1 - v1 * (t1 - t) - (vlim - v1) / (2.0 * t_d) * (t1 - t).powi(2),
+                pos: q1 - v1 * (t1 - t) - (vlim - v1) / (two * t_d) * (t1 - t).powi(2),
                 vel: v1 + (vlim - v1) / t_d * (t1 - t),
                 acc: -(vlim - v1) / t_d,
             })
@@ -237,21 +445,126 @@ impl Segment {
         })
     }
 
-    pub fn q0(&self) -> Coord3 {
+    pub fn q0(&self) -> Coord<N, T> {
         self.q0.component_mul(&self.sign)
     }
 
-    pub fn q1(&self) -> Coord3 {
+    pub fn q1(&self) -> Coord<N, T> {
         self.q1.component_mul(&self.sign)
     }
 
-    pub fn v0(&self) -> Coord3 {
+    pub fn v0(&self) -> Coord<N, T> {
         self.v0.component_mul(&self.sign)
     }
 
-    pub fn v1(&self) -> Coord3 {
+    pub fn v1(&self) -> Coord<N, T> {
         self.v1.component_mul(&self.sign)
     }
+
+    pub fn lim(&self) -> Lim<N, T> {
+        self.lim
+    }
+
+    /// Check this segment against the joint limits of the given kinematics, sampling every `dt`
+    /// seconds.
+    pub fn check_joint_limits<K: Kinematics<N, T> + ?Sized>(
+        &self,
+        kinematics: &K,
+        dt: T,
+    ) -> Result<(), LimitError<N, T>> {
+        kinematics::check_limits(
+            kinematics,
+            self.start_t,
+            self.start_t + self.total_time,
+            dt,
+            |t| self.tp(t).map(|(out, _phase)| out),
+        )
+    }
+}
+
+impl<const N: usize, T: Real> Profile<T> for Segment<N, T> {
+    type Out = Out<N, T>;
+
+    fn start_t(&self) -> T {
+        self.start_t
+    }
+
+    fn duration(&self) -> T {
+        self.total_time
+    }
+
+    fn tp(&self, t: T) -> Option<Out<N, T>> {
+        if t < self.start_t {
+            return None;
+        }
+
+        Segment::tp(self, t).map(|(out, _phase)| out)
+    }
+
+    fn phase_times(&self) -> Vec<T> {
+        profile::phase_times(
+            self.start_t,
+            &[self.t_a, self.total_time - self.t_a - self.t_d, self.t_d],
+        )
+    }
+}
+
+/// Check the inputs of a multi-axis trapezoidal segment with velocity limits `vel` and
+/// acceleration limits `acc`, treating each axis like a single axis segment.
+/// Check every upper limit is positive and every lower limit negative.
+fn check_asymmetric_limits<const N: usize, T: Real>(
+    max: &Lim<N, T>,
+    min: &Lim<N, T>,
+) -> Result<(), PlanError<T>> {
+    let min = min.mirrored();
+
+    error::check_limits(
+        max.vel
+            .iter()
+            .chain(max.acc.iter())
+            .chain(min.vel.iter())
+            .chain(min.acc.iter())
+            .copied(),
+    )
+}
+
+pub(crate) fn validate<const N: usize, T: Real>(
+    q0: Coord<N, T>,
+    q1: Coord<N, T>,
+    v0: Coord<N, T>,
+    v1: Coord<N, T>,
+    start_t: T,
+    vel: &Coord<N, T>,
+    acc: &Coord<N, T>,
+) -> Result<(), PlanError<T>> {
+    error::check_finite(
+        q0.iter()
+            .chain(q1.iter())
+            .chain(v0.iter())
+            .chain(v1.iter())
+            .copied()
+            .chain([start_t]),
+    )?;
+    error::check_limits(vel.iter().chain(acc.iter()).copied())?;
+
+    if q0 == q1 {
+        return Err(PlanError::ZeroDisplacement);
+    }
+
+    (0..N).try_for_each(|axis| {
+        let h = q1[axis] - q0[axis];
+        let sign = if h < T::zero() { -T::one() } else { T::one() };
+
+        trapezoidal_non_zero::check_reachable(
+            h.abs(),
+            v0[axis] * sign,
+            v1[axis] * sign,
+            &trapezoidal_non_zero::Lim {
+                vel: vel[axis],
+                acc: acc[axis],
+            },
+        )
+    })
 }
 
 pub enum Phase {
@@ -280,4 +593,346 @@ mod tests {
 
         dbg!(seg);
     }
+
+    #[test]
+    fn five_axes() {
+        let q0 = Coord::<5>::zeros();
+        let q1 = Coord::<5>::from([10.0, -15.0, 20.0, 5.0, 0.0]);
+
+        let lim = Lim {
+            vel: Coord::repeat(2.0),
+            acc: Coord::repeat(5.0),
+        };
+
+        let seg = Segment::new(q0, q1, Coord::zeros(), Coord::zeros(), 0.0, &lim);
+
+        let (end, _) = seg.tp(seg.total_time).unwrap();
+
+        assert!((end.pos - q1).norm() < 1.0e-3);
+
+        // Every axis moves in proportion to its displacement so all of them finish together
+        for i in 0..=100 {
+            let t = seg.total_time * i as f32 / 100.0;
+
+            let (out, _) = seg.tp(t).unwrap();
+
+            let progress = out
+                .pos
+                .component_div(&q1.map(|q| if q == 0.0 { 1.0 } else { q }));
+
+            for axis in [1, 2, 3] {
+                assert!(
+                    (progress[axis] - progress[0]).abs() < 1.0e-3,
+                    "{t}: {progress}"
+                );
+            }
+
+            assert_eq!(out.pos[4], 0.0);
+            assert!(out.vel.abs() <= lim.vel.map(|v| v * 1.001));
+        }
+    }
+
+    #[test]
+    fn f64_long_job() {
+        // Over 11 days into a job, where `f32` can only resolve time to 1/16th of a second
+        let start_t = 1.0e6;
+
+        let q0 = Coord::<3, f64>::new(0.0, 0.0, 0.0);
+        let q1 = Coord::<3, f64>::new(10.0, 15.0, 20.0);
+
+        let lim = Lim {
+            vel: Coord::repeat(2.0),
+            acc: Coord::repeat(5.0),
+        };
+
+        let seg = Segment::new(q0, q1, Coord::zeros(), Coord::zeros(), 0.0, &lim);
+        let late_seg = Segment::new(q0, q1, Coord::zeros(), Coord::zeros(), start_t, &lim);
+
+        for i in 0..100 {
+            let t = seg.total_time * i as f64 / 100.0;
+
+            let (out, _) = seg.tp(t).unwrap();
+            let (late_out, _) = late_seg.tp(start_t + t).unwrap();
+
+            assert!((out.pos - late_out.pos).norm() < 1.0e-6);
+        }
+    }
+
+    #[test]
+    fn fixed_duration() {
+        let q0 = Coord::<3, f64>::new(1.0, 2.0, 3.0);
+        let q1 = Coord::<3, f64>::new(11.0, -3.0, 3.0);
+        let v0 = Coord::<3, f64>::new(1.0, -0.5, 0.0);
+
+        let lim = Lim {
+            vel: Coord::repeat(5.0),
+            acc: Coord::repeat(5.0),
+        };
+
+        let fastest = Segment::new(q0, q1, v0, Coord::zeros(), 0.0, &lim);
+
+        assert_eq!(
+            Segment::with_duration(q0, q1, v0, Coord::zeros(), 0.0, 1.0, &lim).unwrap_err(),
+            PlanError::DurationTooShort {
+                duration: 1.0,
+                min_duration: fastest.total_time
+            }
+        );
+
+        for duration in [fastest.total_time, 4.0, 10.0] {
+            let seg =
+                Segment::with_duration(q0, q1, v0, Coord::zeros(), 2.0, duration, &lim).unwrap();
+
+            assert_eq!(seg.total_time, duration);
+
+            let (start, _) = seg.tp(2.0).unwrap();
+            let (end, _) = seg.tp(2.0 + duration).unwrap();
+
+            assert!((start.pos - q0).norm() < 1.0e-9);
+            assert!((start.vel - v0).norm() < 1.0e-9);
+            assert!((end.pos - q1).norm() < 1.0e-9, "{duration}: {}", end.pos);
+            assert!(end.vel.norm() < 1.0e-9);
+
+            for i in 0..=100 {
+                let (out, _) = seg.tp(2.0 + duration * i as f64 / 100.0).unwrap();
+
+                assert!(out.vel.abs() <= lim.vel.add_scalar(1.0e-9), "{}", out.vel);
+                assert!(out.acc.abs() <= lim.acc.add_scalar(1.0e-9), "{}", out.acc);
+            }
+        }
+    }
+
+    #[test]
+    fn asymmetric_limits() {
+        let max = Lim {
+            vel: Coord::<3, f64>::new(5.0, 5.0, 2.0),
+            acc: Coord::<3, f64>::new(10.0, 10.0, 2.0),
+        };
+        let min = Lim {
+            vel: Coord::<3, f64>::new(-5.0, -5.0, -4.0),
+            acc: Coord::<3, f64>::new(-10.0, -10.0, -8.0),
+        };
+
+        let q0 = Coord::<3, f64>::new(1.0, 0.0, 0.0);
+
+        // Z moves the furthest, so its limits in the direction of travel set the timing
+        for q1 in [
+            Coord::<3, f64>::new(2.0, 1.0, 10.0),
+            Coord::<3, f64>::new(2.0, 1.0, -10.0),
+        ] {
+            let seg =
+                Segment::new_asymmetric(q0, q1, Coord::zeros(), Coord::zeros(), 0.0, &max, &min);
+
+            let (end, _) = seg.tp(seg.total_time).unwrap();
+
+            assert!((end.pos - q1).norm() < 1.0e-9);
+            assert!(end.vel.norm() < 1.0e-9);
+
+            for i in 0..=100 {
+                let (out, _) = seg.tp(seg.total_time * i as f64 / 100.0).unwrap();
+
+                assert!(out.vel[2] <= max.vel[2] + 1.0e-9 && out.vel[2] >= min.vel[2] - 1.0e-9);
+                assert!(out.acc[2] <= max.acc[2] + 1.0e-9 && out.acc[2] >= min.acc[2] - 1.0e-9);
+            }
+
+            let (t_a, t_d) = if q1[2] > 0.0 { (1.0, 0.25) } else { (0.5, 2.0) };
+
+            assert_eq!(seg.t_a, t_a);
+            assert_eq!(seg.t_d, t_d);
+        }
+
+        // The tightest of both limits is used when blending
+        assert_eq!(
+            Segment::new_asymmetric(
+                q0,
+                q0 * 2.0,
+                Coord::zeros(),
+                Coord::zeros(),
+                0.0,
+                &max,
+                &min
+            )
+            .lim(),
+            Lim {
+                vel: Coord::<3, f64>::new(5.0, 5.0, 2.0),
+                acc: Coord::<3, f64>::new(10.0, 10.0, 2.0),
+            }
+        );
+    }
+
+    #[test]
+    fn asymmetric_minor_axis() {
+        // Z is slow going up and moves the least, but still takes longer than X
+        let max = Lim {
+            vel: Coord::<3, f64>::new(5.0, 5.0, 0.5),
+            acc: Coord::<3, f64>::new(10.0, 10.0, 0.5),
+        };
+        let min = Lim {
+            vel: Coord::<3, f64>::new(-5.0, -5.0, -4.0),
+            acc: Coord::<3, f64>::new(-10.0, -10.0, -8.0),
+        };
+
+        let q0 = Coord::<3, f64>::zeros();
+
+        for q1 in [
+            Coord::<3, f64>::new(10.0, 0.0, 2.0),
+            Coord::<3, f64>::new(10.0, 0.0, -2.0),
+        ] {
+            let seg =
+                Segment::new_asymmetric(q0, q1, Coord::zeros(), Coord::zeros(), 0.0, &max, &min);
+
+            let (end, _) = seg.tp(seg.total_time).unwrap();
+
+            assert!((end.pos - q1).norm() < 1.0e-9);
+            assert!(end.vel.norm() < 1.0e-9);
+
+            // Slower than X could manage on its own, which is 2.5s
+            assert!(seg.total_time > 2.5, "{}", seg.total_time);
+
+            for i in 0..=1000 {
+                let (out, _) = seg.tp(seg.total_time * i as f64 / 1000.0).unwrap();
+
+                for axis in 0..3 {
+                    assert!(
+                        out.vel[axis] <= max.vel[axis] + 1.0e-9
+                            && out.vel[axis] >= min.vel[axis] - 1.0e-9,
+                        "{q1} {i} {}",
+                        out.vel
+                    );
+                    assert!(
+                        out.acc[axis] <= max.acc[axis] + 1.0e-9
+                            && out.acc[axis] >= min.acc[axis] - 1.0e-9,
+                        "{q1} {i} {}",
+                        out.acc
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn full_speed_throughout() {
+        let lim = Lim {
+            vel: Coord3::new(2.0, 2.0, 2.0),
+            acc: Coord3::new(5.0, 5.0, 5.0),
+        };
+
+        let q0 = Coord3::new(1.0, 0.0, 0.0);
+        let q1 = Coord3::new(11.0, 0.0, 0.0);
+        let v = Coord3::new(2.0, 0.0, 0.0);
+
+        // No accel or decel phases at all
+        let seg = Segment::new(q0, q1, v, v, 1.0, &lim);
+
+        assert_eq!(seg.total_time, 5.0);
+
+        for (t, pos) in [(1.0, q0), (3.5, Coord3::new(6.0, 0.0, 0.0)), (6.0, q1)] {
+            let (out, _) = seg.tp(t).unwrap();
+
+            assert!((out.pos - pos).norm() < 1.0e-5, "{t} {}", out.pos);
+            assert_eq!(out.vel, v);
+            assert_eq!(out.acc, Coord3::zeros());
+        }
+    }
+
+    #[test]
+    fn invalid_inputs() {
+        let lim = Lim {
+            vel: Coord3::new(2.0, 2.0, 2.0),
+            acc: Coord3::new(5.0, 5.0, 5.0),
+        };
+
+        let q1 = Coord3::new(10.0, 15.0, 0.0);
+
+        assert!(Segment::try_new(
+            Coord3::zeros(),
+            q1,
+            Coord3::zeros(),
+            Coord3::zeros(),
+            0.0,
+            &lim
+        )
+        .is_ok());
+
+        assert_eq!(
+            Segment::try_new(
+                Coord3::zeros(),
+                q1,
+                Coord3::new(f32::NAN, 0.0, 0.0),
+                Coord3::zeros(),
+                0.0,
+                &lim
+            )
+            .unwrap_err(),
+            PlanError::NonFinite
+        );
+        assert_eq!(
+            Segment::try_new(
+                Coord3::zeros(),
+                q1,
+                Coord3::zeros(),
+                Coord3::zeros(),
+                0.0,
+                &Lim {
+                    vel: Coord3::new(2.0, -2.0, 2.0),
+                    acc: lim.acc
+                }
+            )
+            .unwrap_err(),
+            PlanError::InvalidLimit
+        );
+        assert_eq!(
+            Segment::try_new(q1, q1, Coord3::zeros(), Coord3::zeros(), 0.0, &lim).unwrap_err(),
+            PlanError::ZeroDisplacement
+        );
+
+        // Z doesn't move, so can't change speed
+        assert_eq!(
+            Segment::try_new(
+                Coord3::zeros(),
+                q1,
+                Coord3::new(0.0, 0.0, 1.0),
+                Coord3::zeros(),
+                0.0,
+                &lim
+            )
+            .unwrap_err(),
+            PlanError::InfeasibleVelocities
+        );
+
+        // A lower limit on the wrong side of zero, or a zero limit like an `F0` feed
+        for (max, min) in [
+            (
+                lim,
+                Lim {
+                    vel: Coord3::new(-2.0, -2.0, 2.0),
+                    acc: -lim.acc,
+                },
+            ),
+            (
+                Lim {
+                    vel: Coord3::new(2.0, 0.0, 2.0),
+                    acc: lim.acc,
+                },
+                Lim {
+                    vel: -lim.vel,
+                    acc: -lim.acc,
+                },
+            ),
+        ] {
+            assert_eq!(
+                Segment::try_new_asymmetric(
+                    Coord3::zeros(),
+                    q1,
+                    Coord3::zeros(),
+                    Coord3::zeros(),
+                    0.0,
+                    &max,
+                    &min
+                )
+                .unwrap_err(),
+                PlanError::InvalidLimit
+            );
+        }
+    }
 }
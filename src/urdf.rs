@@ -0,0 +1,409 @@
+//! Serial chain robot arms loaded from URDF files.
+//!
+//! A blended [`Trajectory`] is planned in cartesian space as normal. [`Arm::sample`] then solves
+//! IK for samples along the path, and slows the path down wherever a joint would exceed its
+//! velocity or acceleration limit.
+
+use crate::{segments_blends::Trajectory, trapezoidal_non_zero_3d::Coord3};
+use core::fmt;
+use k::{nalgebra as na, InverseKinematicsSolver};
+use std::path::Path;
+
+/// Maximum number of passes when time scaling the path to fit the joint limits.
+const MAX_ITERATIONS: usize = 100;
+
+/// Relative amount a joint limit can be exceeded by before the path is slowed down.
+const LIMIT_TOLERANCE: f32 = 1.0e-3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UrdfError {
+    /// The URDF file couldn't be read or parsed.
+    Load(String),
+    /// The given end link doesn't exist in the URDF.
+    LinkNotFound(String),
+    /// The number of acceleration limits doesn't match the number of movable joints.
+    JointCount { expected: usize, got: usize },
+    /// IK has no solution for the trajectory position at time `t`.
+    Unreachable { t: f32 },
+}
+
+impl fmt::Display for UrdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Load(e) => write!(f, "failed to load URDF: {}", e),
+            Self::LinkNotFound(name) => write!(f, "link '{}' not found", name),
+            Self::JointCount { expected, got } => {
+                write!(f, "expected {} acceleration limits, got {}", expected, got)
+            }
+            Self::Unreachable { t } => write!(f, "no IK solution at t = {}", t),
+        }
+    }
+}
+
+impl std::error::Error for UrdfError {}
+
+/// A single time scaled sample along the path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JointSample {
+    /// Time of this sample after time scaling.
+    pub t: f32,
+    /// Time of this sample in the original trajectory.
+    pub path_t: f32,
+    /// Cartesian position.
+    pub pos: Coord3,
+    /// Joint positions.
+    pub joints: Vec<f32>,
+    /// Joint velocities.
+    pub vel: Vec<f32>,
+    /// Joint accelerations.
+    pub acc: Vec<f32>,
+}
+
+pub struct Arm {
+    arm: k::SerialChain<f32>,
+    solver: k::JacobianIkSolver<f32>,
+    /// Velocity limit of each movable joint, from the URDF.
+    pub vel_limits: Vec<f32>,
+    /// Acceleration limit of each movable joint. URDF doesn't include these so they're provided
+    /// by the user.
+    pub acc_limits: Vec<f32>,
+    /// Tool orientation held for the whole path, in the robot base frame. Defaults to the
+    /// orientation of the arm at its initial joint positions.
+    pub orientation: na::UnitQuaternion<f32>,
+    /// Multiplier to convert trajectory units into URDF units (meters), e.g. `0.001` for a
+    /// trajectory planned in millimeters.
+    pub scale: f32,
+    /// Position of the trajectory origin in the robot base frame, in URDF units.
+    pub origin: na::Vector3<f32>,
+    /// Which parts of the target pose IK must reach. Arms with fewer than 6 joints can't reach
+    /// every orientation so should ignore some rotation axes.
+    pub constraints: k::Constraints,
+}
+
+impl Arm {
+    /// Load the serial chain ending at `end_link` from a URDF file.
+    ///
+    /// `acc_limits` must contain one limit for every movable joint in the chain.
+    pub fn from_urdf_file(
+        path: impl AsRef<Path>,
+        end_link: &str,
+        acc_limits: Vec<f32>,
+    ) -> Result<Self, UrdfError> {
+        let robot = urdf_rs::read_file(path).map_err(|e| UrdfError::Load(e.to_string()))?;
+
+        Self::from_robot(&robot, end_link, acc_limits)
+    }
+
+    /// Like [`from_urdf_file`](Self::from_urdf_file), but parses the URDF from a string.
+    pub fn from_urdf_str(
+        urdf: &str,
+        end_link: &str,
+        acc_limits: Vec<f32>,
+    ) -> Result<Self, UrdfError> {
+        let robot = urdf_rs::read_from_string(urdf).map_err(|e| UrdfError::Load(e.to_string()))?;
+
+        Self::from_robot(&robot, end_link, acc_limits)
+    }
+
+    fn from_robot(
+        robot: &urdf_rs::Robot,
+        end_link: &str,
+        acc_limits: Vec<f32>,
+    ) -> Result<Self, UrdfError> {
+        let chain = k::Chain::<f32>::from(robot);
+
+        let end = chain
+            .find_link(end_link)
+            .ok_or_else(|| UrdfError::LinkNotFound(end_link.to_string()))?;
+
+        let arm = k::SerialChain::from_end(end);
+
+        // `k` doesn't keep joint velocity limits so read them from the URDF directly
+        let vel_limits = arm
+            .iter_joints()
+            .map(|joint| {
+                robot
+                    .joints
+                    .iter()
+                    .find(|urdf_joint| urdf_joint.name == joint.name)
+                    .map(|urdf_joint| urdf_joint.limit.velocity as f32)
+                    // Zero means no limit was given
+                    .filter(|vel| *vel > 0.0)
+                    .unwrap_or(f32::INFINITY)
+            })
+            .collect::<Vec<_>>();
+
+        if acc_limits.len() != vel_limits.len() {
+            return Err(UrdfError::JointCount {
+                expected: vel_limits.len(),
+                got: acc_limits.len(),
+            });
+        }
+
+        arm.update_transforms();
+
+        let orientation = arm.end_transform().rotation;
+
+        Ok(Self {
+            arm,
+            solver: k::JacobianIkSolver::default(),
+            vel_limits,
+            acc_limits,
+            orientation,
+            scale: 1.0,
+            origin: na::Vector3::zeros(),
+            constraints: k::Constraints::default(),
+        })
+    }
+
+    /// Number of movable joints.
+    pub fn dof(&self) -> usize {
+        self.vel_limits.len()
+    }
+
+    /// Solve IK for a cartesian position in trajectory coordinates. The arm's joints are left at
+    /// the solution, which is used as the starting point for the next solve.
+    fn solve(&self, pos: Coord3) -> Option<Vec<f32>> {
+        let target = na::Isometry3::from_parts(
+            na::Translation3::from(
+                self.origin + na::Vector3::new(pos.x, pos.y, pos.z) * self.scale,
+            ),
+            self.orientation,
+        );
+
+        self.solver
+            .solve_with_constraints(&self.arm, &target, &self.constraints)
+            .ok()?;
+
+        Some(self.arm.joint_positions())
+    }
+
+    /// Sample the trajectory every `dt` seconds, solve IK for each sample and time scale the
+    /// result so no joint exceeds its velocity or acceleration limit.
+    ///
+    /// IK for the first sample starts from the arm's current joint positions, and each sample after
+    /// that starts from the previous solution.
+    pub fn sample(&self, trajectory: &Trajectory, dt: f32) -> Result<Vec<JointSample>, UrdfError> {
+        let num_samples = (trajectory.total_time / dt).ceil() as usize;
+
+        let mut path = Vec::with_capacity(num_samples + 1);
+        let mut joints = Vec::with_capacity(num_samples + 1);
+
+        for i in 0..=num_samples {
+            let t = (i as f32 * dt).min(trajectory.total_time);
+
+            // Clamping to the end time can produce a duplicate sample
+            if path.last().is_some_and(|(last_t, _)| *last_t >= t) {
+                continue;
+            }
+
+            let Some((out, _is_arc)) = trajectory.tp(t) else {
+                continue;
+            };
+
+            joints.push(self.solve(out.pos).ok_or(UrdfError::Unreachable { t })?);
+            path.push((t, out.pos));
+        }
+
+        let durations = self.time_scale(
+            &joints,
+            path.windows(2).map(|pair| pair[1].0 - pair[0].0).collect(),
+        );
+
+        let mut t = 0.0;
+
+        let samples = (0..path.len())
+            .map(|i| {
+                if i > 0 {
+                    t += durations[i - 1];
+                }
+
+                let (path_t, pos) = path[i];
+
+                // Joint velocity over the interval before and after this sample
+                let interval_vel = |from: usize, joint: usize| {
+                    (joints[from + 1][joint] - joints[from][joint]) / durations[from]
+                };
+
+                let (vel, acc): (Vec<f32>, Vec<f32>) = (0..self.dof())
+                    .map(|joint| {
+                        let before = i.checked_sub(1).map(|prev| interval_vel(prev, joint));
+                        let after = (i + 1 < path.len()).then(|| interval_vel(i, joint));
+
+                        match (before, after) {
+                            (Some(before), Some(after)) => {
+                                let span = durations[i - 1] + durations[i];
+
+                                (
+                                    (joints[i + 1][joint] - joints[i - 1][joint]) / span,
+                                    2.0 * (after - before) / span,
+                                )
+                            }
+                            (Some(vel), None) | (None, Some(vel)) => (vel, 0.0),
+                            (None, None) => (0.0, 0.0),
+                        }
+                    })
+                    .unzip();
+
+                JointSample {
+                    t,
+                    path_t,
+                    pos,
+                    joints: joints[i].clone(),
+                    vel,
+                    acc,
+                }
+            })
+            .collect();
+
+        Ok(samples)
+    }
+
+    /// Stretch the duration of each interval between joint samples until every joint is within
+    /// its limits.
+    fn time_scale(&self, joints: &[Vec<f32>], mut durations: Vec<f32>) -> Vec<f32> {
+        let dof = self.dof();
+
+        for _ in 0..MAX_ITERATIONS {
+            let mut changed = false;
+
+            // Velocity over each interval scales with 1 / duration
+            for (i, duration) in durations.iter_mut().enumerate() {
+                let stretch = (0..dof)
+                    .map(|joint| {
+                        let vel = (joints[i + 1][joint] - joints[i][joint]) / *duration;
+
+                        vel.abs() / self.vel_limits[joint]
+                    })
+                    .fold(1.0f32, f32::max);
+
+                if stretch > 1.0 + LIMIT_TOLERANCE {
+                    *duration *= stretch;
+                    changed = true;
+                }
+            }
+
+            // Acceleration at each sample scales with 1 / duration^2 of the intervals either side
+            for i in 1..durations.len() {
+                let (before, after) = (durations[i - 1], durations[i]);
+
+                let stretch = (0..dof)
+                    .map(|joint| {
+                        let vel_before = (joints[i][joint] - joints[i - 1][joint]) / before;
+                        let vel_after = (joints[i + 1][joint] - joints[i][joint]) / after;
+                        let acc = 2.0 * (vel_after - vel_before) / (before + after);
+
+                        (acc.abs() / self.acc_limits[joint]).sqrt()
+                    })
+                    .fold(1.0f32, f32::max);
+
+                if stretch > 1.0 + LIMIT_TOLERANCE {
+                    durations[i - 1] *= stretch;
+                    durations[i] *= stretch;
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        durations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Planar two link arm with 1m links, rotating about Z.
+    const TWO_LINK: &str = r#"<?xml version="1.0"?>
+<robot name="two_link">
+  <link name="base" />
+  <link name="upper" />
+  <link name="lower" />
+  <link name="tool" />
+  <joint name="shoulder" type="revolute">
+    <parent link="base" />
+    <child link="upper" />
+    <axis xyz="0 0 1" />
+    <limit lower="-3.14" upper="3.14" effort="10" velocity="1.0" />
+  </joint>
+  <joint name="elbow" type="revolute">
+    <parent link="upper" />
+    <child link="lower" />
+    <origin xyz="1 0 0" />
+    <axis xyz="0 0 1" />
+    <limit lower="-3.14" upper="3.14" effort="10" velocity="1.0" />
+  </joint>
+  <joint name="tool_joint" type="fixed">
+    <parent link="lower" />
+    <child link="tool" />
+    <origin xyz="1 0 0" />
+  </joint>
+</robot>
+"#;
+
+    fn arm() -> Arm {
+        let arm = Arm::from_urdf_str(TWO_LINK, "tool", vec![2.0, 2.0]).unwrap();
+
+        // Start with a bent elbow so IK doesn't start at a singularity
+        arm.arm.set_joint_positions(&[0.0, 1.0]).unwrap();
+        arm.arm.update_transforms();
+
+        arm
+    }
+
+    #[test]
+    fn load() {
+        let arm = arm();
+
+        assert_eq!(arm.dof(), 2);
+        assert_eq!(arm.vel_limits, vec![1.0, 1.0]);
+    }
+
+    #[test]
+    fn time_scaled_within_limits() {
+        let mut arm = arm();
+
+        // Trajectory is planned in millimeters
+        arm.scale = 0.001;
+        // Planar arm can only control its X and Y position
+        arm.constraints = k::Constraints {
+            position_z: false,
+            rotation_x: false,
+            rotation_y: false,
+            rotation_z: false,
+            ..Default::default()
+        };
+
+        let mut trajectory = Trajectory::new();
+
+        trajectory.limits.vel = Coord3::new(500.0, 500.0, 500.0);
+        trajectory.limits.acc = Coord3::new(2000.0, 2000.0, 2000.0);
+
+        trajectory
+            .push_point(Coord3::new(1200.0, 800.0, 0.0))
+            .unwrap();
+        trajectory
+            .push_point(Coord3::new(1200.0, 1200.0, 0.0))
+            .unwrap();
+        trajectory
+            .push_point(Coord3::new(800.0, 1200.0, 0.0))
+            .unwrap();
+
+        let samples = arm.sample(&trajectory, 0.01).unwrap();
+
+        let last = samples.last().unwrap();
+
+        // Path had to be slowed down
+        assert!(last.t > last.path_t);
+
+        for sample in samples.iter() {
+            for joint in 0..arm.dof() {
+                assert!(sample.vel[joint].abs() <= arm.vel_limits[joint] * 1.01);
+            }
+        }
+    }
+}